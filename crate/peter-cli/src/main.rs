@@ -31,10 +31,34 @@ use {
     peter::{
         Error,
         GEFOLGE,
+        announce::{
+            self,
+            Announcements,
+        },
         commands,
-        config::Config,
+        config::{
+            Config,
+            Feature,
+        },
+        emoji,
+        log_channel,
+        paths::Paths,
+        presence::Presences,
+        reaction_listeners::{
+            self,
+            ReactionListeners,
+        },
+        reactions,
+        setup,
+        stats::{
+            self,
+            Stats,
+        },
         twitch,
-        user_list,
+        user_list::{
+            self,
+            UserList,
+        },
         voice::{
             self,
             VoiceStates,
@@ -69,22 +93,34 @@ impl EventHandler for Handler {
         }
     }
 
-    async fn guild_ban_addition(&self, _: Context, guild_id: GuildId, user: User) {
+    async fn guild_ban_addition(&self, ctx: Context, guild_id: GuildId, user: User) {
         println!("User {} was banned from {}", user.name, guild_id);
+        if let Err(e) = log_channel::post(&ctx, guild_id, MessageBuilder::default().push_safe(user.tag()).push(" wurde gebannt")).await {
+            eprintln!("failed to post ban to log channel: {} ({:?})", e, e); // best effort, the user list below must be updated regardless
+        }
         if guild_id != GEFOLGE { return; }
-        user_list::remove(user).await.expect("failed to remove banned user from user list");
+        UserList::get(&ctx).await.remove(user.id).await.expect("failed to remove banned user from user list");
     }
 
     async fn guild_ban_removal(&self, ctx: Context, guild_id: GuildId, user: User) {
         println!("User {} was unbanned from {}", user.name, guild_id);
+        if let Err(e) = log_channel::post(&ctx, guild_id, MessageBuilder::default().push_safe(user.tag()).push(" wurde entbannt")).await {
+            eprintln!("failed to post unban to log channel: {} ({:?})", e, e); // best effort, the user list below must be updated regardless
+        }
         if guild_id != GEFOLGE { return; }
-        user_list::add(guild_id.member(ctx, user).await.expect("failed to get unbanned guild member"), None).await.expect("failed to add unbanned user to user list");
+        UserList::get(&ctx).await.add(guild_id.member(&ctx, user).await.expect("failed to get unbanned guild member"), None).await.expect("failed to add unbanned user to user list");
     }
 
     async fn guild_create(&self, ctx: Context, guild: Guild, _: bool) {
         println!("Connected to {}", guild.name);
         if guild.id != GEFOLGE { return; }
-        user_list::set(guild.members.values().cloned()).await.expect("failed to initialize user list");
+        let paths = ctx.data.read().await.get::<Paths>().expect("missing paths").clone();
+        let mut members = Vec::with_capacity(guild.members.len());
+        for member in guild.members.values() {
+            members.push(user_list::backfill_join_date(&ctx, member.clone()).await.expect("failed to get guild member join date"));
+        }
+        UserList::get(&ctx).await.set(members).await.expect("failed to initialize user list");
+        ctx.data.write().await.get_mut::<Presences>().expect("missing presences").record_all(&paths, guild.presences.values()).await.expect("failed to initialize presence snapshots");
         let VoiceStates(mut chan_map) = VoiceStates::default();
         for (user_id, voice_state) in guild.voice_states {
             if let Some(channel_id) = voice_state.channel_id {
@@ -102,38 +138,54 @@ impl EventHandler for Handler {
         let mut data = ctx.data.write().await;
         data.insert::<VoiceStates>(VoiceStates(chan_map));
         let chan_map = data.get::<VoiceStates>().expect("missing voice states map");
-        voice::dump_info(chan_map).await.expect("failed to update BitBar plugin");
+        voice::dump_info(&paths, chan_map).await.expect("failed to update BitBar plugin");
     }
 
-    async fn guild_member_addition(&self, _: Context, guild_id: GuildId, member: Member) {
+    async fn guild_member_addition(&self, ctx: Context, guild_id: GuildId, member: Member) {
         println!("User {} joined {}", member.user.name, guild_id);
         if guild_id != GEFOLGE { return; }
-        user_list::add(member, None).await.expect("failed to add new guild member to user list");
+        UserList::get(&ctx).await.add(member, None).await.expect("failed to add new guild member to user list");
     }
 
-    async fn guild_member_removal(&self, _: Context, guild_id: GuildId, user: User, _: Option<Member>) {
+    async fn guild_member_removal(&self, ctx: Context, guild_id: GuildId, user: User, _: Option<Member>) {
         println!("User {} left {}", user.name, guild_id);
+        werewolf::handle_member_removal(&ctx, guild_id, user.id).await.expect("failed to handle werewolf player leaving");
         if guild_id != GEFOLGE { return; }
-        user_list::remove(user).await.expect("failed to remove removed guild member from user list");
+        UserList::get(&ctx).await.remove(user.id).await.expect("failed to remove removed guild member from user list");
     }
 
-    async fn guild_member_update(&self, _: Context, _: Option<Member>, member: Member) {
+    async fn guild_member_update(&self, ctx: Context, _: Option<Member>, member: Member) {
         println!("Member data for {} updated", member.user.name);
         if member.guild_id != GEFOLGE { return; }
-        user_list::update(member).await.expect("failed to update guild member info in user list");
+        let member = user_list::backfill_join_date(&ctx, member).await.expect("failed to get guild member join date");
+        UserList::get(&ctx).await.update(member).await.expect("failed to update guild member info in user list");
     }
 
-    async fn guild_members_chunk(&self, _: Context, chunk: GuildMembersChunkEvent) {
+    async fn guild_members_chunk(&self, ctx: Context, chunk: GuildMembersChunkEvent) {
         println!("Received chunk of members for guild {}", chunk.guild_id);
         if chunk.guild_id != GEFOLGE { return; }
+        let user_list = UserList::get(&ctx).await;
         for member in chunk.members.values() {
-            user_list::add(member.clone(), None).await.expect("failed to add chunk of guild members to user list");
+            let member = user_list::backfill_join_date(&ctx, member.clone()).await.expect("failed to get guild member join date");
+            user_list.add(member, None).await.expect("failed to add chunk of guild members to user list");
         }
     }
 
     async fn message(&self, mut ctx: Context, msg: Message) { //TODO move to normal_message in the framework?
         if msg.author.bot { return; } // ignore bots to prevent message loops
-        if ctx.data.read().await.get::<Config>().expect("missing config").werewolf.iter().any(|(_, conf)| conf.text_channel == msg.channel_id) {
+        if let Some(guild_id) = msg.guild_id {
+            let paths = ctx.data.read().await.get::<Paths>().expect("missing paths").clone();
+            let emoji_metrics = emoji::message_metrics(&paths, guild_id, &msg.content); // scan before locking the typemap for writing
+            let mut data = ctx.data.write().await;
+            let stats = data.get_mut::<Stats>().expect("missing stats");
+            stats.increment(format!("messages:{}", guild_id));
+            for metric in emoji_metrics {
+                stats.increment(metric);
+            }
+        }
+        reactions::handle_message(&ctx, &msg).await.expect("failed to handle random reactions");
+        if Config::read(&ctx).await.guilds.values().flat_map(|guild_config| &guild_config.werewolf).any(|conf| conf.is_game_channel(msg.channel_id)) {
+            werewolf::handle_message(&ctx, &msg).await.expect("failed to handle werewolf message");
             if let Some(action) = werewolf::parse_action(&mut ctx, msg.author.id, &msg.content).await {
                 match async move { action }.and_then(|action| werewolf::handle_action(&mut ctx, &msg, action)).await {
                     Ok(()) => {} // reaction is posted in handle_action
@@ -144,12 +196,41 @@ impl EventHandler for Handler {
         }
     }
 
+    async fn presence_update(&self, ctx: Context, update: PresenceUpdateEvent) {
+        if let Some(ref user) = update.presence.user {
+            // username changes apply to all guilds, so this also handles presence updates from other guilds
+            UserList::get(&ctx).await.update_user(user).await.expect("failed to update user info in user list");
+        }
+        if update.guild_id != Some(GEFOLGE) { return; }
+        let mut data = ctx.data.write().await;
+        let paths = data.get::<Paths>().expect("missing paths").clone();
+        data.get_mut::<Presences>().expect("missing presences").update(&paths, &update.presence).await.expect("failed to update presence snapshots");
+    }
+
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        reaction_listeners::dispatch(&ctx, reaction, reaction_listeners::Kind::Add).await.expect("failed to handle reaction");
+    }
+
+    async fn reaction_remove(&self, ctx: Context, reaction: Reaction) {
+        reaction_listeners::dispatch(&ctx, reaction, reaction_listeners::Kind::Remove).await.expect("failed to handle reaction removal");
+    }
+
     async fn voice_state_update(&self, ctx: Context, guild_id: Option<GuildId>, _old: Option<VoiceState>, new: VoiceState) {
         println!("Voice states in guild {:?} updated", guild_id);
+        if let Some(guild_id) = guild_id {
+            werewolf::voice_state_update(&ctx, guild_id, &new).await.expect("failed to update werewolf voice mutes");
+        }
         if guild_id.map_or(true, |gid| gid != GEFOLGE) { return; } //TODO make sure this works, i.e. serenity never passes None for GEFOLGE
         let user = new.user_id.to_user(&ctx).await.expect("failed to get user info");
+        let guild_channels = GEFOLGE.to_guild_cached(&ctx).await.map(|guild| guild.channels).unwrap_or_default();
         let mut data = ctx.data.write().await;
-        let ignored_channels = data.get::<Config>().expect("missing config").channels.ignored.clone();
+        let paths = data.get::<Paths>().expect("missing paths").clone();
+        let ignored_channels = {
+            let config = data.get::<Config>().expect("missing config").read().await;
+            // categories and patterns can only be checked for cached channels, but explicitly ignored channels are always ignored
+            let explicit = config.guild(GEFOLGE).map(|guild_config| guild_config.channels.ignored.clone()).unwrap_or_default();
+            guild_channels.values().filter(|channel| config.is_ignored(GEFOLGE, channel)).map(|channel| channel.id).chain(explicit).collect::<Vec<_>>()
+        };
         let voice_states = data.get_mut::<VoiceStates>().expect("missing voice states map");
         let VoiceStates(ref mut chan_map) = voice_states;
         let was_empty = chan_map.iter().all(|(channel_id, (_, members))| members.is_empty() || ignored_channels.contains(channel_id));
@@ -175,15 +256,17 @@ impl EventHandler for Handler {
             }
         }
         let is_empty = chan_map.iter().all(|(channel_id, (_, members))| members.is_empty() || ignored_channels.contains(channel_id));
-        voice::dump_info(voice_states).await.expect("failed to update voice state dump");
-        if was_empty && !is_empty {
-            let config = data.get::<Config>().expect("missing config");
-            let mut msg_builder = MessageBuilder::default();
-            msg_builder.push("Discord Party? ");
-            MessageBuilder::mention(&mut msg_builder, &user);
-            msg_builder.push(" ist jetzt im voice channel ");
-            msg_builder.mention(&chan_id.unwrap());
-            config.channels.voice.say(&ctx, msg_builder).await.expect("failed to send channel message"); //TODO don't prefix channel name with `#`
+        voice::dump_info(&paths, voice_states).await.expect("failed to update voice state dump");
+        let config = data.get::<Config>().expect("missing config").read().await;
+        if was_empty && !is_empty && config.feature_enabled(GEFOLGE, Feature::VoiceNotifications) {
+            if let Some(voice_channel) = config.guild(GEFOLGE).and_then(|guild_config| guild_config.channels.voice) {
+                let mut msg_builder = MessageBuilder::default();
+                msg_builder.push("Discord Party? ");
+                MessageBuilder::mention(&mut msg_builder, &user);
+                msg_builder.push(" ist jetzt im voice channel ");
+                msg_builder.mention(&chan_id.unwrap());
+                voice_channel.say(&ctx, msg_builder).await.expect("failed to send channel message"); //TODO don't prefix channel name with `#`
+            }
         }
     }
 }
@@ -192,16 +275,32 @@ impl EventHandler for Handler {
 async fn main() -> Result<(), Error> {
     let mut args = env::args().peekable();
     let _ = args.next(); // ignore executable name
-    if args.peek().is_some() {
+    if args.peek().map_or(false, |arg| arg == "init-config") {
+        let path = Config::init(&Paths::new()).await?;
+        println!("default config written to {}, fill in the placeholders before starting the bot", path.display());
+    } else if args.peek().map_or(false, |arg| arg == "config-schema") {
+        println!("{}", serde_json::to_string_pretty(&Config::json_schema())?);
+    } else if args.peek().is_some() {
         println!("{}", peter::ipc::send(args)?);
     } else {
         // read config
-        let config = Config::new().await?;
+        let mut paths = Paths::new();
+        let config = Config::new(&paths).await?;
+        paths.apply_config(&config.paths);
+        let presences = Presences::load(&paths).await?;
+        let stats = Stats::load(&paths).await?;
+        let werewolf_games = werewolf::GameState::load_all(&paths).await?;
+        let user_list = UserList::open(&config.user_list, &paths)?;
         let (handler, rx) = Handler::new();
         let ctx_fut_ipc = rx.clone();
+        let ctx_fut_webhook = rx.clone();
+        let ctx_fut_stats = rx.clone();
+        let ctx_fut_user_list = rx.clone();
+        let ctx_fut_werewolf = rx.clone();
+        peter::report_panics(rx.clone());
         let ctx_fut_twitch = rx;
-        let owners = iter::once(Http::new_with_token(&config.peter.bot_token).get_current_application_info().await?.owner.id).collect();
-        let mut client = Client::builder(&config.peter.bot_token)
+        let owners = iter::once(Http::new_with_token(&config.bot_token()).get_current_application_info().await?.owner.id).collect();
+        let mut client = Client::builder(&config.bot_token())
             .event_handler(handler)
             .intents(
                 GatewayIntents::DIRECT_MESSAGES
@@ -212,6 +311,7 @@ async fn main() -> Result<(), Error> {
                 | GatewayIntents::GUILD_BANS
                 | GatewayIntents::GUILD_VOICE_STATES
                 | GatewayIntents::GUILD_MESSAGES
+                | GatewayIntents::GUILD_MESSAGE_REACTIONS
             )
             .framework(StandardFramework::new()
                 .configure(|c| c
@@ -220,9 +320,12 @@ async fn main() -> Result<(), Error> {
                     .no_dm_prefix(true) // allow /msg @peter command (also allows game actions in DMs and “did not understand DM” error messages to work)
                     .on_mention(Some(UserId(365936493539229699))) // allow @peter command
                     .owners(owners)
-                    .prefix("!") // allow !command
+                    .dynamic_prefix(|ctx, msg| Box::pin(async move { // allow !command, or the server's configured prefix
+                        Some(Config::read(ctx).await.prefix(msg.guild_id).to_owned())
+                    }))
                 )
-                .after(|_, _, command_name, result| Box::pin(async move {
+                .after(|ctx, _, command_name, result| Box::pin(async move {
+                    ctx.data.write().await.get_mut::<Stats>().expect("missing stats").increment(format!("command:{}", command_name));
                     if let Err(why) = result {
                         println!("{}: Command '{}' returned error {:?}", Utc::now().format("%Y-%m-%d %H:%M:%S"), command_name, why);
                     }
@@ -243,15 +346,27 @@ async fn main() -> Result<(), Error> {
                     }
                 }))
                 .help(&commands::HELP_COMMAND)
-                .group(&commands::GROUP)
+                .group(&commands::GENERAL_GROUP)
+                .group(&setup::SETUP_GROUP)
+                .group(&werewolf::WEREWOLF_GROUP)
             )
             .await?;
         {
             let mut data = client.data.write().await;
             data.insert::<ShardManagerContainer>(Arc::clone(&client.shard_manager));
-            data.insert::<Config>(config);
+            data.insert::<Announcements>(HashMap::default());
+            data.insert::<Config>(Arc::new(RwLock::new(config)));
+            data.insert::<Paths>(paths);
+            data.insert::<Presences>(presences);
+            let mut reaction_listeners = ReactionListeners::default();
+            announce::register(&mut reaction_listeners);
+            emoji::register(&mut reaction_listeners);
+            werewolf::register(&mut reaction_listeners);
+            data.insert::<ReactionListeners>(reaction_listeners);
+            data.insert::<Stats>(stats);
+            data.insert::<UserList>(user_list);
             data.insert::<VoiceStates>(VoiceStates::default());
-            data.insert::<werewolf::GameState>(HashMap::default());
+            data.insert::<werewolf::GameState>(werewolf_games);
         }
         // listen for IPC commands
         tokio::spawn(async move {
@@ -263,6 +378,31 @@ async fn main() -> Result<(), Error> {
                 }
             }
         });
+        // listen for webhooks
+        tokio::spawn(async move {
+            if let Err(e) = peter::webhook::listen(ctx_fut_webhook.clone()).await {
+                eprintln!("{}", e);
+                peter::notify_thread_crash(ctx_fut_webhook, format!("webhook"), e, None).await;
+            }
+        });
+        // roll up and save stats
+        tokio::spawn(async move {
+            let e = match stats::maintain(ctx_fut_stats.clone()).await {
+                Ok(never) => match never {},
+                Err(e) => e,
+            };
+            eprintln!("{}", e);
+            peter::notify_thread_crash(ctx_fut_stats, format!("stats"), e, None).await;
+        });
+        // periodically resync the member list
+        tokio::spawn(user_list::resync(ctx_fut_user_list));
+        // continue werewolf games interrupted by a restart
+        tokio::spawn(async move {
+            if let Err(e) = werewolf::resume(ctx_fut_werewolf.clone()).await {
+                eprintln!("{}", e);
+                peter::notify_thread_crash(ctx_fut_werewolf, format!("Werwölfe"), e, None).await;
+            }
+        });
         // check Twitch stream status
         tokio::spawn(async move {
             let mut last_crash = Instant::now();
@@ -40,6 +40,26 @@ fn user_to_id(user: &PyAny) -> PyResult<UserId> {
         .map_err(|e| CommandError::new_err(e.to_string()))
 }
 
+#[pyfunction] fn export_former_members(path: String) -> PyResult<()> {
+    peter_ipc::export_former_members(path)
+        .map_err(|e| CommandError::new_err(e.to_string()))
+}
+
+#[pyfunction] fn export_members(format: String, path: String) -> PyResult<()> {
+    peter_ipc::export_members(format, path)
+        .map_err(|e| CommandError::new_err(e.to_string()))
+}
+
+#[pyfunction] fn export_seasons(path: String) -> PyResult<()> {
+    peter_ipc::export_seasons(path)
+        .map_err(|e| CommandError::new_err(e.to_string()))
+}
+
+#[pyfunction] fn export_werewolf_history(path: String) -> PyResult<()> {
+    peter_ipc::export_werewolf_history(path)
+        .map_err(|e| CommandError::new_err(e.to_string()))
+}
+
 #[pyfunction] fn msg(user_id: &PyAny, msg: String) -> PyResult<()> {
     peter_ipc::msg(user_to_id(user_id)?, msg)
         .map_err(|e| CommandError::new_err(e.to_string()))
@@ -50,18 +70,34 @@ fn user_to_id(user: &PyAny) -> PyResult<UserId> {
         .map_err(|e| CommandError::new_err(e.to_string()))
 }
 
+#[pyfunction] fn reload_config(section: String) -> PyResult<()> {
+    peter_ipc::reload_config(section)
+        .map_err(|e| CommandError::new_err(e.to_string()))
+}
+
 #[pyfunction] fn set_display_name(user_id: &PyAny, new_display_name: String) -> PyResult<()> {
     peter_ipc::set_display_name(user_to_id(user_id)?, new_display_name)
         .map_err(|e| CommandError::new_err(e.to_string()))
 }
 
+#[pyfunction] fn verify_members(fix: bool) -> PyResult<()> {
+    peter_ipc::verify_members(fix)
+        .map_err(|e| CommandError::new_err(e.to_string()))
+}
+
 #[pymodule] fn peter(_: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_wrapped(wrap_pyfunction!(escape))?;
     //TODO make sure that all IPC commands are listed below
     m.add_wrapped(wrap_pyfunction!(add_role))?;
     m.add_wrapped(wrap_pyfunction!(channel_msg))?;
+    m.add_wrapped(wrap_pyfunction!(export_former_members))?;
+    m.add_wrapped(wrap_pyfunction!(export_members))?;
+    m.add_wrapped(wrap_pyfunction!(export_seasons))?;
+    m.add_wrapped(wrap_pyfunction!(export_werewolf_history))?;
     m.add_wrapped(wrap_pyfunction!(msg))?;
     m.add_wrapped(wrap_pyfunction!(quit))?;
+    m.add_wrapped(wrap_pyfunction!(reload_config))?;
     m.add_wrapped(wrap_pyfunction!(set_display_name))?;
+    m.add_wrapped(wrap_pyfunction!(verify_members))?;
     Ok(())
 }
@@ -0,0 +1,206 @@
+//! Announcements which members have to acknowledge, e.g. code of conduct confirmations for events.
+
+use {
+    std::{
+        collections::{
+            HashMap,
+            HashSet,
+        },
+        pin::Pin,
+        time::Duration,
+    },
+    futures::future::Future,
+    schemars::JsonSchema,
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    serenity::{
+        framework::standard::{
+            Args,
+            CommandResult,
+            macros::command,
+        },
+        model::prelude::*,
+        prelude::*,
+        utils::MessageBuilder,
+    },
+    tokio::time::sleep,
+    crate::{
+        Error,
+        config::Config as BotConfig,
+        log_channel,
+        reaction_listeners::{
+            self,
+            Filter,
+            ReactionListeners,
+        },
+    },
+};
+
+/// The reaction members use to acknowledge an announcement.
+const ACK_EMOJI: char = '✅';
+
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    /// How long to wait before reminding members who haven't acknowledged an announcement.
+    #[serde(default = "default_reminder_delay")]
+    reminder_delay_hours: u64,
+}
+
+fn default_reminder_delay() -> u64 { 24 }
+
+impl Default for Config {
+    fn default() -> Config {
+        Config { reminder_delay_hours: default_reminder_delay() }
+    }
+}
+
+#[derive(Clone)]
+pub struct Announcement {
+    guild_id: GuildId,
+    /// Only members with this role have to acknowledge the announcement. If `None`, all members do.
+    role: Option<RoleId>,
+    acknowledged: HashSet<UserId>,
+}
+
+/// `typemap` key for announcements which are still waiting for acknowledgments. Not persisted across restarts.
+pub struct Announcements;
+
+impl TypeMapKey for Announcements {
+    type Value = HashMap<MessageId, Announcement>;
+}
+
+fn message_link(guild_id: GuildId, channel_id: ChannelId, message_id: MessageId) -> String {
+    format!("https://discord.com/channels/{}/{}/{}", guild_id, channel_id, message_id)
+}
+
+/// The members who have to acknowledge an announcement.
+async fn targets(ctx: &Context, announcement: &Announcement) -> serenity::Result<Vec<Member>> {
+    Ok(announcement.guild_id.members(ctx, None, None).await? //TODO make sure all members are checked
+        .into_iter()
+        .filter(|member| !member.user.bot && announcement.role.map_or(true, |role| member.roles.contains(&role)))
+        .collect())
+}
+
+/// Posts an announcement.
+///
+/// With `--ack`, members have to confirm that they read the announcement by reacting with ✅. Members who haven't done so after a while get a reminder via DM, and the log channel is notified once everyone has confirmed. With `--role @Rolle`, only members with that role have to confirm.
+#[command]
+#[usage = "[--ack] [--role <Rolle>] <Text>"]
+#[example = "--ack Am Samstag ist Server-Wartung."]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+pub async fn announce(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.expect("not in guild but only_in(guilds) passed");
+    let mut ack = false;
+    let mut role = None;
+    loop {
+        match args.current() {
+            Some("--ack") => { ack = true; args.advance(); }
+            Some("--role") => {
+                args.advance();
+                match args.single::<RoleId>() {
+                    Ok(role_id) => role = Some(role_id),
+                    Err(_) => {
+                        msg.reply(ctx, "bitte gib nach `--role` eine Rolle an").await?;
+                        return Ok(())
+                    }
+                }
+            }
+            _ => break,
+        }
+    }
+    let text = args.rest().trim();
+    if text.is_empty() {
+        msg.reply(ctx, "bitte gib den Text der Ankündigung an").await?;
+        return Ok(())
+    }
+    let announcement = msg.channel_id.say(ctx, text).await?;
+    if !ack { return Ok(()) }
+    announcement.react(ctx, ACK_EMOJI).await?;
+    let delay = {
+        let mut data = ctx.data.write().await;
+        let delay = data.get::<BotConfig>().expect("missing config").read().await.guild(guild_id).map_or(default_reminder_delay(), |guild_config| guild_config.announcements.reminder_delay_hours);
+        data.get_mut::<Announcements>().expect("missing announcements").insert(announcement.id, Announcement {
+            guild_id, role,
+            acknowledged: HashSet::default(),
+        });
+        delay
+    };
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        sleep(Duration::from_secs(delay * 60 * 60)).await;
+        if let Err(e) = remind(&ctx, announcement.channel_id, announcement.id).await {
+            eprintln!("failed to send announcement reminders: {} ({:?})", e, e);
+        }
+    });
+    Ok(())
+}
+
+/// Reminds members who haven't acknowledged the given announcement yet, and reports the current status to the log channel.
+async fn remind(ctx: &Context, channel_id: ChannelId, message_id: MessageId) -> Result<(), Error> {
+    let announcement = if let Some(announcement) = ctx.data.read().await.get::<Announcements>().expect("missing announcements").get(&message_id) { announcement.clone() } else { return Ok(()) }; // already acknowledged by everyone
+    let targets = targets(ctx, &announcement).await?;
+    let link = message_link(announcement.guild_id, channel_id, message_id);
+    let mut reminded = 0;
+    for member in &targets {
+        if announcement.acknowledged.contains(&member.user.id) { continue }
+        let dm = member.user.dm(ctx, |m| m.content(format!("Bitte bestätige, dass du diese Ankündigung gelesen hast, indem du mit {} reagierst: {}", ACK_EMOJI, link))).await;
+        if dm.is_ok() { reminded += 1 } // members may have DMs disabled
+    }
+    log_channel::post(ctx, announcement.guild_id, format!("{} von {} haben die Ankündigung {} bestätigt, {} wurden erinnert.", targets.len() - targets.iter().filter(|member| !announcement.acknowledged.contains(&member.user.id)).count(), targets.len(), link, reminded)).await?;
+    Ok(())
+}
+
+/// Subscribes to ✅ reactions, which are used to acknowledge announcements.
+pub fn register(listeners: &mut ReactionListeners) {
+    listeners.subscribe(Filter { emoji: Some(ReactionType::Unicode(ACK_EMOJI.to_string())), ..Filter::default() }, on_reaction);
+}
+
+fn on_reaction(ctx: Context, reaction: Reaction, kind: reaction_listeners::Kind) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send>> {
+    Box::pin(async move {
+        match kind {
+            reaction_listeners::Kind::Add => handle_reaction(&ctx, &reaction).await,
+            reaction_listeners::Kind::Remove => handle_reaction_removal(&ctx, &reaction).await,
+        }
+    })
+}
+
+/// Records an acknowledgment if the reaction is on an announcement, and reports to the log channel once everyone has acknowledged it.
+async fn handle_reaction(ctx: &Context, reaction: &Reaction) -> Result<(), Error> {
+    let user_id = if let Some(user_id) = reaction.user_id { user_id } else { return Ok(()) };
+    if user_id == ctx.cache.current_user_id().await { return Ok(()) } // ignore the bot's own reaction
+    let announcement = {
+        let mut data = ctx.data.write().await;
+        let announcement = if let Some(announcement) = data.get_mut::<Announcements>().expect("missing announcements").get_mut(&reaction.message_id) { announcement } else { return Ok(()) };
+        announcement.acknowledged.insert(user_id);
+        announcement.clone()
+    };
+    // fetching the members can take a while, so don't hold the lock meanwhile
+    let targets = targets(ctx, &announcement).await?;
+    {
+        let mut data = ctx.data.write().await;
+        let announcements = data.get_mut::<Announcements>().expect("missing announcements");
+        // the acknowledgments may have changed in the meantime, and another reaction may have completed the announcement already
+        let complete = announcements.get(&reaction.message_id).map_or(false, |announcement| targets.iter().all(|member| announcement.acknowledged.contains(&member.user.id)));
+        if !complete { return Ok(()) }
+        announcements.remove(&reaction.message_id);
+    }
+    log_channel::post(ctx, announcement.guild_id, MessageBuilder::default()
+        .push("Alle haben die Ankündigung ")
+        .push(message_link(announcement.guild_id, reaction.channel_id, reaction.message_id))
+        .push(" bestätigt.")
+    ).await?;
+    Ok(())
+}
+
+/// Withdraws an acknowledgment if the reaction was on an announcement which is still waiting for acknowledgments.
+async fn handle_reaction_removal(ctx: &Context, reaction: &Reaction) -> Result<(), Error> {
+    let user_id = if let Some(user_id) = reaction.user_id { user_id } else { return Ok(()) };
+    if let Some(announcement) = ctx.data.write().await.get_mut::<Announcements>().expect("missing announcements").get_mut(&reaction.message_id) {
+        announcement.acknowledged.remove(&user_id);
+    }
+    Ok(())
+}
@@ -0,0 +1,29 @@
+//! Writing data files so that readers never see a partially written file, even if the bot crashes while writing.
+
+use {
+    std::{
+        ffi::OsString,
+        io,
+        path::Path,
+    },
+    tokio::{
+        fs::{
+            self,
+            File,
+        },
+        io::AsyncWriteExt as _,
+    },
+};
+
+/// Writes the file via a temporary file in the same directory, which then replaces the file.
+pub(crate) async fn write(path: &Path, buf: &[u8]) -> io::Result<()> {
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(path.file_name().expect("path has no file name"));
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    let mut f = File::create(&tmp_path).await?;
+    f.write_all(buf).await?;
+    f.sync_all().await?;
+    drop(f);
+    fs::rename(tmp_path, path).await
+}
@@ -3,7 +3,10 @@
 #![allow(missing_docs)]
 
 use {
-    std::collections::HashSet,
+    std::collections::{
+        BTreeSet,
+        HashSet,
+    },
     rand::{
         Rng as _,
         thread_rng,
@@ -11,10 +14,11 @@ use {
     serenity::{
         framework::standard::{
             Args,
+            Command,
             CommandGroup,
             CommandResult,
             HelpOptions,
-            help_commands,
+            OnlyIn,
             macros::{
                 command,
                 group,
@@ -27,32 +31,148 @@ use {
         },
         prelude::*,
     },
+    serde::{
+        Deserialize,
+        Serialize,
+    },
     serenity_utils::shut_down,
     crate::{
-        config::Config,
-        emoji,
+        GEFOLGE,
+        announce::ANNOUNCE_COMMAND,
+        config::{
+            Config,
+            Feature,
+        },
+        emoji::{
+            self,
+            EMOJI_COMMAND,
+            EMOJIBACKUP_COMMAND,
+        },
+        lang::*,
         parse,
-        werewolf::{
-            COMMAND_DAY_COMMAND,
-            COMMAND_IN_COMMAND,
-            COMMAND_NIGHT_COMMAND,
-            COMMAND_OUT_COMMAND,
+        paths::Paths,
+        reactions::REACTIONS_COMMAND,
+        stats::STATS_COMMAND,
+        user_list::{
+            MEMBERS_COMMAND,
+            ProfileField,
+            UserList,
         },
     },
 };
-pub use self::{
-    HELP as HELP_COMMAND,
-    MAIN_GROUP as GROUP,
-};
+pub use self::HELP as HELP_COMMAND;
+
+/// Checks whether the given feature is enabled in the guild where the message was sent, replying with an error message if it isn't.
+async fn feature_enabled(ctx: &Context, msg: &Message, feature: Feature) -> serenity::Result<bool> {
+    if let Some(guild_id) = msg.guild_id {
+        if !Config::read(ctx).await.feature_enabled(guild_id, feature) {
+            msg.reply(ctx, format!("{} sind auf diesem Server deaktiviert", feature)).await?;
+            return Ok(false)
+        }
+    }
+    Ok(true)
+}
+
+/// The roles a Gefolge member assigned to themself using `!iam`, stored in their profile so gefolge.org can tell opt-in roles apart from permission roles.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct SelfAssigned(pub BTreeSet<RoleId>);
+
+impl ProfileField for SelfAssigned {
+    const KEY: &'static str = "selfAssigned";
+}
+
+/// Records that the member assigned (`assigned = true`) or unassigned the given role. Only Gefolge members have profiles, so this is a no-op elsewhere.
+///
+/// Called after the role was changed, so a failed API call doesn't leave a marker for a role the member doesn't have. Profile writes are serialized, so the member update event which follows can't overwrite this.
+async fn record_self_assigned(ctx: &Context, member: &Member, role: RoleId, assigned: bool) -> Result<(), crate::Error> {
+    if member.guild_id != GEFOLGE { return Ok(()) }
+    let user_list = UserList::get(ctx).await;
+    let mut self_assigned = user_list.get_field::<SelfAssigned>(member.user.id).await?.unwrap_or_default();
+    if assigned { self_assigned.0.insert(role); } else { self_assigned.0.remove(&role); }
+    user_list.set_field(member.user.id, if self_assigned.0.is_empty() { None } else { Some(&self_assigned) }).await?;
+    Ok(())
+}
+
+/// Finds a command by name or alias, followed by the names of its subcommands, e.g. `wwmod advance`. Returns the command and its full name.
+fn find_command(commands: impl IntoIterator<Item = &'static Command>, query: &str) -> Option<(&'static Command, String)> {
+    fn find(commands: impl IntoIterator<Item = &'static Command>, word: &str) -> Option<&'static Command> {
+        commands.into_iter().find(|command| command.options.names.iter().any(|name| name.eq_ignore_ascii_case(word)))
+    }
 
+    let mut words = query.split_whitespace();
+    let mut command = find(commands, words.next()?)?;
+    let mut name = command.options.names[0].to_owned();
+    for word in words {
+        command = if let Some(sub_command) = find(command.options.sub_commands.iter().copied(), word) { sub_command } else { break };
+        name.push(' ');
+        name.push_str(command.options.names[0]);
+    }
+    Some((command, name))
+}
+
+/// Lists the commands by module, or shows how to use a command, e.g. `!help wwmod vote`.
 #[help]
-async fn help(ctx: &Context, msg: &Message, args: Args, help_options: &'static HelpOptions, groups: &[&'static CommandGroup], owners: HashSet<UserId>) -> CommandResult {
-    let _ = help_commands::with_embeds(ctx, msg, args, help_options, groups, owners).await;
+async fn help(ctx: &Context, msg: &Message, args: Args, _: &'static HelpOptions, groups: &[&'static CommandGroup], owners: HashSet<UserId>) -> CommandResult {
+    let prefix = Config::read(ctx).await.prefix(msg.guild_id).to_owned();
+    let is_visible = |command: &Command| command.options.help_available && (!command.options.owners_only || owners.contains(&msg.author.id));
+    let query = args.rest().trim();
+    let query = query.trim_start_matches(&*prefix);
+    if query.is_empty() {
+        msg.channel_id.send_message(ctx, |m| m.embed(|e| {
+            e.title(help_title());
+            for group in groups {
+                let names = group.options.commands.iter().filter(|command| is_visible(command)).map(|command| command.options.names[0]).collect::<Vec<_>>();
+                if !names.is_empty() {
+                    e.field(group.name, help_command_list(&prefix, &names), false);
+                }
+            }
+            e.footer(|f| f.text(help_footer(&prefix)))
+        })).await?;
+        return Ok(())
+    }
+    let (command, name) = if let Some((command, name)) = find_command(groups.iter().flat_map(|group| group.options.commands.iter().copied()).filter(|command| is_visible(command)), query) {
+        (command, name)
+    } else {
+        msg.reply(ctx, help_unknown_command(&prefix, query)).await?;
+        return Ok(())
+    };
+    let options = command.options;
+    let checks = options.checks.iter().filter(|check| check.display_in_help).map(|check| check.name).collect::<Vec<_>>();
+    let (guild_only, dm_only) = match options.only_in {
+        OnlyIn::Guild => (true, false),
+        OnlyIn::Dm => (false, true),
+        _ => (false, false),
+    };
+    msg.channel_id.send_message(ctx, |m| m.embed(|e| {
+        e.title(format!("{}{}", prefix, name));
+        e.description(options.desc.unwrap_or_else(help_no_description));
+        e.field(help_usage_field(), help_usage(&prefix, &name, options.usage), false);
+        if !options.examples.is_empty() {
+            e.field(help_examples_field(), help_examples(&prefix, &name, options.examples), false);
+        }
+        if let Some(requirements) = help_requirements(options.required_permissions, &checks, options.owners_only, guild_only, dm_only) {
+            e.field(help_requirements_field(), requirements, false);
+        }
+        if options.names.len() > 1 {
+            let parent = name.rfind(' ').map_or("", |idx| &name[..=idx]);
+            e.field(help_aliases_field(), help_command_list(&format!("{}{}", prefix, parent), &options.names[1..]), false);
+        }
+        let sub_commands = options.sub_commands.iter().filter(|command| is_visible(command)).map(|command| command.options.names[0]).collect::<Vec<_>>();
+        if !sub_commands.is_empty() {
+            e.field(help_sub_commands_field(), help_command_list(&format!("{}{} ", prefix, name), &sub_commands), false);
+        }
+        e
+    })).await?;
     Ok(())
 }
 
+/// Gives you a self-assignable role on this server.
 #[command]
+#[usage = "<Rolle>"]
+#[example = "@Werwölfe"]
 pub async fn iam(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    if !feature_enabled(ctx, msg, Feature::SelfAssignableRoles).await? { return Ok(()) }
     let mut sender = match msg.member(&ctx).await {
         Ok(sender) => sender,
         Err(serenity::Error::Model(ModelError::ItemMissing)) => {
@@ -69,7 +189,7 @@ pub async fn iam(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
         msg.reply(ctx, "diese Rolle existiert nicht").await?;
         return Ok(());
     };
-    if !ctx.data.read().await.get::<Config>().expect("missing self-assignable roles list").peter.self_assignable_roles.contains(&role) {
+    if !Config::read(ctx).await.is_self_assignable(sender.guild_id, role) {
         msg.reply(ctx, "diese Rolle ist nicht selbstzuweisbar").await?;
         return Ok(());
     }
@@ -78,12 +198,17 @@ pub async fn iam(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
         return Ok(());
     }
     sender.add_role(&ctx, role).await?;
+    record_self_assigned(ctx, &sender, role, true).await?;
     msg.react(&ctx, '✅').await?;
     Ok(())
 }
 
+/// Removes a self-assignable role from you, see `iam`.
 #[command]
+#[usage = "<Rolle>"]
+#[example = "@Werwölfe"]
 pub async fn iamn(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    if !feature_enabled(ctx, msg, Feature::SelfAssignableRoles).await? { return Ok(()) }
     let mut sender = match msg.member(&ctx).await {
         Ok(sender) => sender,
         Err(serenity::Error::Model(ModelError::ItemMissing)) => {
@@ -100,7 +225,7 @@ pub async fn iamn(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
         msg.reply(ctx, "diese Rolle existiert nicht").await?;
         return Ok(());
     };
-    if !ctx.data.read().await.get::<Config>().expect("missing self-assignable roles list").peter.self_assignable_roles.contains(&role) {
+    if !Config::read(ctx).await.is_self_assignable(sender.guild_id, role) {
         msg.reply(ctx, "diese Rolle ist nicht selbstzuweisbar").await?;
         return Ok(());
     }
@@ -109,10 +234,12 @@ pub async fn iamn(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
         return Ok(());
     }
     sender.remove_role(&ctx, role).await?;
+    record_self_assigned(ctx, &sender, role, false).await?;
     msg.react(&ctx, '✅').await?;
     Ok(())
 }
 
+/// Checks whether the bot is online.
 #[command]
 pub async fn ping(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
     let reply = {
@@ -124,9 +251,16 @@ pub async fn ping(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
     Ok(())
 }
 
+/// Adds reactions to your message for voting: the emoji in the message if there are any, otherwise the given number of letters, otherwise 👍 and 👎.
 #[command]
+#[usage = "[<Anzahl>] [<Frage>]"]
+#[example = "Pizza oder Döner? 🍕 🥙"]
+#[example = "3 Welcher Termin passt euch? A: Freitag, B: Samstag, C: Sonntag"]
 pub async fn poll(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let mut emoji_iter = emoji::Iter::new(msg.content.to_owned())?.peekable();
+    if !feature_enabled(ctx, msg, Feature::Polls).await? { return Ok(()) }
+    let paths = ctx.data.read().await.get::<Paths>().expect("missing paths").clone();
+    let guild_emojis = msg.guild_field(ctx, |guild| guild.emojis.clone()).await.unwrap_or_default();
+    let mut emoji_iter = emoji::Iter::new(&paths, msg.content.to_owned()).with_guild_emojis(guild_emojis).unique().peekable();
     if emoji_iter.peek().is_some() {
         for emoji in emoji_iter {
             msg.react(&ctx, emoji).await?;
@@ -142,6 +276,7 @@ pub async fn poll(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult
     Ok(())
 }
 
+/// Shuts down the bot.
 #[command]
 #[owners_only]
 pub async fn quit(ctx: &Context, _: &Message, _: Args) -> CommandResult {
@@ -157,6 +292,7 @@ pub async fn shuffle(_: &Context, _: &Message, _: Args) -> CommandResult {
     unimplemented!(); //TODO
 }
 
+/// Prints the message and arguments to the bot's console, for debugging.
 #[command]
 #[owners_only]
 pub async fn test(_: &Context, msg: &Message, args: Args) -> CommandResult {
@@ -164,17 +300,20 @@ pub async fn test(_: &Context, msg: &Message, args: Args) -> CommandResult {
     Ok(())
 }
 
-#[group]
+/// Commands which don't belong to a game or to the server setup.
+#[group("Allgemein")]
 #[commands(
-    command_day,
+    announce,
+    emoji,
+    emojibackup,
     iam,
     iamn,
-    command_in,
-    command_night,
-    command_out,
+    members,
     ping,
     poll,
     quit,
+    reactions,
+    stats,
     test,
 )]
-struct Main;
+pub struct General;
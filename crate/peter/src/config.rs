@@ -1,7 +1,26 @@
 use {
-    std::collections::{
-        BTreeMap,
-        BTreeSet,
+    std::{
+        collections::{
+            BTreeMap,
+            BTreeSet,
+        },
+        convert::TryFrom,
+        env,
+        fmt,
+        io,
+        mem,
+        path::{
+            Path,
+            PathBuf,
+        },
+        str::FromStr,
+        sync::Arc,
+    },
+    regex::Regex,
+    schemars::{
+        JsonSchema,
+        schema::RootSchema,
+        schema_for,
     },
     serde::{
         Deserialize,
@@ -11,54 +30,762 @@ use {
         model::prelude::*,
         prelude::*,
     },
-    tokio::fs,
+    tokio::{
+        fs::{
+            self,
+            OpenOptions,
+        },
+        io::AsyncWriteExt as _,
+        sync::{
+            OwnedRwLockReadGuard,
+            OwnedRwLockWriteGuard,
+        },
+    },
     crate::{
         Error,
+        GEFOLGE,
+        IntoResultExt as _,
+        announce,
+        atomic_file,
+        paths::{
+            self,
+            Paths,
+        },
+        reactions,
+        stats,
         twitch,
+        user_list,
+        webhook,
         werewolf,
     },
 };
 
-const PATH: &str = "/usr/local/share/fidera/config.json";
-
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
-    pub channels: Channels,
+    /// The version of the config layout, see [`VERSION`].
+    version: u64,
+    #[serde(default)]
+    #[schemars(with = "BTreeMap<String, GuildConfig>")]
+    pub guilds: BTreeMap<GuildId, GuildConfig>,
+    #[serde(default)]
+    pub paths: paths::Config,
     pub peter: Peter,
+    #[serde(default)]
+    pub stats: stats::Config,
     pub(crate) twitch: twitch::Config,
-    pub werewolf: BTreeMap<GuildId, werewolf::Config>,
+    /// How the guild member list for gefolge.org is stored.
+    #[serde(default)]
+    pub user_list: user_list::Config,
+    /// The webhook receiver is only started if this is present.
+    #[serde(default)]
+    pub webhooks: Option<webhook::Config>,
+    /// The format the config was read from, used when saving it back.
+    #[serde(skip)]
+    format: Format,
+}
+
+/// The current version of the config layout. Configs without a `version` field are version 0.
+///
+/// When changing the layout in an incompatible way, increment this and add a migration to [`MIGRATIONS`].
+const VERSION: u64 = 4;
+
+/// `MIGRATIONS[n]` upgrades a config from version `n` to version `n + 1`.
+const MIGRATIONS: [fn(&mut serde_json::Map<String, serde_json::Value>); VERSION as usize] = [
+    add_twitch_section,
+    upgrade_global_layout,
+    move_log_channel,
+    werewolf_list,
+];
+
+/// Marks values in the config generated by [`Config::init`] which have to be filled in.
+const PLACEHOLDER: &str = "<TODO>";
+
+#[derive(Clone, Copy)]
+enum Format {
+    Json,
+    Toml,
 }
 
+impl Default for Format {
+    fn default() -> Format { Format::Json }
+}
+
+impl Format {
+    fn path(&self, paths: &Paths) -> PathBuf {
+        match self {
+            Format::Json => paths.config.clone(),
+            Format::Toml => paths.config.with_extension("toml"),
+        }
+    }
+}
+
+/// The config is shared behind its own lock so it can be changed at runtime without holding a lock on the entire `typemap`.
+///
+/// To avoid deadlocks, never lock the `typemap` while holding a lock on the config. If a lock on the `typemap` is already held, lock the config through it instead of using [`Config::read`] or [`Config::write`].
 impl TypeMapKey for Config {
-    type Value = Config;
+    type Value = Arc<RwLock<Config>>;
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Default, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Channels {
+    #[serde(default)]
+    #[schemars(with = "BTreeSet<u64>")]
     pub ignored: BTreeSet<ChannelId>,
-    pub voice: ChannelId,
+    /// All channels in these categories are ignored as well.
+    #[serde(default)]
+    #[schemars(with = "BTreeSet<u64>")]
+    pub ignored_categories: BTreeSet<ChannelId>,
+    /// Channels whose names match any of these patterns are ignored as well. `*` matches any number of characters and `?` matches a single character, ignoring case.
+    #[serde(default)]
+    #[schemars(with = "Vec<String>")]
+    pub ignored_patterns: Vec<ChannelPattern>,
+    /// Where to post operational messages, see [`log_channel`](crate::log_channel).
+    #[serde(default)]
+    #[schemars(with = "Option<u64>")]
+    pub log: Option<ChannelId>,
+    /// Where to announce that someone joined a voice channel.
+    #[serde(default)]
+    #[schemars(with = "Option<u64>")]
+    pub voice: Option<ChannelId>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Peter {
-    pub bot_token: String,
+    /// May be omitted if the token is provided via `secretsFile` or the `PETER_BOT_TOKEN` environment variable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bot_token: Option<String>,
+    /// A JSON file with secrets which should not be stored in the shared config file, e.g. `{"botToken": "…"}`. Should only be readable by the bot's user.
+    #[serde(default)]
+    secrets_file: Option<PathBuf>,
+    /// How many previous versions of the config file to keep when it's changed from Discord, as `config.json.1` (the most recent) through `config.json.<n>`.
+    #[serde(default = "default_config_backups")]
+    config_backups: u32,
+    /// Read from `secrets_file`. Not saved back to the config file.
+    #[serde(skip)]
+    secrets: Secrets,
+    /// Set from the `PETER_BOT_TOKEN` environment variable. Not saved back to the config file.
+    #[serde(skip)]
+    bot_token_override: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Secrets {
+    bot_token: Option<String>,
+}
+
+/// Settings which apply to a single guild.
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GuildConfig {
+    #[serde(default)]
+    pub announcements: announce::Config,
+    #[serde(default)]
+    pub channels: Channels,
+    #[serde(default = "all_features")]
+    pub features: BTreeSet<Feature>,
+    #[serde(default)]
+    #[schemars(with = "BTreeSet<u64>")]
+    pub mod_roles: BTreeSet<RoleId>,
+    /// The prefix for commands on this server, in addition to mentioning the bot.
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+    #[serde(default)]
+    pub reactions: reactions::Config,
+    #[serde(default)]
+    #[schemars(with = "BTreeSet<u64>")]
     pub(crate) self_assignable_roles: BTreeSet<RoleId>,
+    /// One entry per channel in which games of Werewolf can be played, see [`crate::werewolf`].
+    #[serde(default)]
+    pub werewolf: Vec<werewolf::Config>,
+}
+
+impl Default for GuildConfig {
+    fn default() -> GuildConfig {
+        GuildConfig {
+            announcements: announce::Config::default(),
+            channels: Channels::default(),
+            features: all_features(),
+            mod_roles: BTreeSet::default(),
+            prefix: default_prefix(),
+            reactions: reactions::Config::default(),
+            self_assignable_roles: BTreeSet::default(),
+            werewolf: Vec::default(),
+        }
+    }
+}
+
+fn all_features() -> BTreeSet<Feature> {
+    Feature::ALL.iter().copied().collect()
+}
+
+/// A channel name pattern from [`Channels::ignored_patterns`], where `*` matches any number of characters and `?` matches a single character, ignoring case.
+///
+/// The pattern is compiled when the config is loaded, so an invalid pattern is reported then instead of silently matching nothing.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ChannelPattern {
+    glob: String,
+    regex: Regex,
+}
+
+impl ChannelPattern {
+    pub fn is_match(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+}
+
+impl TryFrom<String> for ChannelPattern {
+    type Error = regex::Error;
+
+    fn try_from(glob: String) -> Result<ChannelPattern, regex::Error> {
+        let mut re = String::from("(?i)^");
+        for c in glob.chars() {
+            match c {
+                '*' => re.push_str(".*"),
+                '?' => re.push('.'),
+                _ => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+            }
+        }
+        re.push('$');
+        Ok(ChannelPattern { regex: Regex::new(&re)?, glob })
+    }
+}
+
+impl From<ChannelPattern> for String {
+    fn from(pattern: ChannelPattern) -> String {
+        pattern.glob
+    }
+}
+
+/// The command prefix in DMs and on servers which haven't configured one.
+pub const DEFAULT_PREFIX: &str = "!";
+
+fn default_prefix() -> String { DEFAULT_PREFIX.to_owned() }
+
+/// A feature which can be enabled or disabled per guild.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum Feature {
+    Polls,
+    SelfAssignableRoles,
+    VoiceNotifications,
+    Werewolf,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 4] = [Feature::Polls, Feature::SelfAssignableRoles, Feature::VoiceNotifications, Feature::Werewolf];
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Feature::Polls => write!(f, "Umfragen"),
+            Feature::SelfAssignableRoles => write!(f, "selbstzuweisbare Rollen"),
+            Feature::VoiceNotifications => write!(f, "Voice-Benachrichtigungen"),
+            Feature::Werewolf => write!(f, "Werwölfe"),
+        }
+    }
+}
+
+/// A part of the config which can be reloaded on its own using [`Config::reload_section`].
+#[derive(Debug, Clone, Copy)]
+pub enum Section {
+    /// All per-guild settings.
+    Guilds,
+    Stats,
+    Twitch,
+    Webhooks,
+    /// The werewolf settings of the guilds which are already configured.
+    Werewolf,
+}
+
+impl FromStr for Section {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Section, ()> {
+        match s {
+            "guilds" => Ok(Section::Guilds),
+            "stats" => Ok(Section::Stats),
+            "twitch" => Ok(Section::Twitch),
+            "webhooks" => Ok(Section::Webhooks),
+            "werewolf" => Ok(Section::Werewolf),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Section::Guilds => write!(f, "guilds"),
+            Section::Stats => write!(f, "stats"),
+            Section::Twitch => write!(f, "twitch"),
+            Section::Webhooks => write!(f, "webhooks"),
+            Section::Werewolf => write!(f, "werewolf"),
+        }
+    }
+}
+
+/// Reads the config file as JSON, upgrading it to the current layout if necessary. Returns whether it was upgraded.
+async fn read_json(paths: &Paths) -> Result<(Format, serde_json::Value, bool), Error> {
+    let (format, mut json) = match fs::read_to_string(Format::Toml.path(paths)).await {
+        Ok(buf) => (Format::Toml, toml::from_str::<serde_json::Value>(&buf)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let buf = fs::read_to_string(Format::Json.path(paths)).await?;
+            (Format::Json, serde_json::from_str(&buf)?) //TODO use async-json
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let migrated = if let Some(map) = json.as_object_mut() { migrate(map)? } else { false };
+    Ok((format, json, migrated))
 }
 
 impl Config {
-    pub async fn new() -> Result<Config, Error> {
-        let buf = fs::read_to_string(PATH).await?;
-        Ok(serde_json::from_str(&buf)?) //TODO use async-json
+    /// Reads the config file, then applies overrides from `PETER_*` environment variables.
+    ///
+    /// If a file named `config.toml` exists next to the configured config path, it is used instead of the JSON config file.
+    ///
+    /// Configs with an older layout are upgraded using [`MIGRATIONS`] and written back.
+    ///
+    /// Currently supported overrides are:
+    ///
+    /// * `PETER_BOT_TOKEN` for `peter.botToken` (also takes precedence over `peter.secretsFile`)
+    ///
+    /// Filesystem paths can be overridden using other environment variables, see [`Paths::new`].
+    pub async fn new(paths: &Paths) -> Result<Config, Error> {
+        let (format, json, migrated) = read_json(paths).await?;
+        check_placeholders(&json, "")?;
+        let mut config = Config {
+            format,
+            ..serde_json::from_value(json)?
+        };
+        if migrated { config.save(paths).await? }
+        config.load_secrets().await?;
+        config.apply_env_overrides()?;
+        if config.peter.bot_token_override.is_none() && config.peter.secrets.bot_token.is_none() && config.peter.bot_token.is_none() { return Err(Error::MissingBotToken) }
+        Ok(config)
+    }
+
+    /// Replaces the given section with its current contents in the config file, leaving the rest of the config untouched. Returns the changes as `path: old → new` lines.
+    ///
+    /// Only the given section is validated. Subsystems pick up the changes the next time they read the config.
+    pub async fn reload_section(&mut self, paths: &Paths, section: Section) -> Result<Vec<String>, Error> {
+        let (_, mut json, _) = read_json(paths).await?;
+        let old = self.section_json(section)?;
+        let key = match section {
+            Section::Guilds | Section::Werewolf => "guilds",
+            Section::Stats => "stats",
+            Section::Twitch => "twitch",
+            Section::Webhooks => "webhooks",
+        };
+        let value = json.get_mut(key).map(serde_json::Value::take).unwrap_or_else(|| match section {
+            Section::Guilds | Section::Werewolf | Section::Stats => serde_json::Value::Object(serde_json::Map::default()),
+            Section::Twitch | Section::Webhooks => serde_json::Value::Null,
+        });
+        check_placeholders(&value, key)?;
+        match section {
+            Section::Guilds => self.guilds = serde_json::from_value(value)?,
+            Section::Stats => self.stats = serde_json::from_value(value)?,
+            Section::Twitch => self.twitch = serde_json::from_value(value)?,
+            Section::Webhooks => self.webhooks = serde_json::from_value(value)?,
+            Section::Werewolf => {
+                let mut guilds = serde_json::from_value::<BTreeMap<GuildId, serde_json::Map<String, serde_json::Value>>>(value)?;
+                let werewolf = self.guilds.keys()
+                    .map(|guild_id| Ok((*guild_id, guilds.get_mut(guild_id).and_then(|guild| guild.remove("werewolf")).map(serde_json::from_value).transpose()?.unwrap_or_default())))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                for (guild_id, werewolf) in werewolf {
+                    self.guilds.get_mut(&guild_id).expect("iterating over existing guilds").werewolf = werewolf;
+                }
+            }
+        }
+        Ok(diff(&old, &self.section_json(section)?))
+    }
+
+    fn section_json(&self, section: Section) -> serde_json::Result<serde_json::Value> {
+        match section {
+            Section::Guilds => serde_json::to_value(&self.guilds),
+            Section::Stats => serde_json::to_value(&self.stats),
+            Section::Twitch => serde_json::to_value(&self.twitch),
+            Section::Webhooks => serde_json::to_value(&self.webhooks),
+            Section::Werewolf => serde_json::to_value(self.guilds.iter().map(|(guild_id, guild_config)| (guild_id, &guild_config.werewolf)).collect::<BTreeMap<_, _>>()),
+        }
+    }
+
+    /// A JSON Schema describing the config file, for validating config edits before they're loaded by the bot.
+    ///
+    /// Discord IDs are described as numbers, or as strings when used as object keys.
+    pub fn json_schema() -> RootSchema {
+        schema_for!(Config)
+    }
+
+    /// Writes a config file with default settings and placeholders for required settings to the TOML config path, and returns that path.
+    ///
+    /// Fails if a config file already exists. [`Config::new`] refuses to load the config until all placeholders are replaced.
+    pub async fn init(paths: &Paths) -> Result<PathBuf, Error> {
+        if fs::metadata(Format::Json.path(paths)).await.is_ok() { return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", Format::Json.path(paths).display())).into()) }
+        let path = Format::Toml.path(paths);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let mut file = OpenOptions::new().write(true).create_new(true).open(&path).await.annotate(format!("failed to create {}", path.display()))?;
+        file.write_all(default_config().as_bytes()).await?;
+        Ok(path)
     }
 
-    /*
-    pub(crate) async fn save(self) -> Result<(), Error> {
-        let buf = serde_json::to_vec(&self)?; //TODO use async-json
-        File::create(PATH).await?.write_all(&buf).await?;
+    async fn load_secrets(&mut self) -> Result<(), Error> {
+        if let Some(ref secrets_file) = self.peter.secrets_file {
+            #[cfg(unix)] {
+                use std::os::unix::fs::PermissionsExt as _;
+
+                if fs::metadata(secrets_file).await.annotate(format!("failed to read {}", secrets_file.display()))?.permissions().mode() & 0o077 != 0 {
+                    eprintln!("warning: {} is accessible by other users", secrets_file.display());
+                }
+            }
+            let buf = fs::read_to_string(secrets_file).await.annotate(format!("failed to read {}", secrets_file.display()))?;
+            self.peter.secrets = serde_json::from_str(&buf)?; //TODO use async-json
+        }
         Ok(())
     }
-    */
+
+    /// Returns a handle to the shared config.
+    pub async fn shared(ctx: &Context) -> Arc<RwLock<Config>> {
+        Arc::clone(ctx.data.read().await.get::<Config>().expect("missing config"))
+    }
+
+    /// Locks the shared config for reading.
+    pub async fn read(ctx: &Context) -> OwnedRwLockReadGuard<Config> {
+        Config::shared(ctx).await.read_owned().await
+    }
+
+    /// Locks the shared config for writing. Use [`Config::update`] to also save the changes.
+    pub async fn write(ctx: &Context) -> OwnedRwLockWriteGuard<Config> {
+        Config::shared(ctx).await.write_owned().await
+    }
+
+    /// Applies the given change to the shared config and saves it.
+    pub async fn update<T>(ctx: &Context, f: impl FnOnce(&mut Config) -> T) -> Result<T, Error> {
+        let paths = ctx.data.read().await.get::<Paths>().expect("missing paths").clone();
+        let mut config = Config::write(ctx).await;
+        let result = f(&mut config);
+        config.save(&paths).await?;
+        Ok(result)
+    }
+
+    fn apply_env_overrides(&mut self) -> Result<(), Error> {
+        self.peter.bot_token_override = env_override("PETER_BOT_TOKEN")?;
+        Ok(())
+    }
+
+    /// The bot token from the environment, the secrets file, or the config file, in that order of preference.
+    pub fn bot_token(&self) -> &str {
+        self.peter.bot_token_override.as_ref()
+            .or(self.peter.secrets.bot_token.as_ref())
+            .or(self.peter.bot_token.as_ref())
+            .expect("checked in Config::new")
+    }
+
+    pub fn guild(&self, guild_id: GuildId) -> Option<&GuildConfig> {
+        self.guilds.get(&guild_id)
+    }
+
+    /// Returns whether the given feature is enabled in the given guild. Always `false` for unconfigured guilds.
+    pub fn feature_enabled(&self, guild_id: GuildId, feature: Feature) -> bool {
+        self.guild(guild_id).map_or(false, |guild_config| guild_config.features.contains(&feature))
+    }
+
+    /// The command prefix for the given guild, or [`DEFAULT_PREFIX`] in DMs.
+    pub fn prefix(&self, guild_id: Option<GuildId>) -> &str {
+        guild_id.and_then(|guild_id| self.guild(guild_id)).map_or(DEFAULT_PREFIX, |guild_config| &guild_config.prefix)
+    }
+
+    /// Returns whether the given channel is ignored, either explicitly or by its category or name.
+    pub fn is_ignored(&self, guild_id: GuildId, channel: &GuildChannel) -> bool {
+        self.guild(guild_id).map_or(false, |guild_config| {
+            let channels = &guild_config.channels;
+            channels.ignored.contains(&channel.id)
+            || channel.category_id.map_or(false, |category_id| channels.ignored_categories.contains(&category_id))
+            || channels.ignored_patterns.iter().any(|pattern| pattern.is_match(&channel.name))
+        })
+    }
+
+    pub fn is_self_assignable(&self, guild_id: GuildId, role_id: RoleId) -> bool {
+        self.guild(guild_id).map_or(false, |guild_config| guild_config.self_assignable_roles.contains(&role_id))
+    }
+
+    /// The settings of the Werewolf game played in the given channel, which may be the game's discussion or action channel.
+    pub fn werewolf(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<&werewolf::Config> {
+        self.guild(guild_id).and_then(|guild_config| guild_config.werewolf.iter().find(|conf| conf.is_game_channel(channel_id)))
+    }
+
+    /// Returns the config as JSON with secrets like the bot token replaced, for displaying it in Discord.
+    pub(crate) fn redacted(&self) -> Result<serde_json::Value, Error> {
+        let mut json = serde_json::to_value(self)?;
+        redact(&mut json);
+        Ok(json)
+    }
+
+    /// Writes the config back to the file it was read from, in the same format. Environment overrides are not saved.
+    ///
+    /// The previous versions of the file are kept according to `peter.configBackups`.
+    pub(crate) async fn save(&self, paths: &Paths) -> Result<(), Error> {
+        let buf = match self.format {
+            Format::Json => serde_json::to_vec_pretty(self)?, //TODO use async-json
+            Format::Toml => {
+                // TOML only supports string keys and has no null, so go through a JSON value which converts snowflake keys to strings
+                let mut json = serde_json::to_value(self)?;
+                remove_nulls(&mut json);
+                toml::to_string_pretty(&toml::Value::try_from(json)?)?.into_bytes()
+            }
+        };
+        let path = self.format.path(paths);
+        rotate_backups(&path, self.peter.config_backups).await?;
+        atomic_file::write(&path, &buf).await?;
+        Ok(())
+    }
+}
+
+fn default_config_backups() -> u32 { 5 }
+
+fn backup_path(path: &Path, n: u32) -> PathBuf {
+    let mut backup_path = path.as_os_str().to_owned();
+    backup_path.push(format!(".{}", n));
+    PathBuf::from(backup_path)
+}
+
+/// Shifts `<path>.1` through `<path>.<count - 1>` up by one, dropping the oldest, and copies the file at the given path to `<path>.1`.
+async fn rotate_backups(path: &Path, count: u32) -> io::Result<()> {
+    if count == 0 { return Ok(()) }
+    for n in (1..count).rev() {
+        match fs::rename(backup_path(path, n), backup_path(path, n + 1)).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    match fs::copy(path, backup_path(path, 1)).await {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+fn default_config() -> String {
+    format!(r#"# Generated by `peter init-config`. Replace all values marked "{placeholder}" before starting the bot.
+version = {version}
+
+[peter]
+botToken = "{placeholder}"
+# Alternatively, remove botToken and store the token in a file which is only readable by the bot's user, e.g. {{"botToken": "…"}}:
+# secretsFile = "/etc/peter/secrets.json"
+# How many previous versions of this file to keep when it is changed using !config or !setup:
+# configBackups = 5
+
+[twitch]
+# Stream notifications are only sent for the users listed in [twitch.users].
+clientID = ""
+clientSecret = ""
+
+[twitch.users]
+# "<Discord user ID>" = "<Twitch user ID>"
+
+# Settings for a guild. Copy these sections for each guild the bot should be active in, or use the !setup command.
+[guilds."{placeholder}"]
+features = ["polls", "selfAssignableRoles", "voiceNotifications", "werewolf"]
+modRoles = []
+selfAssignableRoles = []
+
+[guilds."{placeholder}".channels]
+ignored = []
+ignoredCategories = []
+ignoredPatterns = [] # e.g. "spam-*"
+# log = <channel ID>
+# voice = <channel ID>
+
+# [[guilds."{placeholder}".werewolf]] # repeat this section for each channel where games should be played
+# role = <role ID>
+# textChannel = <channel ID>
+# actionChannel = <channel ID>
+# voiceChannel = <channel ID>
+# muteVoice = false
+# spectatorChannel = <channel ID>
+# createSpectatorChannel = false
+# moderatorRole = <role ID>
+# minPlayers = {min_players}
+# maxPlayers = 10
+# signupSecs = 600
+# voteDelegation = false
+# daySecs = 1800
+# nightSecs = 180
+# dayEndHour = 20 # for long-form games, overrides daySecs
+# nightEndHour = 9 # for long-form games, overrides nightSecs
+# reminderSecs = 300
+# prodSecs = 0
+# afkLimit = 2
+# reactionVotes = false
+# anonymousVotes = false
+# revealVotes = false # with anonymousVotes, list the votes in the game log
+# tieBreak = "noLynch" # or "random" or "runoff"
+# runoffSecs = 300
+# noLynch = true
+# revealRoles = true
+# lastWordsSecs = 60 # 0 to disable
+# departure = "substitute" # or "lynch"
+"#, placeholder = PLACEHOLDER, version = VERSION, min_players = quantum_werewolf::game::state::MIN_PLAYERS)
+}
+
+/// Fails if any key or value in the config is still a placeholder from [`Config::init`].
+fn check_placeholders(value: &serde_json::Value, path: &str) -> Result<(), Error> {
+    match value {
+        serde_json::Value::String(s) if s == PLACEHOLDER => Err(Error::ConfigPlaceholder(path.to_owned())),
+        serde_json::Value::Array(values) => {
+            for (idx, value) in values.iter().enumerate() {
+                check_placeholders(value, &format!("{}[{}]", path, idx))?;
+            }
+            Ok(())
+        }
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                if key == PLACEHOLDER { return Err(Error::ConfigPlaceholder(path)) }
+                check_placeholders(value, &path)?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn env_override(var: &str) -> Result<Option<String>, Error> {
+    match env::var(var) {
+        Ok(value) => Ok(Some(value)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(e) => Err(e.annotate(format!("failed to read {}", var))),
+    }
+}
+
+/// Upgrades the config to the current layout version. Returns whether any migrations were applied.
+pub fn migrate(config: &mut serde_json::Map<String, serde_json::Value>) -> Result<bool, Error> {
+    let version = config.get("version").and_then(serde_json::Value::as_u64).unwrap_or_default();
+    if version > VERSION { return Err(Error::UnsupportedConfigVersion(version)) }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(config);
+    }
+    config.insert(format!("version"), serde_json::Value::from(VERSION));
+    Ok(version < VERSION)
+}
+
+/// Adds an empty `twitch` section to configs from before Twitch stream notifications were added.
+fn add_twitch_section(config: &mut serde_json::Map<String, serde_json::Value>) {
+    config.entry("twitch").or_insert_with(|| serde_json::json!({
+        "clientID": "",
+        "clientSecret": "",
+        "users": {},
+    }));
+}
+
+/// Moves the settings which were global before per-guild configuration was introduced into the Gefolge guild's config.
+fn upgrade_global_layout(config: &mut serde_json::Map<String, serde_json::Value>) {
+    fn guild_entry(guilds: &mut serde_json::Map<String, serde_json::Value>, guild_id: String) -> &mut serde_json::Map<String, serde_json::Value> {
+        let entry = guilds.entry(guild_id).or_insert_with(|| serde_json::Value::Object(serde_json::Map::default()));
+        if !entry.is_object() { *entry = serde_json::Value::Object(serde_json::Map::default()) }
+        entry.as_object_mut().expect("just inserted")
+    }
+
+    let channels = config.remove("channels");
+    let self_assignable_roles = config.get_mut("peter").and_then(|peter| peter.as_object_mut()).and_then(|peter| peter.remove("selfAssignableRoles"));
+    let werewolf = config.remove("werewolf");
+    let guilds = config.entry("guilds").or_insert_with(|| serde_json::Value::Object(serde_json::Map::default()));
+    if let Some(guilds) = guilds.as_object_mut() {
+        if let Some(channels) = channels {
+            guild_entry(guilds, GEFOLGE.to_string()).insert(format!("channels"), channels);
+        }
+        if let Some(self_assignable_roles) = self_assignable_roles {
+            guild_entry(guilds, GEFOLGE.to_string()).insert(format!("selfAssignableRoles"), self_assignable_roles);
+        }
+        if let Some(serde_json::Value::Object(werewolf)) = werewolf {
+            for (guild_id, werewolf_config) in werewolf {
+                guild_entry(guilds, guild_id).insert(format!("werewolf"), werewolf_config);
+            }
+        }
+    }
+}
+
+/// Moves each guild's `logChannel` setting to `channels.log`.
+fn move_log_channel(config: &mut serde_json::Map<String, serde_json::Value>) {
+    if let Some(guilds) = config.get_mut("guilds").and_then(|guilds| guilds.as_object_mut()) {
+        for guild_config in guilds.values_mut().filter_map(|guild_config| guild_config.as_object_mut()) {
+            if let Some(log_channel) = guild_config.remove("logChannel") {
+                let channels = guild_config.entry("channels").or_insert_with(|| serde_json::Value::Object(serde_json::Map::default()));
+                if let Some(channels) = channels.as_object_mut() {
+                    channels.insert(format!("log"), log_channel);
+                }
+            }
+        }
+    }
+}
+
+/// Turns each guild's `werewolf` section into a list, to allow multiple games per guild.
+fn werewolf_list(config: &mut serde_json::Map<String, serde_json::Value>) {
+    if let Some(guilds) = config.get_mut("guilds").and_then(|guilds| guilds.as_object_mut()) {
+        for guild_config in guilds.values_mut().filter_map(|guild_config| guild_config.as_object_mut()) {
+            if let Some(werewolf) = guild_config.remove("werewolf") {
+                if !werewolf.is_null() {
+                    guild_config.insert(format!("werewolf"), serde_json::Value::Array(vec![werewolf]));
+                }
+            }
+        }
+    }
+}
+
+/// Lists the differences between two configs or parts of configs as `path: old → new` lines, with secrets masked.
+pub(crate) fn diff(old: &serde_json::Value, new: &serde_json::Value) -> Vec<String> {
+    fn diff_into(lines: &mut Vec<String>, path: &str, old: &serde_json::Value, new: &serde_json::Value) {
+        match (old, new) {
+            (serde_json::Value::Object(old_map), serde_json::Value::Object(new_map)) => {
+                for key in old_map.keys().chain(new_map.keys()).collect::<BTreeSet<_>>() {
+                    let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                    diff_into(lines, &path, old_map.get(key).unwrap_or(&serde_json::Value::Null), new_map.get(key).unwrap_or(&serde_json::Value::Null));
+                }
+            }
+            _ if old == new => {}
+            _ => lines.push(format!("{}: {} → {}", if path.is_empty() { "." } else { path }, old, new)),
+        }
+    }
+
+    let mut old = old.clone();
+    redact(&mut old);
+    let mut new = new.clone();
+    redact(&mut new);
+    let mut lines = Vec::default();
+    diff_into(&mut lines, "", &old, &new);
+    lines
+}
+
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Array(values) => for value in values { redact(value) },
+        serde_json::Value::Object(map) => for (key, value) in map {
+            if ["botToken", "clientSecret", "secret"].contains(&&**key) {
+                *value = serde_json::Value::String(format!("[redacted]"));
+            } else {
+                redact(value);
+            }
+        },
+        _ => {}
+    }
+}
+
+fn remove_nulls(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Array(values) => for value in values { remove_nulls(value) },
+        serde_json::Value::Object(map) => {
+            *map = mem::take(map).into_iter().filter(|(_, value)| !value.is_null()).collect();
+            for value in map.values_mut() { remove_nulls(value) }
+        }
+        _ => {}
+    }
 }
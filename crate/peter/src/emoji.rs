@@ -2,21 +2,70 @@
 
 use {
     std::{
-        collections::BTreeSet,
+        collections::{
+            BTreeSet,
+            HashMap,
+            HashSet
+        },
         ffi::OsString,
         fmt,
         fs,
         io,
-        mem,
-        str::FromStr
+        ops::Range,
+        path::Path,
+        pin::Pin,
+        sync::{
+            Arc,
+            RwLock
+        },
+        time::Duration
     },
+    chrono::{
+        Duration as ChronoDuration,
+        prelude::*
+    },
+    futures::future::Future,
     once_cell::sync::Lazy,
     regex::Regex,
-    serenity::model::prelude::*
+    serde::Serialize,
+    serenity::{
+        framework::standard::{
+            Args,
+            CommandResult,
+            macros::command
+        },
+        model::prelude::*,
+        prelude::*
+    },
+    tokio::time::sleep,
+    unicode_segmentation::UnicodeSegmentation as _,
+    crate::{
+        paths::Paths,
+        reaction_listeners::{
+            self,
+            Filter,
+            ReactionListeners
+        },
+        stats::Stats
+    }
 };
 
+/// All Unicode emoji known to the `emojis` crate, including skin tone variants, also without variation selectors since Discord accepts both forms.
+static BUNDLED_EMOJI: Lazy<Arc<Vec<String>>> = Lazy::new(|| {
+    let mut bundled = BTreeSet::default();
+    for emoji in emojis::iter().flat_map(|emoji| emoji.skin_tones().into_iter().flatten().chain(Some(emoji))) {
+        bundled.insert(emoji.as_str().to_owned());
+        bundled.insert(emoji.as_str().replace('\u{fe0f}', ""));
+    }
+    Arc::new(bundled.into_iter().collect())
+});
+/// The emoji database, sorted so that longer emoji come after their prefixes. Built on first use, see [`reload`].
+static DATABASE: Lazy<RwLock<Option<Arc<Vec<String>>>>> = Lazy::new(RwLock::default);
+/// Maps each bundled emoji with variation selectors removed to its fully qualified form, see [`normalize`].
+static QUALIFIED_EMOJI: Lazy<HashMap<String, &'static str>> = Lazy::new(|| emojis::iter().map(|emoji| (emoji.as_str().replace('\u{fe0f}', ""), emoji.as_str())).collect());
 static FILENAME_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("^([0-9a-f]{1,6}(?:-[0-9a-f]{1,6})*)\\.svg$").expect("failed to compile twemoji filename regex"));
-static CUSTOM_EMOJI_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("^<:[0-9A-Z_a-z]{2,}:[0-9]+>").expect("failed to compile custom emoji regex"));
+static SHORTCODE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("^:([0-9A-Za-z_+-]+):").expect("failed to compile shortcode regex"));
+static CUSTOM_EMOJI_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("^<(a?):([0-9A-Z_a-z]{2,}):([0-9]+)>").expect("failed to compile custom emoji regex"));
 
 /// An error that can occur while parsing emoji from a message.
 #[derive(Debug)]
@@ -51,54 +100,236 @@ impl fmt::Display for Error {
 impl std::error::Error for Error {}
 
 /// An iterator over all the emoji in a message.
-///
-/// Note that the `animated` field of yielded values is bogus and should not be relied upon.
 pub struct Iter {
     text: String,
-    emoji: Vec<String>
+    /// The byte offset in `text` up to which emoji have been yielded.
+    pos: usize,
+    emoji: Arc<Vec<String>>,
+    guild_emojis: HashMap<EmojiId, Emoji>,
+    normalize: bool,
+    /// `Some` if each emoji should only be yielded once, in which case this contains the emoji yielded so far.
+    seen: Option<HashSet<Result<EmojiId, String>>>
 }
 
 impl Iter {
     /// Create an iterator over all emoji in the given text.
-    pub fn new(text: String) -> Result<Iter, Error> {
-        let mut emoji = BTreeSet::default();
-        for entry in fs::read_dir("/opt/git/github.com/twitter/twemoji/master/2/svg")? {
-            let file_name = entry?.file_name().into_string()?;
-            if let Some(capture) = FILENAME_REGEX.captures(&file_name).and_then(|captures| captures.get(1)) {
-                // convert the filename encoding the emoji (e.g. 1f3f3-fe0f-200d-1f308.svg) to the emoji itself (e.g. 🏳️‍🌈)
-                emoji.insert(capture.as_str().split('-').filter_map(|hex| u32::from_str_radix(hex, 16).ok().and_then(::std::char::from_u32)).collect());
+    ///
+    /// The emoji database is built on the first call and reused afterwards, see [`reload`].
+    pub fn new(paths: &Paths, text: String) -> Iter {
+        let cached = DATABASE.read().expect("emoji database lock poisoned").clone();
+        Iter::with_database(text, if let Some(emoji) = cached { emoji } else { reload(paths) })
+    }
+
+    fn with_database(text: String, emoji: Arc<Vec<String>>) -> Iter {
+        Iter { text, pos: 0, emoji, guild_emojis: HashMap::default(), normalize: false, seen: None }
+    }
+
+    /// Also resolve shortcodes to the given custom emoji by name, see [`parse_shortcode`].
+    pub fn with_guild_emojis(mut self, guild_emojis: HashMap<EmojiId, Emoji>) -> Iter {
+        self.guild_emojis = guild_emojis;
+        self
+    }
+
+    /// Yield Unicode emoji in their [`normalize`]d form.
+    pub fn normalized(mut self) -> Iter {
+        self.normalize = true;
+        self
+    }
+}
+
+/// Removes skin tone modifiers from the given Unicode emoji and canonicalizes its variation selectors, so that e.g. 👍🏻, 👍🏽, and 👍 are considered the same emoji.
+///
+/// Emoji which are known to the bundled emoji list are returned in their fully qualified form. Other emoji are returned without variation selectors.
+pub fn normalize(emoji: &str) -> String {
+    let stripped = emoji.chars().filter(|&c| !matches!(c, '\u{fe0f}' | '\u{1f3fb}'..='\u{1f3ff}')).collect::<String>();
+    QUALIFIED_EMOJI.get(&stripped).map_or(stripped, |&qualified| qualified.to_owned())
+}
+
+/// Looks up a custom emoji of the given guild by name, ignoring case if there's no exact match, so it can be used as a reaction without hard-coding its ID.
+///
+/// The guild's emoji are taken from the cache if available, and fetched via the REST API otherwise.
+pub async fn custom_by_name(ctx: &Context, guild_id: GuildId, name: &str) -> serenity::Result<Option<ReactionType>> {
+    let guild_emojis = match guild_id.to_guild_cached(ctx).await {
+        Some(guild) => guild.emojis.into_values().collect(),
+        None => guild_id.emojis(ctx).await?
+    };
+    let emoji = guild_emojis.iter().find(|emoji| emoji.name == name)
+        .or_else(|| guild_emojis.iter().find(|emoji| emoji.name.to_lowercase() == name.to_lowercase()));
+    Ok(emoji.map(|emoji| ReactionType::Custom {
+        animated: emoji.animated,
+        id: emoji.id,
+        name: Some(emoji.name.clone())
+    }))
+}
+
+/// Checks whether the given text consists only of emoji (Unicode emoji, custom emoji, and shortcodes) and whitespace, with at least one emoji.
+///
+/// This uses the emoji database if it has been built (see [`Iter::new`]), and the bundled emoji list otherwise.
+pub fn is_emoji_only(text: &str) -> bool {
+    let emoji = DATABASE.read().expect("emoji database lock poisoned").clone().unwrap_or_else(|| Arc::clone(&BUNDLED_EMOJI));
+    let mut pos = 0;
+    let mut any = false;
+    for (range, _) in Iter::with_database(text.to_owned(), emoji).spans() {
+        if !text[pos..range.start].trim().is_empty() { return false }
+        pos = range.end;
+        any = true;
+    }
+    any && text[pos..].trim().is_empty()
+}
+
+/// Checks whether a grapheme cluster is a single emoji.
+///
+/// Besides the emoji in the database, this accepts flags, keycaps, and ZWJ sequences of known emoji, since the database may not list every combination.
+fn is_emoji_grapheme(database: &[String], grapheme: &str) -> bool {
+    fn is_known(database: &[String], emoji: &str) -> bool {
+        database.binary_search_by(|known| known.as_str().cmp(emoji)).is_ok()
+    }
+
+    if is_known(database, grapheme) { return true }
+    let chars = grapheme.chars().filter(|&c| c != '\u{fe0f}').collect::<Vec<_>>();
+    match &chars[..] {
+        [first, second] if ('\u{1f1e6}'..='\u{1f1ff}').contains(first) && ('\u{1f1e6}'..='\u{1f1ff}').contains(second) => true, // flag
+        [base, '\u{20e3}'] if matches!(*base, '0'..='9' | '#' | '*') => true, // keycap
+        _ => grapheme.contains('\u{200d}') && grapheme.split('\u{200d}').all(|component| {
+            let component = component.chars().filter(|&c| !matches!(c, '\u{fe0f}' | '\u{1f3fb}'..='\u{1f3ff}')).collect::<String>();
+            !component.is_empty() && is_known(database, &component)
+        })
+    }
+}
+
+/// Parses a custom emoji like `<:name:id>` or `<a:name:id>` at the start of the given text, returning it along with its length in bytes.
+///
+/// Returns `None` if the text doesn't start with a custom emoji, including if the ID is out of range.
+pub fn parse_custom_emoji(text: &str) -> Option<(ReactionType, usize)> {
+    let captures = CUSTOM_EMOJI_REGEX.captures(text)?;
+    let id = captures[3].parse().ok()?;
+    Some((ReactionType::Custom {
+        animated: !captures[1].is_empty(),
+        id: EmojiId(id),
+        name: Some(captures[2].to_owned())
+    }, captures[0].len()))
+}
+
+/// Resolves a shortcode like `thumbsup` (without the surrounding colons) to one of the given custom emoji with that name or, if there is none, to a Unicode emoji.
+pub fn parse_shortcode(shortcode: &str, guild_emojis: &HashMap<EmojiId, Emoji>) -> Option<ReactionType> {
+    if let Some(custom) = guild_emojis.values().find(|emoji| emoji.name == shortcode) {
+        Some(ReactionType::Custom {
+            animated: custom.animated,
+            id: custom.id,
+            name: Some(custom.name.clone())
+        })
+    } else {
+        emojis::get_by_shortcode(shortcode).map(|emoji| ReactionType::Unicode(emoji.as_str().to_owned()))
+    }
+}
+
+/// Rebuilds the emoji database, e.g. after the twemoji directory was updated, and returns it.
+///
+/// The bundled emoji list is used unless a twemoji directory is configured, in which case the emoji are read from the file names in that directory. If that directory can't be read, this falls back to the bundled list.
+pub fn reload(paths: &Paths) -> Arc<Vec<String>> {
+    let emoji = if let Some(ref twemoji) = paths.twemoji {
+        match scan_twemoji(twemoji) {
+            Ok(emoji) => Arc::new(emoji.into_iter().collect()),
+            Err(e) => {
+                eprintln!("failed to read twemoji directory {}, using bundled emoji: {}", twemoji.display(), e);
+                Arc::clone(&BUNDLED_EMOJI)
             }
         }
-        Ok(Iter {
-            text,
-            emoji: emoji.into_iter().collect()
-        })
+    } else {
+        Arc::clone(&BUNDLED_EMOJI)
+    };
+    *DATABASE.write().expect("emoji database lock poisoned") = Some(Arc::clone(&emoji));
+    emoji
+}
+
+/// Reads the emoji from the file names in a twemoji SVG directory.
+fn scan_twemoji(dir: &Path) -> Result<BTreeSet<String>, Error> {
+    let mut emoji = BTreeSet::default();
+    for entry in fs::read_dir(dir)? {
+        let file_name = entry?.file_name().into_string()?;
+        if let Some(capture) = FILENAME_REGEX.captures(&file_name).and_then(|captures| captures.get(1)) {
+            // convert the filename encoding the emoji (e.g. 1f3f3-fe0f-200d-1f308.svg) to the emoji itself (e.g. 🏳️‍🌈)
+            emoji.insert(capture.as_str().split('-').filter_map(|hex| u32::from_str_radix(hex, 16).ok().and_then(::std::char::from_u32)).collect());
+        }
     }
+    Ok(emoji)
 }
 
-impl Iterator for Iter {
-    type Item = ReactionType;
+impl Iter {
+    /// Yield each distinct emoji only once, in order of first appearance. Custom emoji are considered the same if they have the same ID.
+    ///
+    /// If combined with [`normalized`](Iter::normalized), emoji which are the same after normalization are only yielded once.
+    pub fn unique(mut self) -> Iter {
+        self.seen = Some(HashSet::default());
+        self
+    }
 
-    fn next(&mut self) -> Option<ReactionType> {
-        let text = mem::replace(&mut self.text, String::default());
-        let mut text = &text[..];
+    /// Returns an iterator which also yields the byte range of each emoji in the text.
+    pub fn spans(self) -> Spans {
+        Spans(self)
+    }
+
+    fn next_span(&mut self) -> Option<(Range<usize>, ReactionType)> {
         loop {
-            if let Some(captures) = CUSTOM_EMOJI_REGEX.captures(text) {
-                let capture = captures.get(0).expect("failed to capture match object").as_str();
-                if let Ok(emoji_id) = EmojiIdentifier::from_str(capture) {
-                    self.text = text[capture.len()..].to_owned();
-                    break Some(emoji_id.into());
+            let (range, emoji) = self.next_raw_span()?;
+            let emoji = match emoji {
+                ReactionType::Unicode(emoji) if self.normalize => ReactionType::Unicode(normalize(&emoji)),
+                emoji => emoji
+            };
+            if let Some(ref mut seen) = self.seen {
+                let key = if let ReactionType::Custom { id, .. } = emoji { Ok(id) } else { Err(emoji.to_string()) };
+                if !seen.insert(key) { continue }
+            }
+            break Some((range, emoji))
+        }
+    }
+
+    /// Returns the next emoji as it appears in the text.
+    fn next_raw_span(&mut self) -> Option<(Range<usize>, ReactionType)> {
+        let (range, emoji) = loop {
+            let text = &self.text[self.pos..];
+            if let Some((emoji, len)) = parse_custom_emoji(text) {
+                break (self.pos..self.pos + len, emoji);
+            }
+            if let Some(captures) = SHORTCODE_REGEX.captures(text) {
+                if let Some(emoji) = parse_shortcode(&captures[1], &self.guild_emojis) {
+                    break (self.pos..self.pos + captures[0].len(), emoji);
                 }
             }
-            if let Some(emoji) = self.emoji.iter().rev().filter(|&emoji| text.starts_with(emoji)).next() { // longest emoji first
-                self.text = text[emoji.len()..].to_owned();
-                break Some(ReactionType::Unicode(emoji.to_owned()));
+            if let Some(grapheme) = text.graphemes(true).next() {
+                if is_emoji_grapheme(&self.emoji, grapheme) {
+                    break (self.pos..self.pos + grapheme.len(), ReactionType::Unicode(grapheme.to_owned()));
+                }
             }
-            match text.char_indices().nth(1) {
-                Some((idx, _)) => { text = &text[idx..]; }
-                None => { break None; }
+            match text.grapheme_indices(true).nth(1) {
+                Some((idx, _)) => { self.pos += idx; }
+                None => {
+                    self.pos = self.text.len();
+                    return None;
+                }
             }
-        }
+        };
+        self.pos = range.end;
+        Some((range, emoji))
+    }
+}
+
+impl Iterator for Iter {
+    type Item = ReactionType;
+
+    fn next(&mut self) -> Option<ReactionType> {
+        self.next_span().map(|(_, emoji)| emoji)
+    }
+}
+
+/// An iterator over all the emoji in a message along with their byte ranges, see [`Iter::spans`].
+pub struct Spans(Iter);
+
+impl Iterator for Spans {
+    type Item = (Range<usize>, ReactionType);
+
+    fn next(&mut self) -> Option<(Range<usize>, ReactionType)> {
+        self.0.next_span()
     }
 }
 
@@ -107,3 +338,234 @@ pub fn nth_letter(n: u8) -> ReactionType {
     if n >= 26 { panic!("letter not in range"); }
     ReactionType::Unicode(::std::char::from_u32('🇦' as u32 + n as u32).expect("failed to create regional indicator").to_string())
 }
+
+/// Reacts to the message with a letter for each of the given options (see [`nth_letter`]), waits for the given duration, and returns each option with the number of users who reacted with its letter, not counting the bot itself.
+///
+/// # Panics
+///
+/// If there are more than 26 options.
+pub async fn poll<T>(ctx: &Context, msg: &Message, options: Vec<T>, duration: Duration) -> serenity::Result<Vec<(T, u64)>> {
+    let letters = (0..options.len()).map(|i| nth_letter(i as u8)).collect::<Vec<_>>();
+    for letter in &letters {
+        msg.react(ctx, letter.clone()).await?;
+    }
+    sleep(duration).await;
+    let reactions = msg.channel_id.message(ctx, msg.id).await?.reactions;
+    Ok(options.into_iter().zip(letters).map(|(option, letter)| {
+        let count = reactions.iter()
+            .find(|reaction| reaction.reaction_type == letter)
+            .map_or(0, |reaction| reaction.count - if reaction.me { 1 } else { 0 });
+        (option, count)
+    }).collect())
+}
+
+/// An entry in the manifest written by [`backup`].
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestEntry {
+    id: EmojiId,
+    name: String,
+    animated: bool,
+    require_colons: bool,
+    roles: Vec<RoleId>,
+    /// The image file name, relative to the manifest.
+    file: String
+}
+
+/// Downloads the images of all custom emoji of the given guild into a subdirectory of the emoji backup directory named after the guild ID, along with a `manifest.json` listing each emoji's metadata, so the emoji can be restored if they're lost. Returns the number of emoji backed up.
+pub async fn backup(ctx: &Context, paths: &Paths, guild_id: GuildId) -> Result<usize, crate::Error> {
+    let dir = paths.emoji_backup.join(guild_id.to_string());
+    tokio::fs::create_dir_all(&dir).await?;
+    let client = reqwest::Client::new();
+    let mut manifest = Vec::default();
+    for emoji in guild_id.emojis(ctx).await? {
+        let file = format!("{}.{}", emoji.id, if emoji.animated { "gif" } else { "png" });
+        let image = client.get(&emoji.url()).send().await?.error_for_status()?.bytes().await?;
+        tokio::fs::write(dir.join(&file), image).await?;
+        manifest.push(ManifestEntry {
+            id: emoji.id,
+            name: emoji.name,
+            animated: emoji.animated,
+            require_colons: emoji.require_colons,
+            roles: emoji.roles,
+            file
+        });
+    }
+    tokio::fs::write(dir.join("manifest.json"), serde_json::to_vec_pretty(&manifest)?).await?; //TODO use async-json
+    Ok(manifest.len())
+}
+
+/// Backs up this server's custom emoji to the bot's data directory.
+#[command]
+#[only_in(guilds)]
+#[required_permissions(ADMINISTRATOR)]
+pub async fn emojibackup(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+    let guild_id = msg.guild_id.expect("not in guild but only_in(guilds) passed");
+    let paths = ctx.data.read().await.get::<Paths>().expect("missing paths").clone();
+    let num_emoji = backup(ctx, &paths, guild_id).await?;
+    msg.reply(ctx, format!("{} Emoji gesichert", num_emoji)).await?;
+    Ok(())
+}
+
+/// The stats metric for uses of the given emoji in the given guild.
+fn usage_metric(guild_id: GuildId, emoji: &ReactionType) -> String {
+    if let ReactionType::Custom { id, .. } = emoji {
+        format!("emoji:{}:custom:{}", guild_id, id)
+    } else {
+        format!("emoji:{}:unicode:{}", guild_id, emoji)
+    }
+}
+
+/// The stats metrics to increment for the emoji in a message. Each emoji is counted once per message, and Unicode emoji are counted in their [`normalize`]d form.
+///
+/// Scanning the message can be slow, especially if the emoji database has to be loaded first, so this should be called before locking the stats.
+pub fn message_metrics(paths: &Paths, guild_id: GuildId, text: &str) -> Vec<String> {
+    Iter::new(paths, text.to_owned()).normalized().unique().map(|emoji| usage_metric(guild_id, &emoji)).collect()
+}
+
+/// Subscribes to all reactions to count them towards the emoji statistics.
+pub fn register(listeners: &mut ReactionListeners) {
+    listeners.subscribe(Filter::default(), on_reaction);
+}
+
+fn on_reaction(ctx: Context, reaction: Reaction, kind: reaction_listeners::Kind) -> Pin<Box<dyn Future<Output = Result<(), crate::Error>> + Send>> {
+    Box::pin(async move {
+        if kind != reaction_listeners::Kind::Add { return Ok(()) }
+        let guild_id = if let Some(guild_id) = reaction.guild_id { guild_id } else { return Ok(()) };
+        if reaction.user_id == Some(ctx.cache.current_user_id().await) { return Ok(()) } // don't count e.g. poll reactions
+        record_reaction(ctx.data.write().await.get_mut::<Stats>().expect("missing stats"), guild_id, &reaction.emoji);
+        Ok(())
+    })
+}
+
+/// Counts a reaction towards the guild's emoji statistics.
+pub fn record_reaction(stats: &mut Stats, guild_id: GuildId, emoji: &ReactionType) {
+    let emoji = match emoji {
+        ReactionType::Unicode(emoji) => ReactionType::Unicode(normalize(emoji)),
+        emoji => emoji.clone()
+    };
+    stats.increment(usage_metric(guild_id, &emoji));
+}
+
+/// Shows emoji statistics or searches for emoji. See `!emoji top` and `!emoji find`.
+#[command]
+#[only_in(guilds)]
+#[sub_commands(emoji_find, emoji_top)]
+pub async fn emoji(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+    msg.reply(ctx, "benutze `!emoji top [custom|unicode] [day|week|month|year|all]` oder `!emoji find <Suchbegriff>`").await?;
+    Ok(())
+}
+
+/// Scores how well the query matches the given name, lower is better. Exact matches are best, followed by prefixes, substrings, and finally names which contain the query's characters in order. Returns `None` if the name doesn't match at all.
+fn fuzzy_score(query: &str, name: &str) -> Option<usize> {
+    let name = name.to_lowercase();
+    let extra = name.chars().count().saturating_sub(query.chars().count());
+    if name == query {
+        Some(0)
+    } else if name.starts_with(query) {
+        Some(1_000 + extra)
+    } else if name.contains(query) {
+        Some(2_000 + extra)
+    } else {
+        let mut name_chars = name.chars();
+        if query.chars().all(|c| name_chars.any(|name_char| name_char == c)) { Some(3_000 + extra) } else { None }
+    }
+}
+
+/// Searches Unicode emoji by name and shortcode and this server's custom emoji by name, and lists the best matches.
+#[command("find")]
+#[usage = "<Suchbegriff>"]
+#[example = "herz"]
+#[only_in(guilds)]
+async fn emoji_find(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.expect("not in guild but only_in(guilds) passed");
+    let query = args.rest().trim().trim_matches(':').to_lowercase();
+    if query.is_empty() {
+        msg.reply(ctx, "bitte gib einen Suchbegriff an").await?;
+        return Ok(())
+    }
+    let mut matches = Vec::default();
+    for emoji in guild_id.emojis(ctx).await? {
+        if let Some(score) = fuzzy_score(&query, &emoji.name) {
+            matches.push((score, format!("{} `:{}:`", emoji, emoji.name)));
+        }
+    }
+    for emoji in emojis::iter() {
+        let score = emoji.shortcode().and_then(|shortcode| fuzzy_score(&query, shortcode)).into_iter()
+            .chain(fuzzy_score(&query, emoji.name()))
+            .min();
+        if let Some(score) = score {
+            matches.push((score, if let Some(shortcode) = emoji.shortcode() {
+                format!("{} `:{}:` ({})", emoji.as_str(), shortcode, emoji.name())
+            } else {
+                format!("{} ({})", emoji.as_str(), emoji.name())
+            }));
+        }
+    }
+    if matches.is_empty() {
+        msg.reply(ctx, "keine passenden Emoji gefunden").await?;
+        return Ok(())
+    }
+    matches.sort_by_key(|&(score, _)| score); // stable, so custom emoji come first among equally good matches
+    msg.channel_id.send_message(ctx, |m| m.embed(|e| e
+        .title(format!("Emoji für „{}“", query))
+        .description(matches.into_iter().take(10).map(|(_, line)| line).collect::<Vec<_>>().join("\n"))
+    )).await?;
+    Ok(())
+}
+
+/// Lists the most used emoji on this server, counting both messages and reactions. Optionally restricted to custom or Unicode emoji and to the last day, week, month (default), or year, or `all` for all time.
+#[command("top")]
+#[usage = "[custom|unicode] [day|week|month|year|all]"]
+#[example = "custom week"]
+#[only_in(guilds)]
+async fn emoji_top(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.expect("not in guild but only_in(guilds) passed");
+    let mut kind = None;
+    let mut timeframe = ("im letzten Monat", Some(ChronoDuration::days(30)));
+    for arg in args.raw() {
+        match &*arg.to_ascii_lowercase() {
+            "custom" => kind = Some("custom:"),
+            "unicode" => kind = Some("unicode:"),
+            "day" => timeframe = ("heute", Some(ChronoDuration::days(1))),
+            "week" => timeframe = ("in der letzten Woche", Some(ChronoDuration::weeks(1))),
+            "month" => timeframe = ("im letzten Monat", Some(ChronoDuration::days(30))),
+            "year" => timeframe = ("im letzten Jahr", Some(ChronoDuration::days(365))),
+            "all" => timeframe = ("insgesamt", None),
+            _ => {
+                msg.reply(ctx, "unbekanntes Argument, benutze `!emoji top [custom|unicode] [day|week|month|year|all]`").await?;
+                return Ok(())
+            }
+        }
+    }
+    let (timeframe_name, duration) = timeframe;
+    let mut totals = {
+        let data = ctx.data.read().await;
+        let stats = data.get::<Stats>().expect("missing stats");
+        stats.totals(&format!("emoji:{}:{}", guild_id, kind.unwrap_or_default()), duration.map(|duration| Utc::now() - duration))
+    }.into_iter().collect::<Vec<_>>();
+    totals.sort_by(|(_, count1), (_, count2)| count2.cmp(count1));
+    let guild_emojis = guild_id.emojis(ctx).await?.into_iter().map(|emoji| (emoji.id, emoji)).collect::<HashMap<_, _>>();
+    let mut description = String::default();
+    for (rank, (key, count)) in totals.into_iter().take(20).enumerate() {
+        let key = if let Some(kind) = kind { format!("{}{}", kind, key) } else { key };
+        let emoji = if let Some(id) = key.strip_prefix("custom:") {
+            match id.parse().ok().and_then(|id| guild_emojis.get(&EmojiId(id))) {
+                Some(emoji) => emoji.to_string(),
+                None => format!("gelöschtes Emoji ({})", id)
+            }
+        } else {
+            key.strip_prefix("unicode:").unwrap_or(&key).to_owned()
+        };
+        description.push_str(&format!("{}. {} — {}\n", rank + 1, emoji, count));
+    }
+    if description.is_empty() {
+        msg.reply(ctx, format!("{} wurden keine Emoji benutzt", timeframe_name)).await?;
+        return Ok(())
+    }
+    msg.channel_id.send_message(ctx, |m| m.embed(|e| e
+        .title(format!("Meistbenutzte Emoji {}", timeframe_name))
+        .description(description)
+    )).await?;
+    Ok(())
+}
@@ -0,0 +1,240 @@
+//! Infrastructure shared by hidden-role games like [Werewolf](crate::werewolf): the game role and channel permissions, the phase timers, and votes with delegation.
+//!
+//! A game implements [`HiddenRoleGame`] on its per-channel state, which is stored in the typemap as a `HashMap<GameId, _>`, and uses [`run_timeouts`] to drive its phases.
+
+use {
+    std::{
+        cmp::Ordering,
+        collections::{
+            HashMap,
+            HashSet,
+        },
+        hash::Hash,
+        iter,
+        time::Duration,
+    },
+    async_trait::async_trait,
+    serenity::{
+        model::prelude::*,
+        prelude::*,
+    },
+    tokio::time::sleep,
+    crate::Error,
+};
+
+/// A game is identified by its guild and its text channel.
+pub type GameId = (GuildId, ChannelId);
+
+/// The phase timers started by a game. A timer which was cancelled, e.g. because the phase ended early, doesn't do anything when it runs out.
+#[derive(Debug, Default)]
+pub struct Timeouts(Vec<bool>);
+
+impl Timeouts {
+    /// Registers a new timer and returns its index.
+    pub fn start(&mut self) -> usize {
+        let idx = self.0.len();
+        self.0.push(true);
+        idx
+    }
+
+    pub fn cancel(&mut self, timeout_idx: usize) {
+        self.0[timeout_idx] = false;
+    }
+
+    pub fn cancel_all(&mut self) {
+        self.0 = vec![false; self.0.len()];
+    }
+
+    pub fn is_cancelled(&self, timeout_idx: usize) -> bool {
+        !self.0[timeout_idx]
+    }
+
+    pub fn any_active(&self) -> bool {
+        self.0.iter().any(|&active| active)
+    }
+}
+
+/// The per-channel state of a hidden-role game.
+///
+/// Games are played in a set of public channels by players who get a game role for the duration of the game. The required methods describe the game, the provided ones handle the Discord side of things.
+#[async_trait]
+pub trait HiddenRoleGame: Send + Sync {
+    fn id(&self) -> GameId;
+    /// The channels where the game is discussed, which are locked for everyone but the players while the game is running.
+    fn channels(&self) -> Vec<ChannelId>;
+    /// The role given to the players while they're signed up or playing.
+    fn game_role(&self) -> RoleId;
+    /// The players of the running game, who are hidden from channels set up using [`HiddenRoleGame::hide_from_players`].
+    fn players(&self) -> &[UserId];
+    fn timeouts(&self) -> &Timeouts;
+    fn timeouts_mut(&mut self) -> &mut Timeouts;
+
+    /// Writes the game to disk so it can be resumed after a restart.
+    async fn save(&self) -> Result<(), Error>;
+
+    /// Called when the timer of the current phase runs out. Returns the duration of the next phase, or `None` if there is no next phase or its timer is started elsewhere.
+    async fn handle_timeout(&mut self, ctx: &Context) -> Result<Option<Duration>, Error>;
+
+    /// How long before the end of a phase of the given duration [`HiddenRoleGame::remind`] should be called, if at all.
+    fn reminder(&self, phase_duration: Duration) -> Option<Duration>;
+
+    /// Reminds the players that the current phase ends soon.
+    async fn remind(&self, ctx: &Context, remaining: Duration) -> Result<(), Error>;
+
+    /// Registers the timer for a new phase and returns its index. Games can override this to schedule additional tasks for the phase.
+    fn start_phase_timeout(&mut self, _: &Context, _: Duration) -> usize {
+        self.timeouts_mut().start()
+    }
+
+    async fn add_game_role(&self, ctx: &Context, player: UserId) -> Result<(), Error> {
+        let (guild, _) = self.id();
+        let roles = iter::once(self.game_role()).chain(guild.member(ctx, player).await?.roles.into_iter());
+        guild.edit_member(ctx, player, |m| m.roles(roles)).await?;
+        Ok(())
+    }
+
+    async fn remove_game_role_from(&self, ctx: &Context, player: UserId) -> Result<(), Error> {
+        let (guild, _) = self.id();
+        let game_role = self.game_role();
+        let roles = guild.member(ctx, player).await?.roles.into_iter().filter(|&role| role != game_role);
+        guild.edit_member(ctx, player, |m| m.roles(roles)).await?;
+        Ok(())
+    }
+
+    /// Removes the game role from everyone who has it.
+    async fn remove_game_role(&self, ctx: &Context) -> Result<(), Error> {
+        let (guild, _) = self.id();
+        for mut member in guild.members(ctx, None, None).await? { //TODO make sure all members are checked
+            if member.roles(ctx).await.map_or(false, |roles| roles.into_iter().any(|role| role.id == self.game_role())) {
+                member.remove_role(ctx, self.game_role()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Keeps everyone but the players from writing or reacting in the game channels.
+    async fn lock_channels(&self, ctx: &Context) -> Result<(), Error> {
+        let (guild, _) = self.id();
+        let everyone = RoleId(guild.0); // @everyone role, same ID as the guild
+        for channel in self.channels() {
+            channel.create_permission(ctx, &PermissionOverwrite {
+                kind: PermissionOverwriteType::Role(everyone),
+                allow: Permissions::empty(),
+                deny: Permissions::SEND_MESSAGES | Permissions::ADD_REACTIONS,
+            }).await?;
+        }
+        Ok(())
+    }
+
+    async fn unlock_channels(&self, ctx: &Context) -> Result<(), Error> {
+        let (guild, _) = self.id();
+        let everyone = RoleId(guild.0); // @everyone role, same ID as the guild
+        for channel in self.channels() {
+            channel.delete_permission(ctx, PermissionOverwriteType::Role(everyone)).await?;
+        }
+        Ok(())
+    }
+
+    /// Makes the given channel invisible to the players, e.g. for spectators.
+    async fn hide_from_players(&self, ctx: &Context, channel: ChannelId) -> Result<(), Error> {
+        for &player in self.players() {
+            channel.create_permission(ctx, &hidden_overwrite(player)).await?;
+        }
+        Ok(())
+    }
+
+    /// Undoes [`HiddenRoleGame::hide_from_players`].
+    async fn show_to_players(&self, ctx: &Context, channel: ChannelId) -> Result<(), Error> {
+        for &player in self.players() {
+            channel.delete_permission(ctx, PermissionOverwriteType::Member(player)).await?;
+        }
+        Ok(())
+    }
+
+    /// Creates a text channel which the players can't see, in the same category as the game's text channel.
+    async fn create_hidden_channel(&self, ctx: &Context, name: &str) -> Result<ChannelId, Error> {
+        let (guild, text_channel) = self.id();
+        let category = text_channel.to_channel(ctx).await?.guild().and_then(|channel| channel.category_id);
+        let hidden = self.players().iter().map(|&player| hidden_overwrite(player)).collect::<Vec<_>>();
+        let channel = guild.create_channel(ctx, |c| {
+            c.name(name).kind(ChannelType::Text).permissions(hidden);
+            if let Some(category) = category { c.category(category); }
+            c
+        }).await?;
+        Ok(channel.id)
+    }
+}
+
+fn hidden_overwrite(player: UserId) -> PermissionOverwrite {
+    PermissionOverwrite {
+        kind: PermissionOverwriteType::Member(player),
+        allow: Permissions::empty(),
+        deny: Permissions::READ_MESSAGES,
+    }
+}
+
+/// Waits for the given phase timer of the game and handles it, then does the same for the timers of the following phases until there is no next phase, the timer is cancelled, or the game was moved to another channel.
+pub async fn run_timeouts<G: HiddenRoleGame + TypeMapKey<Value = HashMap<GameId, G>>>(ctx: &Context, game: GameId, mut timeout_idx: usize, mut sleep_duration: Duration) -> Result<(), Error> {
+    loop {
+        let reminder = ctx.data.read().await.get::<G>().expect("missing game state").get(&game).and_then(|state_ref| state_ref.reminder(sleep_duration));
+        if let Some(reminder) = reminder {
+            sleep(sleep_duration - reminder).await;
+            let data = ctx.data.read().await;
+            let state_ref = if let Some(state_ref) = data.get::<G>().expect("missing game state").get(&game) { state_ref } else { break }; // the game was moved to another channel, so this timer is stale
+            if state_ref.timeouts().is_cancelled(timeout_idx) { break }
+            state_ref.remind(ctx, reminder).await?;
+            sleep_duration = reminder;
+        }
+        sleep(sleep_duration).await;
+        let mut data = ctx.data.write().await;
+        let state_ref = if let Some(state_ref) = data.get_mut::<G>().expect("missing game state").get_mut(&game) { state_ref } else { break }; // the game was moved to another channel, so this timer is stale
+        if state_ref.timeouts().is_cancelled(timeout_idx) { break }
+        state_ref.timeouts_mut().cancel(timeout_idx);
+        let duration = state_ref.handle_timeout(ctx).await?;
+        state_ref.save().await?;
+        if let Some(duration) = duration {
+            if state_ref.timeouts().any_active() { break }
+            timeout_idx = state_ref.start_phase_timeout(ctx, duration);
+            sleep_duration = duration;
+        } else {
+            break
+        }
+    }
+    Ok(())
+}
+
+/// Follows the vote delegations starting at the given player. The first item is the player themselves, the last one is the player who ultimately casts the vote.
+pub fn delegation_chain(delegations: &HashMap<UserId, UserId>, player: UserId) -> impl Iterator<Item = UserId> + '_ {
+    iter::successors(Some(player), move |player| delegations.get(player).copied())
+}
+
+/// The number of votes cast by the given player, including any votes delegated to them.
+pub fn vote_weight(delegations: &HashMap<UserId, UserId>, voter: UserId) -> usize {
+    1 + delegations.keys()
+        .filter(|&&delegator| delegation_chain(delegations, delegator).last() == Some(voter))
+        .count()
+}
+
+/// Returns the number of votes each option received, counting delegated votes.
+pub fn vote_counts<V: Copy + Eq + Hash>(votes: &HashMap<UserId, V>, delegations: &HashMap<UserId, UserId>) -> HashMap<V, usize> {
+    let mut vote_count = HashMap::<V, usize>::default();
+    for (&voter, &vote) in votes.iter() {
+        *vote_count.entry(vote).or_insert(0) += vote_weight(delegations, voter);
+    }
+    vote_count
+}
+
+/// Returns the highest number of votes received, and the options which received that many.
+pub fn vote_leads<V: Copy + Eq + Hash>(votes: &HashMap<UserId, V>, delegations: &HashMap<UserId, UserId>) -> (usize, HashSet<V>) {
+    vote_counts(votes, delegations).into_iter()
+        .fold((0, HashSet::default()), |(max_votes, mut voted), (vote, count)|
+            match count.cmp(&max_votes) {
+                Ordering::Less => (max_votes, voted),
+                Ordering::Equal => {
+                    voted.insert(vote);
+                    (max_votes, voted)
+                }
+                Ordering::Greater => (count, iter::once(vote).collect()),
+            }
+        )
+}
@@ -22,6 +22,40 @@ serenity_utils::ipc! {
         Ok(())
     }
 
+    /// Writes the profiles of all former Gefolge guild members as a JSON array to the given path.
+    async fn export_former_members(ctx: &Context, path: String) -> Result<(), String> {
+        let former = crate::user_list::UserList::get(ctx).await.former().await.map_err(|e| format!("failed to load former members: {}", e))?;
+        let buf = serde_json::to_vec_pretty(&former).map_err(|e| format!("failed to serialize former members: {}", e))?;
+        tokio::fs::write(path, buf).await.map_err(|e| format!("failed to write former members: {}", e))?;
+        Ok(())
+    }
+
+    /// Writes a list of all current Gefolge guild members to the given path, in the given format (`csv` or `json`).
+    async fn export_members(ctx: &Context, format: String, path: String) -> Result<(), String> {
+        let format = format.parse::<crate::user_list::ExportFormat>().map_err(|()| format!("unknown export format: {}", format))?;
+        let doc = crate::user_list::export(ctx, format).await.map_err(|e| format!("failed to export members: {}", e))?;
+        tokio::fs::write(path, doc).await.map_err(|e| format!("failed to write members: {}", e))?;
+        Ok(())
+    }
+
+    /// Writes the werewolf seasons of all guilds as JSON to the given path.
+    async fn export_seasons(ctx: &Context, path: String) -> Result<(), String> {
+        let paths = ctx.data.read().await.get::<crate::paths::Paths>().ok_or_else(|| format!("missing paths"))?.clone();
+        let seasons = crate::tournament::Seasons::load(&paths).await.map_err(|e| format!("failed to load seasons: {}", e))?;
+        let buf = serde_json::to_vec_pretty(&seasons).map_err(|e| format!("failed to serialize seasons: {}", e))?;
+        tokio::fs::write(path, buf).await.map_err(|e| format!("failed to write seasons: {}", e))?;
+        Ok(())
+    }
+
+    /// Writes all finished werewolf games as JSON to the given path.
+    async fn export_werewolf_history(ctx: &Context, path: String) -> Result<(), String> {
+        let paths = ctx.data.read().await.get::<crate::paths::Paths>().ok_or_else(|| format!("missing paths"))?.clone();
+        let history = crate::werewolf_history::History::load(&paths).await.map_err(|e| format!("failed to load werewolf history: {}", e))?;
+        let buf = serde_json::to_vec_pretty(&history).map_err(|e| format!("failed to serialize werewolf history: {}", e))?;
+        tokio::fs::write(path, buf).await.map_err(|e| format!("failed to write werewolf history: {}", e))?;
+        Ok(())
+    }
+
     /// Sends the given message, unescaped, directly to the given user.
     async fn msg(ctx: &Context, rcpt: UserId, msg: String) -> Result<(), String> {
         rcpt.create_dm_channel(ctx).await
@@ -37,6 +71,20 @@ serenity_utils::ipc! {
         Ok(())
     }
 
+    /// Reloads the given section of the config file, one of `guilds`, `stats`, `twitch`, `webhooks`, or `werewolf`, without affecting the rest of the config. The changes are posted to the Gefolge log channel.
+    async fn reload_config(ctx: &Context, section: String) -> Result<(), String> {
+        let section = section.parse::<crate::config::Section>().map_err(|()| format!("unknown config section: {}", section))?;
+        let paths = ctx.data.read().await.get::<crate::paths::Paths>().ok_or_else(|| format!("missing paths"))?.clone();
+        let changes = crate::config::Config::write(ctx).await.reload_section(&paths, section).await.map_err(|e| format!("failed to reload config section {}: {}", section, e))?;
+        if !changes.is_empty() {
+            let mut builder = serenity::utils::MessageBuilder::default();
+            builder.push(format!("Der Abschnitt {} der Einstellungen wurde neu geladen:", section));
+            builder.push_codeblock_safe(changes.join("\n"), None);
+            crate::log_channel::post(ctx, GEFOLGE, builder).await.map_err(|e| format!("failed to post config changes: {}", e))?;
+        }
+        Ok(())
+    }
+
     /// Changes the display name for the given user in the Gefolge guild to the given string.
     ///
     /// If the given string is equal to the user's username, the display name will instead be removed.
@@ -52,4 +100,11 @@ serenity_utils::ipc! {
             Err(e) => Err(e.to_string()),
         }
     }
+
+    /// Checks the member list for orphaned, missing, and unparseable profiles, fixing them if `fix` is true. The results are posted to the Gefolge log channel.
+    async fn verify_members(ctx: &Context, fix: bool) -> Result<(), String> {
+        let verification = crate::user_list::verify(ctx, fix).await.map_err(|e| format!("failed to verify member list: {}", e))?;
+        crate::log_channel::post(ctx, GEFOLGE, verification).await.map_err(|e| format!("failed to post member list verification: {}", e))?;
+        Ok(())
+    }
 }
@@ -1,21 +1,33 @@
-//! German language utilities.
+//! German language utilities, and the messages posted by the Werewolf game.
 
 #![allow(missing_docs)] //TODO remove
 
 use {
     std::{
         borrow::Cow,
+        collections::HashMap,
         fmt
     },
+    chrono::prelude::*,
     num_traits::One,
     quantum_werewolf::game::{
         Faction,
+        NightAction,
         Role
     },
     serenity::{
-        model::user::User,
+        model::{
+            id::{
+                ChannelId,
+                RoleId,
+                UserId
+            },
+            permissions::Permissions,
+            user::User
+        },
         utils::MessageBuilder
-    }
+    },
+    crate::werewolf::RolePreset
 };
 
 pub enum Gender { M, F, N }
@@ -60,6 +72,17 @@ pub fn cardinal<N: Eq + One + ToString>(n: N, case: Case, gender: Gender) -> Cow
     }
 }
 
+/// A number followed by a noun in the singular or plural, e.g. “einer Stimme” or “3 Stimmen”.
+pub fn counted<N: Eq + One + ToString>(n: N, case: Case, gender: Gender, singular: &str, plural: &str) -> String {
+    let noun = if n == N::one() { singular } else { plural };
+    format!("{} {}", cardinal(n, case, gender), noun)
+}
+
+/// Like [`counted`], but always using digits, e.g. “1 Werwolf” or “2 Werwölfe”.
+pub fn numbered(n: usize, singular: &str, plural: &str) -> String {
+    format!("{} {}", n, if n == 1 { singular } else { plural })
+}
+
 pub fn faction_gender(faction: Faction) -> Option<Gender> {
     match faction {
         Faction::Village => Some(N),
@@ -145,6 +168,116 @@ pub fn role_name(role: Role, case: Case, plural: bool) -> Cow<'static, str> {
     }
 }
 
+/// The heading for a player's record with the given role in `!wwstats`. Werewolf ranks are ignored, and `None` stands for games the player survived.
+pub fn role_stats_label(role: Option<Role>) -> Cow<'static, str> {
+    match role {
+        Some(Role::Werewolf(_)) => "Werwolf".into(),
+        Some(role) => role_name(role, Nom, false),
+        None => "überlebt (Rolle unbekannt)".into(),
+    }
+}
+
+/// Parses a role name as used in `!roles` and `!wwhelp`. Werewolf ranks are ignored.
+pub fn parse_role(name: &str) -> Option<Role> {
+    match &*name.trim().to_lowercase() {
+        "werwolf" | "werwölfe" | "werwoelfe" => Some(Role::Werewolf(0)),
+        "detektiv" | "detektive" | "seher" | "seherin" => Some(Role::Detective),
+        "heiler" | "heilerin" => Some(Role::Healer),
+        "dorfbewohner" | "dorfbewohnerin" => Some(Role::Villager),
+        _ => None,
+    }
+}
+
+fn role_faction(role: Role) -> Faction {
+    match role {
+        Role::Werewolf(_) => Faction::Werewolves,
+        Role::Detective | Role::Healer | Role::Villager => Faction::Village,
+    }
+}
+
+/// The role's night action, addressed to a player who might have the role. Used by both the role DM and `!wwhelp`.
+pub fn role_ability(role: Role) -> Option<String> {
+    let mut builder = MessageBuilder::default();
+    match role {
+        Role::Healer => {
+            builder.push("Solange du noch lebst, kannst du jede Nacht einen lebenden Spieler deiner Wahl heilen (");
+            builder.push_mono_safe("heal <player>");
+            builder.push("). In allen Universen, in denen du lebst und Heiler bist, kann dieser Spieler in dieser Nacht nicht sterben. Du kannst keinen Spieler heilen, den du schon in der vorherigen Nacht geheilt hast.");
+        }
+        Role::Detective => {
+            builder.push("Solange du noch lebst, kannst du jede Nacht einen Spieler deiner Wahl untersuchen (");
+            builder.push_mono_safe("investigate <player>");
+            builder.push("). Falls es mindestens ein Universum gibt, in dem du Detektiv bist, erfährst du die Partei dieses Spielers in einem zufälligen solchen Universum. Alle Universen, in denen du Detektiv bist und der Spieler nicht diese Partei hat, werden eliminiert.");
+        }
+        Role::Werewolf(_) => {
+            builder.push("Solange du noch lebst, tötest du in jeder Nacht einen lebenden Spieler deiner Wahl (");
+            builder.push_mono_safe("kill <player>");
+            builder.push("). In allen Universen, in denen du der Werwolf mit der kleinsten Rangnummer unter den lebenden Werwölfen bist, stirbt dieser Spieler.");
+        }
+        Role::Villager => return None,
+    }
+    Some(builder.build())
+}
+
+/// What happens to each player's quantum state every morning and when they die. Used by both the role DM and `!wwhelp`.
+pub fn quantum_rules() -> [&'static str; 3] {
+    [
+        "Jeden Morgen wird öffentlich aber anonym dein Rollenrang sowie die relativen Häufigkeiten der Universen, in denen du zum Dorf gehörst, derer in denen du zu den Werwölfen gehörst, und derer in denen du tot bist angekündigt.",
+        "Wenn du in allen Universen tot bist, stirbst du.",
+        "Wenn du stirbst oder am Ende des Spiels wird aus den Universen, in denen du bis eben noch gelebt hast, ein zufälliges ausgewählt und du bekommst deine Identität aus diesem Universum. Alle anderen Quantenüberlagerungen verlieren diese Identität aus ihren Überlagerungen, und alle Universen, in denen du nicht diese Identität warst, werden eliminiert.",
+    ]
+}
+
+/// The overview shown by `!wwhelp` without arguments.
+pub fn rules_help() -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push_bold_line("Quantenwerwölfe");
+    builder.push_line("Zu Beginn bekommt jeder Spieler per DM eine Quantenüberlagerung aus allen Rollen im Spiel und einen Rollenrang. Jede mögliche Rollenverteilung ist ein Universum, und im Lauf des Spiels werden Universen eliminiert.");
+    builder.push_bold("Nacht:").push(" Alle lebenden Spieler schicken mir ihre Nachtaktionen per DM: ").push_mono("kill <player>");
+    builder.push(", und wer Heiler oder Detektiv sein könnte, auch ").push_mono("heal <player>").push(" bzw. ").push_mono("investigate <player>").push(". ");
+    builder.push_line("Ausgewertet werden alle Aktionen gemeinsam am Ende der Nacht: Geheilte Spieler sterben in dieser Nacht nicht, und die Ergebnisse der Untersuchungen kommen am Morgen per DM.");
+    builder.push_bold("Tag:").push(" Am Morgen wird die Wahrscheinlichkeitsverteilung angekündigt, abrufbar auch mit ").push_mono("wwprob").push(". Mit ").push_mono("vote <player>").push_line(" stimmt ihr ab, wen ihr lynchen wollt.");
+    for rule in &quantum_rules() {
+        builder.push_line(*rule);
+    }
+    builder.push("Rollen: Werwolf, Detektiv, Heiler, Dorfbewohner. Varianten: Gerber, Liebespaar, Amor, Jäger. Details mit ").push_mono("wwhelp <Rolle>").push(".");
+    builder.build()
+}
+
+/// The explanation of a single role shown by `!wwhelp <role>`.
+pub fn role_help(role: Role) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push_bold_safe(match role {
+        Role::Werewolf(_) => Cow::Borrowed("Werwolf"), // the rank is irrelevant here
+        Role::Detective | Role::Healer | Role::Villager => role_name(role, Nom, false),
+    });
+    builder.push_line(format!(" (Partei: {})", faction_name(role_faction(role), Nom)));
+    match role_ability(role) {
+        Some(ability) => builder.push(ability),
+        None => builder.push("Keine Nachtaktion. Wie alle Spieler stimmt der Dorfbewohner tagsüber mit ab."),
+    };
+    if let Role::Werewolf(_) = role {
+        builder.push_line("").push("Da jeder Spieler ein Werwolf sein könnte, schicken alle lebenden Spieler jede Nacht eine Tötung.");
+    }
+    builder.build()
+}
+
+pub fn tanner_help() -> &'static str {
+    "**Gerber** (Variante, keine Partei)\nEin zufälliger Spieler ist zusätzlich zu seiner Quantenrolle heimlich der Gerber. Er gewinnt allein, wenn er gelyncht wird, und sonst nie."
+}
+
+pub fn lovers_help() -> &'static str {
+    "**Liebespaar** (Variante)\nZwei zufällige Spieler sind heimlich ineinander verliebt. Wenn einer von beiden gewinnt, gewinnt auch der andere, egal zu welcher Partei sie gehören."
+}
+
+pub fn cupid_help() -> &'static str {
+    "**Amor** (Variante)\nEin zufälliger Spieler ist zusätzlich zu seiner Quantenrolle heimlich Amor. In der ersten Phase des Spiels wählt er mit `couple <player> <player>` ein Liebespaar, sonst wird es zufällig bestimmt. Wenn einer der Verliebten gewinnt, gewinnt auch der andere."
+}
+
+pub fn hunter_help() -> &'static str {
+    "**Jäger** (Variante)\nEin zufälliger Spieler ist zusätzlich zu seiner Quantenrolle heimlich der Jäger. Mit `aim <player>` wählt er jederzeit ein Ziel. Wenn er stirbt, wird sein Ziel am Ende des nächsten Tages anstelle der Abstimmung gelyncht."
+}
+
 pub fn zu(gender: Option<Gender>) -> Cow<'static, str> {
     match article(Dat, gender) {
         "dem" => "zum".into(),
@@ -152,3 +285,847 @@ pub fn zu(gender: Option<Gender>) -> Cow<'static, str> {
         art => format!("zu {}", art).into()
     }
 }
+
+// Werewolf messages. Deadlines are passed in as Discord timestamps.
+
+pub fn role_preset(preset: &RolePreset) -> String {
+    let mut roles = vec![numbered(preset.werewolves, "Werwolf", "Werwölfe")];
+    if preset.detective { roles.push(format!("Detektiv")); }
+    if preset.healer { roles.push(format!("Heiler")); }
+    if preset.tanner { roles.push(format!("Gerber")); }
+    if preset.lovers { roles.push(format!("Liebespaar")); }
+    if preset.cupid { roles.push(format!("Amor")); }
+    if preset.hunter { roles.push(format!("Jäger")); }
+    join(None, roles)
+}
+
+pub fn signup_title(closed: bool) -> String {
+    format!("🐺 Anmeldung für Werwölfe{}", if closed { " (geschlossen)" } else { "" })
+}
+
+pub fn signup_description(min_players: usize, max_players: Option<usize>, deadline: &str, rematch: bool) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push("Anmelden mit ").push_mono("in").push(", abmelden mit ").push_mono("out").push(". ");
+    if rematch {
+        builder.push("Bei dieser Revanche geht das auch mit einer ✅-Reaktion auf diese Nachricht. ");
+    }
+    builder.push(format!("Die Anmeldung endet {}, dann beginnt das Spiel, falls mindestens {} Spieler angemeldet sind.", deadline, min_players));
+    if let Some(max_players) = max_players {
+        builder.push(format!(" Mit {} beginnt es sofort.", numbered(max_players, "Spieler", "Spielern")));
+    }
+    builder.push(" Wer die Anmeldung eröffnet hat, kann sie mit ").push_mono("wwstart").push(" vorzeitig schließen.");
+    builder.build()
+}
+
+pub fn signed_up_field(num_players: usize, max_players: Option<usize>) -> String {
+    match max_players {
+        Some(max_players) => format!("Angemeldet ({}/{})", num_players, max_players),
+        None => format!("Angemeldet ({})", num_players),
+    }
+}
+
+pub fn signup_list(players: &[UserId]) -> String {
+    if players.is_empty() { return format!("niemand") }
+    let mut builder = MessageBuilder::default();
+    for (i, player) in players.iter().enumerate() {
+        if i > 0 { builder.push_line(""); }
+        builder.mention(player);
+    }
+    builder.build()
+}
+
+/// Posted above the signup embed of a rematch, pinging the players of the last game.
+pub fn rematch_invitation(last_players: &[UserId]) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push("Revanche! ");
+    for player in last_players {
+        builder.mention(player).push(" ");
+    }
+    builder.push("Reagiert mit ✅, um wieder mitzuspielen.");
+    builder.build()
+}
+
+pub fn signups_cancelled(min_players: usize) -> String {
+    format!("Die Anmeldung ist abgelaufen, aber es haben sich weniger als {} Spieler angemeldet. Das Spiel fällt aus.", min_players)
+}
+
+pub fn role_preset_fallback(preset: impl fmt::Display, min_players: usize) -> String {
+    format!("Die Rollenverteilung ({}) braucht mindestens {}, deshalb werden die Rollen zufällig verteilt.", preset, numbered(min_players, "Spieler", "Spielern"))
+}
+
+/// The spectator channel log entry listing the role distribution and the player numbers.
+pub fn game_start_log(roles: &[Role], players: &[UserId]) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push(format!("Das Spiel beginnt mit {}. Rollen: ", numbered(players.len(), "Spieler", "Spielern")));
+    builder.push_safe(join(None, roles.iter().map(|&role| role_name(role, Nom, false))));
+    for (secret_id, player) in players.iter().enumerate() {
+        builder.push_line("").push(format!("{}: ", secret_id + 1)).mention(player);
+    }
+    builder.build()
+}
+
+pub fn tanner_dm() -> &'static str {
+    "Außerdem bist du der Gerber: Du gewinnst nur, wenn du gelyncht wirst. Dann ist das Spiel sofort vorbei und alle anderen verlieren."
+}
+
+pub fn lover_dm(partner: UserId) -> String {
+    MessageBuilder::default()
+        .push("Außerdem bist du verliebt in ")
+        .mention(&partner)
+        .push(". Wenn einer von euch gewinnt, gewinnt auch der andere, egal zu welcher Fraktion ihr gehört.")
+        .build()
+}
+
+pub fn cupid_dm() -> &'static str {
+    "Außerdem bist du Amor: Wähle in dieser Phase ein Liebespaar, indem du mir `couple <player> <player>` schickst. Wenn du bis zum Ende der Phase niemanden wählst, wird das Liebespaar zufällig bestimmt."
+}
+
+/// Sent instead of [`cupid_dm`] to a substitute for Amor after the lovers were chosen.
+pub fn cupid_done_dm() -> &'static str {
+    "Außerdem bist du Amor. Das Liebespaar steht schon fest."
+}
+
+pub fn hunter_dm() -> &'static str {
+    "Außerdem bist du der Jäger: Wähle mit `aim <player>` ein Ziel. Wenn du stirbst, wird dein Ziel am Ende des nächsten Tages gelyncht. Du kannst dein Ziel jederzeit ändern."
+}
+
+/// The spectator channel log entry for the tanner, lovers, Amor, and hunter.
+pub fn variants_log(tanner: Option<UserId>, lovers: Option<(UserId, UserId)>, cupid: Option<UserId>, hunter: Option<UserId>) -> String {
+    let mut lines = Vec::default();
+    if let Some(tanner) = tanner {
+        lines.push(MessageBuilder::default().push("Gerber: ").mention(&tanner).build());
+    }
+    if let Some((lover1, lover2)) = lovers {
+        lines.push(MessageBuilder::default().push("Liebespaar: ").mention(&lover1).push(" und ").mention(&lover2).build());
+    }
+    if let Some(cupid) = cupid {
+        lines.push(MessageBuilder::default().push("Amor: ").mention(&cupid).build());
+    }
+    if let Some(hunter) = hunter {
+        lines.push(MessageBuilder::default().push("Jäger: ").mention(&hunter).build());
+    }
+    lines.join("\n")
+}
+
+/// The spectator channel log entry for the lovers chosen by Amor, or picked randomly if Amor didn't choose.
+pub fn lovers_chosen_log(cupid: UserId, lover1: UserId, lover2: UserId, chosen: bool) -> String {
+    let mut builder = MessageBuilder::default();
+    if chosen {
+        builder.push("Amor (").mention(&cupid).push(") hat ");
+    } else {
+        builder.push("Amor (").mention(&cupid).push(") hat niemanden gewählt, also wurden zufällig ");
+    }
+    builder.mention(&lover1).push(" und ").mention(&lover2).push(" verliebt.");
+    builder.build()
+}
+
+pub fn hunter_revenge(hunter: UserId, target: UserId) -> String {
+    MessageBuilder::default()
+        .mention(&hunter)
+        .push(" war der Jäger und reißt ")
+        .mention(&target)
+        .push(" mit: ")
+        .mention(&target)
+        .push(" wird am Ende des nächsten Tages gelyncht.")
+        .build()
+}
+
+/// Announced instead of the vote result when the hunter's target is lynched.
+pub fn revenge_lynched(player: UserId) -> String {
+    MessageBuilder::default()
+        .mention(&player)
+        .push(" wurde vom Jäger mitgerissen und wird anstelle der Abstimmung gelyncht.")
+        .build()
+}
+
+pub fn tanner_won(tanner: UserId) -> String {
+    MessageBuilder::default()
+        .mention(&tanner)
+        .push(" war der Gerber und hat es geschafft, gelyncht zu werden!")
+        .build()
+}
+
+pub fn quantum_role_dm(roles: &[Role], num_players: usize, secret_id: usize) -> String {
+    // Willkommen
+    let mut builder = MessageBuilder::default();
+    builder.push_line("Willkommen bei Quantenwerwölfe!"); //TODO Spielname (flavor) oder Variantenname (für normales ww etc)
+    // Rollenname
+    let mut role_counts = HashMap::<_, usize>::default();
+    let extra_villagers = num_players - roles.len();
+    if extra_villagers > 0 {
+        role_counts.insert(Role::Villager, extra_villagers);
+    }
+    for &role in roles {
+        let normalized_role = if let Role::Werewolf(_) = role {
+            Role::Werewolf(0)
+        } else {
+            role
+        };
+        *role_counts.entry(normalized_role).or_insert(0) += 1;
+    }
+    let mut role_count_list = role_counts.clone().into_iter().collect::<Vec<_>>();
+    role_count_list.sort_by_key(|&(role, _)| role_name(role, Nom, false));
+    builder.push("Du bist eine ");
+    builder.push_bold_safe(format!("Quantenüberlagerung aus {}", join(None, role_count_list.into_iter().map(|(role, count)| {
+        if let Role::Werewolf(_) = role {
+            counted(count as u64, Dat, role_gender(role), "Werwolf", "Werwölfen")
+        } else {
+            format!("{} {}", cardinal(count as u64, Dat, role_gender(role)), role_name(role, Dat, count != 1))
+        }
+    }))));
+    builder.push(".");
+    // Rollenrang
+    builder.push(" Dein Rollenrang ist ");
+    builder.push_bold(secret_id + 1);
+    builder.push(".");
+    //TODO Partei (für qww erst relevant, wenn nur noch eine Rolle möglich ist)
+    //TODO Dorfname (bei Variante „die Gemeinschaft der Dörfer“)
+    builder.push_line("");
+    //TODO Gruppenmitspieler (irrelevant für qww, zB Werwölfe, Freimaurer, Seherinnen/Kekse)
+    // Aktionen (Parteiaktionen klar als solche kennzeichnen)
+    for role in &[Role::Healer, Role::Detective] {
+        if *role_counts.get(role).unwrap_or(&0) > 0 {
+            builder.push_line(role_ability(*role).expect("special role without ability"));
+        }
+    }
+    builder.push_line(role_ability(Role::Werewolf(0)).expect("werewolves have an ability")); // everyone might be a werewolf
+    // sonstige Effekte (Parteieffekte klar als solche kennzeichnen)
+    for rule in &quantum_rules() {
+        builder.push_line(*rule);
+    }
+    //TODO wincons (für qww erst relevant, wenn nur noch eine Rolle möglich ist)
+    //TODO optional: Kurzzusammenfassung der Regeln bzw link zu den vollständigen Regeln
+    // Unterschrift
+    builder.push("Viel Spaß!");
+    builder.build()
+}
+
+pub fn game_paused() -> &'static str {
+    "Das Spiel ist pausiert. Bis es mit `wwresume` fortgesetzt wird, läuft keine Zeit ab und es werden keine Nachtaktionen oder Stimmen angenommen."
+}
+
+pub fn game_unpaused(phase_end: &str) -> String {
+    format!("Das Spiel geht weiter. Die aktuelle Phase endet {}.", phase_end)
+}
+
+pub fn game_resumed(phase_end: Option<&str>) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push("Ich wurde neu gestartet, das Spiel geht weiter.");
+    if let Some(phase_end) = phase_end {
+        builder.push(" Die aktuelle Phase endet ");
+        builder.push(phase_end);
+        builder.push(".");
+    }
+    builder.build()
+}
+
+/// Announces the given players' deaths, along with their roles if they were revealed.
+pub fn deaths(died: &[(UserId, Option<Role>)]) -> String {
+    let mut builder = MessageBuilder::default();
+    for (i, (player, role)) in died.iter().enumerate() {
+        if i > 0 {
+            builder.push(" ");
+        }
+        builder.mention(player);
+        builder.push(" ist tot");
+        if let Some(role) = role {
+            builder.push(" und war ");
+            builder.push_safe(role_name(*role, Nom, false));
+        }
+        builder.push(".");
+    }
+    builder.build()
+}
+
+/// The morning announcement of each player's probabilities in percent, or their faction (if revealed) if they're dead.
+pub fn probability_table(rows: impl IntoIterator<Item = Result<(u8, u8, u8), Option<Faction>>>) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push("Die aktuelle Wahrscheinlichkeitsverteilung:");
+    for (player_idx, probabilities) in rows.into_iter().enumerate() {
+        builder.push_line("").push_safe(match probabilities {
+            Ok((village, werewolves, dead)) => format!("{}: {}% Dorf, {}% Werwolf, {}% tot", player_idx + 1, village, werewolves, dead),
+            Err(Some(faction)) => format!("{}: tot (war {})", player_idx + 1, faction_name_sg(faction, Nom)),
+            Err(None) => format!("{}: tot", player_idx + 1),
+        });
+    }
+    builder.build()
+}
+
+/// The title of the phase embed, numbered if the number of the day or night is known.
+pub fn phase_title(night: bool, number: Option<usize>, closed: bool) -> String {
+    format!(
+        "{}{}{}",
+        if night { "🌙 Nacht" } else { "☀️ Tag" },
+        number.map(|number| format!(" {}", number)).unwrap_or_default(),
+        if closed { " (vorbei)" } else { "" },
+    )
+}
+
+pub fn alive_players_field() -> &'static str {
+    "Lebende Spieler"
+}
+
+pub fn votes_field() -> &'static str {
+    "Stimmen"
+}
+
+/// Shown instead of the end of the phase while the game is paused.
+pub fn phase_paused(minutes: u64) -> String {
+    format!("pausiert, danach noch {}", counted(minutes, Nom, F, "Minute", "Minuten"))
+}
+
+pub fn phase_end_field() -> &'static str {
+    "Ende"
+}
+
+/// Lists players by their player number.
+pub fn player_list(players: &[(usize, UserId)]) -> String {
+    let mut builder = MessageBuilder::default();
+    for (i, (number, player)) in players.iter().enumerate() {
+        if i > 0 { builder.push_line(""); }
+        builder.push(format!("{}: ", number)).mention(player);
+    }
+    builder.build()
+}
+
+/// The current votes for the phase embed, as pairs of the candidate (`None` for no lynch) and the number of votes.
+pub fn vote_status(counts: &[(Option<UserId>, usize)]) -> String {
+    if counts.is_empty() { return format!("noch keine Stimmen") }
+    let mut builder = MessageBuilder::default();
+    for (i, (candidate, count)) in counts.iter().enumerate() {
+        if i > 0 { builder.push_line(""); }
+        match candidate {
+            Some(candidate) => builder.mention(candidate),
+            None => builder.push("niemand"),
+        };
+        builder.push(format!(": {}", numbered(*count, "Stimme", "Stimmen")));
+    }
+    builder.build()
+}
+
+pub fn probability_embed_title() -> &'static str {
+    "Quantenzustand"
+}
+
+/// The probability table for `wwprob`, as a code block. Rows are given as player name and probabilities in percent (or faction if dead), in player number order.
+pub fn probability_embed_table(rows: &[(String, Result<(u8, u8, u8), Option<Faction>>)]) -> String {
+    let name_width = rows.iter().map(|(name, _)| name.chars().count()).chain(Some("Spieler".len())).max().expect("chained a value");
+    let mut table = format!("```\nNr. {:<width$} Dorf  Werwolf  tot\n", "Spieler", width = name_width);
+    for (player_idx, (name, probabilities)) in rows.iter().enumerate() {
+        let name = name.replace('`', "'"); // keep names from closing the code block
+        table.push_str(&match probabilities {
+            Ok((village, werewolves, dead)) => format!("{:>3} {:<width$} {:>3}%  {:>6}%  {:>2}%\n", player_idx + 1, name, village, werewolves, dead, width = name_width),
+            Err(Some(faction)) => format!("{:>3} {:<width$} tot (war {})\n", player_idx + 1, name, faction_name_sg(*faction, Nom), width = name_width),
+            Err(None) => format!("{:>3} {:<width$} tot\n", player_idx + 1, name, width = name_width),
+        });
+    }
+    table.push_str("```");
+    table
+}
+
+/// The description of the phase embed during the day.
+pub fn day_start(lynch_votes: usize, vote_delegation: bool, action_channel: Option<ChannelId>, anonymous_votes: bool) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push("Es wird Tag. Die Diskussion ist eröffnet. Absolute Mehrheit besteht aus ");
+    builder.push_safe(counted(lynch_votes, Dat, F, "Stimme", "Stimmen"));
+    builder.push(".");
+    if vote_delegation {
+        builder.push(" Mit ");
+        builder.push_mono_safe("delegate <player>");
+        builder.push(" könnt ihr eure Stimme für heute an einen anderen Spieler übertragen.");
+    }
+    if anonymous_votes {
+        builder.push(" Die Abstimmung ist geheim, schickt mir eure Stimme per DM, z.B. ");
+        builder.push_mono_safe("vote <player>");
+        builder.push(".");
+    } else if let Some(action_channel) = action_channel {
+        builder.push(" Abgestimmt wird in ");
+        builder.channel(action_channel);
+        builder.push(".");
+    }
+    builder.build()
+}
+
+/// Posted in the action channel, if any, at the start of the day.
+pub fn day_start_action_channel(text_channel: ChannelId, deadline: &str) -> String {
+    MessageBuilder::default()
+        .push("Es wird Tag. Stimmen zählen nur in diesem Channel, diskutiert wird in ")
+        .channel(text_channel)
+        .push(". Die Abstimmung endet ")
+        .push(deadline)
+        .push(".")
+        .build()
+}
+
+/// The content of the vote message. Each option is given as its emoji, the candidate (`None` for no lynch), and the current number of votes.
+pub fn vote_message<'a>(closed: bool, options: impl IntoIterator<Item = (&'a str, Option<UserId>, usize)>) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push(if closed {
+        voting_closed()
+    } else {
+        "Reagiert mit dem Buchstaben des Spielers, für dessen Lynchen ihr stimmen wollt, oder mit ❌, um niemanden zu lynchen:"
+    });
+    for (emoji, candidate, count) in options {
+        builder.push_line("").push(emoji).push(" ");
+        match candidate {
+            Some(candidate) => builder.mention(&candidate),
+            None => builder.push("niemand"),
+        };
+        builder.push(format!(": {}", numbered(count, "Stimme", "Stimmen")));
+    }
+    builder.build()
+}
+
+/// Announces a runoff vote between the tied players, and no lynch if that was tied as well.
+pub fn runoff_start(candidates: &[UserId], no_lynch: bool, deadline: &str) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push("Gleichstand! In der Stichwahl stehen zur Wahl: ");
+    let mut options = candidates.iter().map(|candidate| MessageBuilder::default().mention(candidate).build()).collect::<Vec<_>>();
+    if no_lynch { options.push(format!("niemanden zu lynchen")); }
+    builder.push(join(None, options));
+    builder.push(". Alle bisherigen Stimmen verfallen, die Stichwahl endet ");
+    builder.push(deadline);
+    builder.push(".");
+    builder.build()
+}
+
+pub fn vote_delegated(src: UserId, tgt: UserId) -> String {
+    MessageBuilder::default()
+        .mention(&src)
+        .push(" überträgt die eigene Stimme für heute an ")
+        .mention(&tgt)
+        .push(".")
+        .build()
+}
+
+pub fn vote_undelegated(src: UserId) -> String {
+    MessageBuilder::default()
+        .mention(&src)
+        .push(" nimmt die Stimmübertragung zurück.")
+        .build()
+}
+
+pub fn delegation_lapsed(delegator: UserId, delegate: UserId) -> String {
+    MessageBuilder::default()
+        .push("Die Stimmübertragung von ")
+        .mention(&delegator)
+        .push(" an ")
+        .mention(&delegate)
+        .push(" verfällt.")
+        .build()
+}
+
+pub fn vote_changed_by_moderator(voter: UserId) -> String {
+    MessageBuilder::default()
+        .push("Die Moderation hat die Stimme von ")
+        .mention(&voter)
+        .push(" geändert.")
+        .build()
+}
+
+/// Posted when the day ends without a decision, listing the players who neither voted nor delegated their vote.
+pub fn time_up(abstained: &[UserId]) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push("Die Zeit ist abgelaufen.");
+    if !abstained.is_empty() {
+        builder.push(" Enthalten haben sich:");
+        for player in abstained {
+            builder.push(" ").mention(player);
+        }
+    }
+    builder.build()
+}
+
+pub fn discussion_closed() -> &'static str {
+    "Die Diskussion ist geschlossen."
+}
+
+pub fn voting_closed() -> &'static str {
+    "Die Abstimmung ist geschlossen."
+}
+
+/// The description of the phase embed during the night.
+pub fn night_start() -> &'static str {
+    "Es wird Nacht. Bitte schickt mir eure Nachtaktionen per DM."
+}
+
+/// The DM asking a living player for night actions. Targets are given as their player number and name.
+pub fn night_prompt(deadline: &str, targets: &[(usize, String)]) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push("Es wird Nacht. Die Nacht endet ");
+    builder.push(deadline);
+    builder.push(". Mögliche Ziele:");
+    for (number, name) in targets {
+        builder.push_line("").push(format!("{}: ", number)).push_safe(name);
+    }
+    builder.push_line("")
+        .push("Antworte hier mit deinen Nachtaktionen, z. B. ")
+        .push_mono("kill 1")
+        .push(", ")
+        .push_mono("investigate 2")
+        .push(" oder ")
+        .push_mono("heal 3")
+        .push(".")
+        .build()
+}
+
+pub fn night_reminder(minutes: u64) -> String {
+    format!("Die Nacht dauert noch {}. Wer noch Nachtaktionen schicken muss, sollte das jetzt tun.", counted(minutes, Nom, F, "Minute", "Minuten"))
+}
+
+pub fn day_reminder(minutes: u64) -> String {
+    format!("Die Abstimmung läuft noch {}. Wer bis dahin nicht abgestimmt hat, enthält sich.", counted(minutes, Nom, F, "Minute", "Minuten"))
+}
+
+/// The DM sent to players who haven't acted yet some time into the phase, see `Config::prod_secs`. `vote_channel` is `None` if votes are sent by DM.
+pub fn prod_dm(night: bool, vote_channel: Option<ChannelId>) -> String {
+    let mut builder = MessageBuilder::default();
+    if night {
+        builder.push("Du hast heute Nacht noch keine Nachtaktionen geschickt. Wenn du bis zum Ende der Nacht nichts schickst, verfallen sie.");
+    } else {
+        builder.push("Du hast heute noch nicht abgestimmt. Wenn du bis zum Ende des Tages nicht abstimmst, enthältst du dich.");
+        if let Some(vote_channel) = vote_channel {
+            builder.push(" Abgestimmt wird in ").channel(vote_channel).push(".");
+        } else {
+            builder.push(" Die Abstimmung ist geheim, schick mir deine Stimme hier per DM.");
+        }
+    }
+    builder.build()
+}
+
+/// Asks the moderators to replace a player who was removed for inactivity, see `Departure::Substitute`.
+pub fn afk_limit_reached(player: UserId, missed: usize, moderator_role: Option<RoleId>) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.mention(&player).push(format!(" war in {} inaktiv und scheidet aus dem Spiel aus. ", counted(missed, Dat, F, "Phase", "Phasen")));
+    if let Some(moderator_role) = moderator_role {
+        builder.role(moderator_role).push(" ");
+    }
+    builder.push("Bitte ersetzt den Spieler mit ").push_mono("wwreplace").push(".");
+    builder.build()
+}
+
+/// Announces that a player who was removed for inactivity will be lynched, see `Departure::Lynch`.
+pub fn afk_limit_reached_lynch(player: UserId, missed: usize) -> String {
+    MessageBuilder::default()
+        .mention(&player)
+        .push(format!(" war in {} inaktiv und scheidet am Ende des Tages aus dem Spiel aus.", counted(missed, Dat, F, "Phase", "Phasen")))
+        .build()
+}
+
+/// Announced instead of the vote result when a player who was removed for inactivity is lynched.
+pub fn inactive_lynched(player: UserId) -> String {
+    MessageBuilder::default()
+        .mention(&player)
+        .push(" war zu lange inaktiv und wird anstelle der Abstimmung gelyncht.")
+        .build()
+}
+
+/// Asks the moderators to replace a player who left the server during a game, see `Departure::Substitute`.
+pub fn player_left(player: UserId, moderator_role: Option<RoleId>) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.mention(&player).push(" hat den Server verlassen. ");
+    if let Some(moderator_role) = moderator_role {
+        builder.role(moderator_role).push(" ");
+    }
+    builder.push("Bitte ersetzt den Spieler mit ").push_mono("wwreplace").push(".");
+    builder.build()
+}
+
+/// Announces that a player who left the server during a game will be lynched, see `Departure::Lynch`.
+pub fn player_left_lynch(player: UserId) -> String {
+    MessageBuilder::default()
+        .mention(&player)
+        .push(" hat den Server verlassen und scheidet am Ende des Tages aus dem Spiel aus.")
+        .build()
+}
+
+/// Announced instead of the vote result when a player who left the server is lynched.
+pub fn departed_lynched(player: UserId) -> String {
+    MessageBuilder::default()
+        .mention(&player)
+        .push(" hat den Server verlassen und wird anstelle der Abstimmung gelyncht.")
+        .build()
+}
+
+/// The spectator channel log entry for a night action.
+pub fn night_action_log(action: &NightAction<UserId>) -> String {
+    let (src, verb, tgt) = match action {
+        NightAction::Heal(src, tgt) => (src, "heilt", tgt),
+        NightAction::Investigate(src, tgt) => (src, "untersucht", tgt),
+        NightAction::Kill(src, tgt) => (src, "greift an:", tgt),
+    };
+    MessageBuilder::default().mention(src).push(" ").push(verb).push(" ").mention(tgt).build()
+}
+
+/// The DM telling a detective the result of an investigation.
+pub fn investigation_result(target: &User, faction: Faction) -> String {
+    MessageBuilder::default()
+        .push("Ergebnis deiner Nachtaktion: ")
+        .dm_mention(target)
+        .push(" gehört ")
+        .push_safe(zu(faction_gender(faction)))
+        .push(" ")
+        .push_safe(faction_name(faction, Dat))
+        .build()
+}
+
+/// The spectator channel log entry for the result of an investigation.
+pub fn investigation_log(player: UserId, target: UserId, faction: Faction) -> String {
+    MessageBuilder::default()
+        .mention(&player)
+        .push(" erfährt: ")
+        .mention(&target)
+        .push(" gehört ")
+        .push_safe(zu(faction_gender(faction)))
+        .push(" ")
+        .push_safe(faction_name(faction, Dat))
+        .build()
+}
+
+pub fn game_over(winners: &[UserId]) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push("das Spiel ist vorbei: ");
+    match winners {
+        [] => builder.push("niemand hat gewonnen"),
+        [winner] => builder.mention(winner).push(" hat gewonnen"),
+        [first, rest @ ..] => {
+            builder.mention(first);
+            for winner in rest {
+                builder.push(" ").mention(winner);
+            }
+            builder.push(" haben gewonnen")
+        }
+    };
+    builder.build()
+}
+
+/// The game result for the guild's log channel. Uses tags instead of mentions to avoid pinging the players.
+pub fn game_over_log(num_players: usize, winners: &[User]) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push(format!("Werwölfe: Spiel mit {} beendet. Gewinner: ", numbered(num_players, "Spieler", "Spielern")));
+    builder.push_safe(join(Some(format!("niemand")), winners.iter().map(|winner| winner.tag())));
+    builder.build()
+}
+
+pub fn spectator_game_over() -> &'static str {
+    "Das Spiel ist vorbei."
+}
+
+pub fn game_cancelled() -> &'static str {
+    "Das Spiel wurde von der Moderation abgebrochen."
+}
+
+/// The game log entry for the end of a vote, with counts as in [`vote_status`].
+pub fn vote_result_log(counts: &[(Option<UserId>, usize)]) -> String {
+    format!("Abstimmungsergebnis:\n{}", vote_status(counts))
+}
+
+/// Lists who voted for whom in an anonymous vote, for the game log. `None` stands for no lynch.
+pub fn anonymous_votes_log(votes: &[(UserId, Option<UserId>)]) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push("Geheime Stimmen:");
+    for (voter, vote) in votes {
+        builder.push_line("").mention(voter).push(": ");
+        match vote {
+            Some(candidate) => builder.mention(candidate),
+            None => builder.push("niemand"),
+        };
+    }
+    builder.build()
+}
+
+/// The heading of the exported game log.
+pub fn game_log_title(channel_name: &str, start: DateTime<Utc>) -> String {
+    format!("Werwolf-Spiel in #{} vom {}", channel_name, start.format("%d.%m.%Y, %H:%M UTC"))
+}
+
+pub fn game_master_changed(game_master: Option<UserId>) -> String {
+    let mut builder = MessageBuilder::default();
+    if let Some(game_master) = game_master {
+        builder.mention(&game_master).push(" leitet ab jetzt die Spiele in diesem Channel, spielt nicht mit und erfährt alle geheimen Informationen.");
+    } else {
+        builder.push("Die Spiele in diesem Channel laufen ab jetzt ohne Spielleitung.");
+    }
+    builder.build()
+}
+
+/// Logged when the game master decides the outcome of the day, or goes back to the vote result (`None`).
+pub fn game_master_lynch_log(decision: Option<Option<UserId>>) -> String {
+    let mut builder = MessageBuilder::default();
+    match decision {
+        Some(Some(player)) => builder.push("Die Spielleitung entscheidet, ").mention(&player).push(" zu lynchen."),
+        Some(None) => builder.push("Die Spielleitung entscheidet, niemanden zu lynchen."),
+        None => builder.push("Die Spielleitung überlässt die Entscheidung wieder der Abstimmung."),
+    };
+    builder.build()
+}
+
+pub fn game_master_decision(lynched: Option<UserId>) -> String {
+    let mut builder = MessageBuilder::default();
+    builder.push("Die Spielleitung hat entschieden: ");
+    if let Some(lynched) = lynched {
+        builder.mention(&lynched).push(" wird gelyncht.");
+    } else {
+        builder.push("heute wird niemand gelyncht.");
+    }
+    builder.build()
+}
+
+pub fn narration(text: &str) -> String {
+    MessageBuilder::default()
+        .push("📜 ")
+        .push_safe(text)
+        .build()
+}
+
+pub fn last_words(player: UserId, text: &str) -> String {
+    MessageBuilder::default()
+        .push("Die letzten Worte von ")
+        .mention(&player)
+        .push_line(":")
+        .push_quote_safe(text)
+        .build()
+}
+
+pub fn last_words_window(player: UserId, end: &str) -> String {
+    MessageBuilder::default()
+        .mention(&player)
+        .push(format!(", du darfst bis {} noch eine letzte Nachricht in diesen Channel schreiben.", end))
+        .build()
+}
+
+pub fn last_words_saved(saved: bool) -> &'static str {
+    if saved {
+        "deine letzten Worte werden veröffentlicht, wenn du stirbst"
+    } else {
+        "deine letzten Worte wurden entfernt"
+    }
+}
+
+/// Lists exactly the decisions covered by the seed: which player gets which secret ID and how the quantum states collapse are decided by `quantum_werewolf` with its own randomness.
+pub fn seed_committed(commitment: &str) -> String {
+    format!("Welche Rollen bei zufälliger Verteilung im Spiel sind, wer Gerber und wer Liebespaar ist und per Zufall entschiedene Gleichstände werden aus einem geheimen Seed bestimmt, der am Ende veröffentlicht wird. Sein SHA-256-Hash ist `{}`. Wer welche Rolle bekommt und wie die Quantenzustände kollabieren, entscheidet die Spiel-Engine unabhängig davon.", commitment)
+}
+
+pub fn seed_revealed(seed: &str, commitment: &str) -> String {
+    format!("Der Seed dieses Spiels war `{}` (SHA-256-Hash `{}`). Damit lassen sich die zufällige Rollenverteilung, Gerber, Liebespaar und per Zufall entschiedene Gleichstände nachprüfen, aber nicht, wer welche Rolle bekommen hat.", seed, commitment)
+}
+
+pub fn game_log_posted() -> &'static str {
+    "Hier ist das vollständige Protokoll des Spiels, inklusive aller geheimen Informationen:"
+}
+
+/// The DM sent to a substitute before their role DM.
+pub fn substitute_dm(leaving: UserId) -> String {
+    MessageBuilder::default()
+        .push("Du ersetzt ")
+        .mention(&leaving)
+        .push(" im laufenden Werwölfe-Spiel, inklusive aller Nachtaktionen und Stimmen.")
+        .build()
+}
+
+pub fn substitute_announcement(substitute: UserId, leaving: UserId) -> String {
+    MessageBuilder::default()
+        .mention(&substitute)
+        .push(" ersetzt ab jetzt ")
+        .mention(&leaving)
+        .push(".")
+        .build()
+}
+
+pub fn help_title() -> &'static str {
+    "Befehle"
+}
+
+pub fn help_footer(prefix: &str) -> String {
+    format!("Mit {}help <Befehl> erfährst du mehr über einen Befehl.", prefix)
+}
+
+pub fn help_unknown_command(prefix: &str, query: &str) -> String {
+    MessageBuilder::default()
+        .push("den Befehl ")
+        .push_mono_safe(query)
+        .push(" gibt es nicht, mit ")
+        .push_mono_safe(format!("{}help", prefix))
+        .push(" siehst du alle Befehle")
+        .build()
+}
+
+pub fn help_no_description() -> &'static str {
+    "Keine Beschreibung vorhanden."
+}
+
+pub fn help_usage_field() -> &'static str {
+    "Benutzung"
+}
+
+pub fn help_examples_field() -> &'static str {
+    "Beispiele"
+}
+
+pub fn help_requirements_field() -> &'static str {
+    "Voraussetzungen"
+}
+
+pub fn help_aliases_field() -> &'static str {
+    "Aliase"
+}
+
+pub fn help_sub_commands_field() -> &'static str {
+    "Unterbefehle"
+}
+
+/// The given commands as a comma-separated list of code spans.
+pub fn help_command_list(prefix: &str, names: &[&str]) -> String {
+    names.iter().map(|name| format!("`{}{}`", prefix, name)).collect::<Vec<_>>().join(", ")
+}
+
+/// How to use a command, with the placeholders from its `#[usage]` attribute if it has arguments.
+pub fn help_usage(prefix: &str, name: &str, usage: Option<&str>) -> String {
+    match usage {
+        Some(usage) => format!("`{}{} {}`", prefix, name, usage),
+        None => format!("`{}{}`", prefix, name),
+    }
+}
+
+/// The arguments from the command's `#[example]` attributes, one full command per line.
+pub fn help_examples(prefix: &str, name: &str, examples: &[&str]) -> String {
+    examples.iter().map(|example| format!("`{}{} {}`", prefix, name, example)).collect::<Vec<_>>().join("\n")
+}
+
+/// The names of the given server permissions as shown in the German Discord client.
+pub fn permission_names(permissions: Permissions) -> Vec<Cow<'static, str>> {
+    let known = [
+        (Permissions::ADMINISTRATOR, "Administrator"),
+        (Permissions::MANAGE_GUILD, "Server verwalten"),
+        (Permissions::MANAGE_CHANNELS, "Kanäle verwalten"),
+        (Permissions::MANAGE_ROLES, "Rollen verwalten"),
+        (Permissions::MANAGE_MESSAGES, "Nachrichten verwalten"),
+    ];
+    let mut names = known.iter().filter(|&&(permission, _)| permissions.contains(permission)).map(|&(_, name)| Cow::Borrowed(name)).collect::<Vec<_>>();
+    let unknown = known.iter().fold(permissions, |permissions, &(permission, _)| permissions - permission);
+    if !unknown.is_empty() {
+        names.push(Cow::Owned(format!("{:?}", unknown)));
+    }
+    names
+}
+
+/// Who can use a command and where, for `!help`. `None` if anyone can use it anywhere. `checks` are the names of the command's checks, e.g. `Werwölfe-Channel`.
+pub fn help_requirements(permissions: Permissions, checks: &[&str], owners_only: bool, guild_only: bool, dm_only: bool) -> Option<String> {
+    let mut lines = Vec::default();
+    if owners_only {
+        lines.push(format!("nur für die Bot-Administration"));
+    }
+    if !permissions.is_empty() {
+        lines.push(format!("Berechtigung: {}", join(None, permission_names(permissions))));
+    }
+    if !checks.is_empty() {
+        lines.push(join(None, checks));
+    }
+    if guild_only {
+        lines.push(format!("nur auf Servern"));
+    } else if dm_only {
+        lines.push(format!("nur per DM"));
+    }
+    if lines.is_empty() { None } else { Some(lines.join("\n")) }
+}
@@ -2,9 +2,11 @@
 
 use {
     std::{
+        backtrace::Backtrace,
         env,
         fmt,
         io,
+        panic,
         process::Stdio,
         time::Duration,
     },
@@ -17,19 +19,35 @@ use {
     tokio::{
         io::AsyncWriteExt as _,
         process::Command,
+        runtime::Handle,
+        task_local,
     },
 };
 
+pub mod announce;
+mod atomic_file;
 pub mod commands;
 pub mod config;
 pub mod emoji;
+pub mod games;
 pub mod ipc;
 pub mod lang;
+pub mod log_channel;
 pub mod parse;
+pub mod paths;
+pub mod presence;
+pub mod reaction_listeners;
+pub mod reactions;
+pub mod setup;
+pub mod stats;
+pub mod tournament;
 pub mod twitch;
 pub mod user_list;
 pub mod voice;
+pub mod webhook;
 pub mod werewolf;
+pub mod werewolf_history;
+pub mod werewolf_rating;
 
 const FENHL: UserId = UserId(86841168427495424);
 pub const GEFOLGE: GuildId = GuildId(355761290809180170);
@@ -38,23 +56,42 @@ pub const GEFOLGE: GuildId = GuildId(355761290809180170);
 pub enum Error {
     Annotated(String, Box<Error>),
     ChannelIdParse(ChannelIdParseError),
+    /// A setting in the config file still has the placeholder value from `peter init-config`.
+    #[from(ignore)]
+    ConfigPlaceholder(String),
     Env(env::VarError),
     #[from(ignore)]
     GameAction(String),
+    Hyper(hyper::Error),
     Io(io::Error),
     Ipc(crate::ipc::Error),
     Json(serde_json::Error),
+    /// The bot token was not found in the environment, the secrets file, or the config file.
+    MissingBotToken,
     /// Returned if the config is not present in Serenity context.
     MissingConfig,
     /// Returned if a Serenity context was required outside of an event handler but the `ready` event has not been received yet.
     MissingContext,
     /// The reply to an IPC command did not end in a newline.
     MissingNewline,
+    /// A command or event handler panicked. Contains the panic message and a backtrace.
+    #[from(ignore)]
+    Panic(String, String),
+    /// The quantum state of a werewolf game changed in a way that isn't possible in the game's current phase, see [`werewolf::Phase::after`].
+    #[from(ignore)]
+    PhaseTransition(werewolf::Phase),
     QwwStartGame(quantum_werewolf::game::state::StartGameError),
+    Reqwest(reqwest::Error),
     RoleIdParse(RoleIdParseError),
     Serenity(serenity::Error),
+    Sqlite(rusqlite::Error),
+    TomlDe(toml::de::Error),
+    TomlSer(toml::ser::Error),
     Twitch(twitch_helix::Error),
     TwitchUserLookup,
+    /// The config file was written by a newer version of Peter.
+    #[from(ignore)]
+    UnsupportedConfigVersion(u64),
     UserIdParse(UserIdParseError),
 }
 
@@ -88,19 +125,29 @@ impl fmt::Display for Error {
         match self {
             Error::Annotated(msg, e) => write!(f, "{}: {}", msg, e),
             Error::ChannelIdParse(e) => e.fmt(f),
+            Error::ConfigPlaceholder(path) => write!(f, "config setting {} has not been filled in", path),
             Error::Env(e) => e.fmt(f),
             Error::GameAction(s) => write!(f, "invalid game action: {}", s),
+            Error::Hyper(e) => e.fmt(f),
             Error::Io(e) => e.fmt(f),
             Error::Ipc(e) => e.fmt(f),
             Error::Json(e) => e.fmt(f),
+            Error::MissingBotToken => write!(f, "no bot token configured, set peter.botToken, peter.secretsFile, or PETER_BOT_TOKEN"),
             Error::MissingConfig => write!(f, "config missing in Serenity context"),
             Error::MissingContext => write!(f, "Serenity context not available before ready event"),
             Error::MissingNewline => write!(f, "the reply to an IPC command did not end in a newline"),
+            Error::Panic(msg, _) => write!(f, "{}", msg),
+            Error::PhaseTransition(phase) => write!(f, "werewolf game state is out of sync with phase {:?}", phase),
             Error::QwwStartGame(e) => e.fmt(f),
+            Error::Reqwest(e) => e.fmt(f),
             Error::RoleIdParse(e) => e.fmt(f),
             Error::Serenity(e) => e.fmt(f),
+            Error::Sqlite(e) => e.fmt(f),
+            Error::TomlDe(e) => e.fmt(f),
+            Error::TomlSer(e) => e.fmt(f),
             Error::Twitch(e) => e.fmt(f),
             Error::TwitchUserLookup => write!(f, "Twitch returned unexpected user info"),
+            Error::UnsupportedConfigVersion(version) => write!(f, "config version {} is not supported by this version of Peter", version),
             Error::UserIdParse(e) => e.fmt(f),
         }
     }
@@ -108,9 +155,30 @@ impl fmt::Display for Error {
 
 impl std::error::Error for Error {}
 
+task_local! {
+    /// Set while reporting a crash, so that a panic during reporting doesn't cause another report.
+    static REPORTING_CRASH: ();
+}
+
+/// Installs a panic hook which reports all panics, including those in commands and event handlers, along with a backtrace.
+///
+/// Serenity runs each command and event handler in its own task, and the locks in the `typemap` are released during unwinding, so a panicking handler doesn't affect the rest of the bot.
+pub fn report_panics(ctx_fut: RwFuture<Context>) {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        if REPORTING_CRASH.try_with(|()| ()).is_ok() { return }
+        if let Ok(runtime) = Handle::try_current() {
+            let e = Error::Panic(info.to_string(), Backtrace::force_capture().to_string());
+            runtime.spawn(REPORTING_CRASH.scope((), notify_thread_crash(ctx_fut.clone(), format!("handler"), e, None)));
+        }
+    }));
+}
+
 pub async fn notify_thread_crash(ctx: RwFuture<Context>, thread_kind: String, e: impl Into<Error>, auto_retry: Option<Duration>) {
     let ctx = ctx.read().await;
     let e = e.into();
+    let _ = log_channel::post(&*ctx, GEFOLGE, format!("{} thread crashed: {}", thread_kind, e)).await; // best effort, the notification below is the one that matters
     if let Ok(fenhl) = FENHL.to_user(&*ctx).await {
         if fenhl.dm(&*ctx, |m| m.content(format!("{} thread crashed: {} (`{:?}`), {}", thread_kind, e, e, if let Some(auto_retry) = auto_retry { format!("auto-retrying in `{:?}`", auto_retry) } else { format!("**not** auto-retrying") }))).await.is_ok() {
             return
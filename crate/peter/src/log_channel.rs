@@ -0,0 +1,21 @@
+//! Operational messages for server admins, e.g. crashes, bans, and config changes.
+
+use {
+    std::fmt,
+    serenity::{
+        model::prelude::*,
+        prelude::*,
+    },
+    crate::config::Config,
+};
+
+/// Posts the given message to the guild's log channel, if it has one.
+///
+/// This locks the `typemap`, so it must not be called while holding a write lock on the `typemap` or any lock on the config.
+pub async fn post(ctx: &Context, guild_id: GuildId, msg: impl fmt::Display) -> serenity::Result<()> {
+    let log_channel = Config::read(ctx).await.guild(guild_id).and_then(|guild_config| guild_config.channels.log);
+    if let Some(log_channel) = log_channel {
+        log_channel.say(ctx, msg).await?;
+    }
+    Ok(())
+}
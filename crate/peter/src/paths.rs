@@ -0,0 +1,184 @@
+//! The on-disk layout of the files Peter reads and writes.
+
+use {
+    std::{
+        env,
+        path::{
+            Path,
+            PathBuf,
+        },
+    },
+    schemars::JsonSchema,
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    serenity::prelude::*,
+};
+
+/// The data root on the production host, shared with gefolge.org.
+const FIDERA_DATA: &str = "/usr/local/share/fidera";
+
+/// `typemap` key for the filesystem layout, so subsystems don't hard-code paths.
+#[derive(Debug, Clone)]
+pub struct Paths {
+    /// The config file.
+    pub config: PathBuf,
+    /// The data root, containing the voice state dump.
+    pub data: PathBuf,
+    /// The directory where custom emoji are backed up, see [`crate::emoji::backup`].
+    pub emoji_backup: PathBuf,
+    /// The directory containing one JSON file per guild member, read by gefolge.org to verify logins.
+    pub profiles: PathBuf,
+    /// A directory containing twemoji SVG files to build the emoji database from instead of the bundled emoji list, see [`crate::emoji::Iter::new`].
+    pub twemoji: Option<PathBuf>,
+}
+
+/// The `paths` section of the config file. Paths set here take precedence over the defaults, but not over the environment variables listed in [`Paths::new`].
+#[derive(Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    #[serde(default)]
+    data: Option<PathBuf>,
+    #[serde(default)]
+    emoji_backup: Option<PathBuf>,
+    #[serde(default)]
+    profiles: Option<PathBuf>,
+    #[serde(default)]
+    twemoji: Option<PathBuf>,
+}
+
+impl Paths {
+    /// Determines the layout to use.
+    ///
+    /// If the `PETER_DATA_DIR` environment variable is set, everything is placed inside that directory. Otherwise, the production layout is used if it exists, falling back to the XDG base directories.
+    ///
+    /// The config file, profiles directory, and emoji backup directory can additionally be overridden, and a twemoji directory can be set, using the `PETER_CONFIG`, `PETER_PROFILES_DIR`, `PETER_EMOJI_BACKUP_DIR`, and `PETER_TWEMOJI_DIR` environment variables.
+    pub fn new() -> Paths {
+        let mut paths = if let Some(data) = env::var_os("PETER_DATA_DIR") {
+            Paths::with_root(PathBuf::from(data))
+        } else if Path::new(FIDERA_DATA).exists() {
+            Paths {
+                config: Path::new(FIDERA_DATA).join("config.json"),
+                data: PathBuf::from(FIDERA_DATA),
+                emoji_backup: Path::new(FIDERA_DATA).join("emoji-backup"),
+                profiles: Path::new(FIDERA_DATA).join("profiles"),
+                twemoji: None,
+            }
+        } else {
+            let data = xdg_dir("XDG_DATA_HOME", &[".local", "share"]).join("peter");
+            Paths {
+                config: xdg_dir("XDG_CONFIG_HOME", &[".config"]).join("peter").join("config.json"),
+                emoji_backup: data.join("emoji-backup"),
+                profiles: data.join("profiles"),
+                twemoji: None,
+                data,
+            }
+        };
+        if let Some(config) = env::var_os("PETER_CONFIG") {
+            paths.config = PathBuf::from(config);
+        }
+        if let Some(profiles) = env::var_os("PETER_PROFILES_DIR") {
+            paths.profiles = PathBuf::from(profiles);
+        }
+        if let Some(emoji_backup) = env::var_os("PETER_EMOJI_BACKUP_DIR") {
+            paths.emoji_backup = PathBuf::from(emoji_backup);
+        }
+        if let Some(twemoji) = env::var_os("PETER_TWEMOJI_DIR") {
+            paths.twemoji = Some(PathBuf::from(twemoji));
+        }
+        paths
+    }
+
+    /// Applies the `paths` section of the config file, except where overridden by environment variables.
+    pub fn apply_config(&mut self, config: &Config) {
+        fn apply(path: &mut PathBuf, var: &str, configured: &Option<PathBuf>) {
+            if env::var_os(var).is_none() {
+                if let Some(configured) = configured {
+                    *path = configured.clone();
+                }
+            }
+        }
+
+        apply(&mut self.data, "PETER_DATA_DIR", &config.data);
+        apply(&mut self.emoji_backup, "PETER_EMOJI_BACKUP_DIR", &config.emoji_backup);
+        apply(&mut self.profiles, "PETER_PROFILES_DIR", &config.profiles);
+        if env::var_os("PETER_TWEMOJI_DIR").is_none() && config.twemoji.is_some() {
+            self.twemoji = config.twemoji.clone();
+        }
+    }
+
+    /// A layout with everything inside the given directory.
+    pub fn with_root(data: PathBuf) -> Paths {
+        Paths {
+            config: data.join("config.json"),
+            emoji_backup: data.join("emoji-backup"),
+            profiles: data.join("profiles"),
+            twemoji: None,
+            data,
+        }
+    }
+
+    /// The online status snapshots, see [`crate::presence`]. Read by gefolge.org and the BitBar plugin.
+    pub fn presences(&self) -> PathBuf {
+        self.data.join("discord").join("presences.json")
+    }
+
+    /// The member list database, used instead of the profiles directory if the SQLite backend is selected, see [`crate::user_list`].
+    pub fn profiles_db(&self) -> PathBuf {
+        self.profiles.with_extension("sqlite")
+    }
+
+    /// The usage statistics, see [`crate::stats`].
+    pub fn stats(&self) -> PathBuf {
+        self.data.join("stats.json")
+    }
+
+    /// The running werewolf games, one JSON file per guild, see [`crate::werewolf`]. Restored when the bot restarts.
+    pub fn werewolf_games(&self) -> PathBuf {
+        self.data.join("werewolf-games")
+    }
+
+    /// The full logs of finished werewolf games, one Markdown file per game, see [`crate::werewolf`].
+    pub fn werewolf_logs(&self) -> PathBuf {
+        self.data.join("werewolf-logs")
+    }
+
+    /// The finished werewolf games, see [`crate::werewolf_history`]. Also read by gefolge.org.
+    pub fn werewolf_history(&self) -> PathBuf {
+        self.data.join("werewolf-history.json")
+    }
+
+    /// The werewolf player ratings, see [`crate::werewolf_rating`].
+    pub fn werewolf_ratings(&self) -> PathBuf {
+        self.data.join("werewolf-ratings.json")
+    }
+
+    /// The werewolf seasons, see [`crate::tournament`]. Also read by gefolge.org.
+    pub fn werewolf_seasons(&self) -> PathBuf {
+        self.data.join("werewolf-seasons.json")
+    }
+
+    /// The voice state dump read by the gefolge.org API.
+    pub fn voice_state(&self) -> PathBuf {
+        self.data.join("discord").join("voice-state.json")
+    }
+}
+
+impl Default for Paths {
+    fn default() -> Paths {
+        Paths::new()
+    }
+}
+
+impl TypeMapKey for Paths {
+    type Value = Paths;
+}
+
+/// Returns the XDG base directory from the given environment variable, falling back to the given path relative to the home directory as specified.
+fn xdg_dir(var: &str, fallback: &[&str]) -> PathBuf {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute()) // relative paths are invalid according to the spec and should be ignored
+        .unwrap_or_else(|| fallback.iter().fold(env::var_os("HOME").map_or_else(PathBuf::default, PathBuf::from), |path, component| path.join(component)))
+}
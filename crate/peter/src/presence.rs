@@ -0,0 +1,93 @@
+//! Snapshots of Gefolge members' online status, for the gefolge.org API and the BitBar plugin.
+
+use {
+    std::{
+        collections::BTreeMap,
+        io,
+    },
+    chrono::prelude::*,
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    serenity::{
+        model::prelude::*,
+        prelude::*,
+    },
+    tokio::{
+        fs::{
+            self,
+            File,
+        },
+        io::AsyncWriteExt as _,
+    },
+    crate::{
+        Error,
+        paths::Paths,
+    },
+};
+
+/// A member's most recent known online status.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub status: OnlineStatus,
+    /// The last time the member was seen with a status other than offline. `None` if they haven't been seen online since presence snapshots were introduced.
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+/// `typemap` key for the presence snapshots, which are written to disk on every status change.
+#[derive(Default, Deserialize, Serialize)]
+pub struct Presences(BTreeMap<UserId, Snapshot>);
+
+impl TypeMapKey for Presences {
+    type Value = Presences;
+}
+
+impl Presences {
+    pub async fn load(paths: &Paths) -> Result<Presences, Error> {
+        match fs::read_to_string(paths.presences()).await {
+            Ok(buf) => Ok(serde_json::from_str(&buf)?), //TODO use async-json
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Presences::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn save(&self, paths: &Paths) -> Result<(), Error> {
+        let buf = serde_json::to_vec(self)?; //TODO use async-json
+        File::create(paths.presences()).await?.write_all(&buf).await?;
+        Ok(())
+    }
+
+    /// Returns the most recent known status of the given user.
+    pub fn get(&self, user_id: UserId) -> Option<Snapshot> {
+        self.0.get(&user_id).copied()
+    }
+
+    /// Records the given user's current status. Returns whether anything changed.
+    fn record(&mut self, user_id: UserId, status: OnlineStatus, now: DateTime<Utc>) -> bool {
+        let old = self.0.get(&user_id).copied();
+        let was_online = old.map_or(false, |old| old.status != OnlineStatus::Offline);
+        let last_seen = if was_online || status != OnlineStatus::Offline { Some(now) } else { old.and_then(|old| old.last_seen) };
+        let new = Snapshot { status, last_seen };
+        self.0.insert(user_id, new);
+        old.map_or(true, |old| old.status != status)
+    }
+
+    /// Records the statuses of all members in the given presences and saves the snapshots, e.g. when connecting to a guild.
+    pub async fn record_all(&mut self, paths: &Paths, presences: impl IntoIterator<Item = &Presence>) -> Result<(), Error> {
+        let now = Utc::now();
+        for presence in presences {
+            self.record(presence.user_id, presence.status, now);
+        }
+        self.save(paths).await
+    }
+
+    /// Records a presence update, saving the snapshots if the user's status changed.
+    pub async fn update(&mut self, paths: &Paths, presence: &Presence) -> Result<(), Error> {
+        if self.record(presence.user_id, presence.status, Utc::now()) {
+            self.save(paths).await?;
+        }
+        Ok(())
+    }
+}
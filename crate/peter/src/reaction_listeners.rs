@@ -0,0 +1,83 @@
+//! A registry where modules subscribe to reaction events, so the event handler doesn't need to know about every feature that uses reactions.
+
+use {
+    std::pin::Pin,
+    futures::future::Future,
+    serenity::{
+        model::prelude::*,
+        prelude::*,
+    },
+    crate::Error,
+};
+
+/// Whether a reaction was added or removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Add,
+    Remove,
+}
+
+/// Which reactions a listener is called for. Fields which are `None` match any reaction.
+#[derive(Default, Clone)]
+pub struct Filter {
+    pub message: Option<MessageId>,
+    pub channel: Option<ChannelId>,
+    pub emoji: Option<ReactionType>,
+}
+
+impl Filter {
+    fn matches(&self, reaction: &Reaction) -> bool {
+        self.message.map_or(true, |message_id| message_id == reaction.message_id)
+        && self.channel.map_or(true, |channel_id| channel_id == reaction.channel_id)
+        && self.emoji.as_ref().map_or(true, |emoji| *emoji == reaction.emoji)
+    }
+}
+
+/// A function called for each matching reaction event.
+pub type Handler = fn(Context, Reaction, Kind) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send>>;
+
+/// Identifies a subscription so it can be removed, e.g. once a poll has ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListenerId(u64);
+
+/// `typemap` key for the registered reaction listeners.
+#[derive(Default)]
+pub struct ReactionListeners {
+    next_id: u64,
+    listeners: Vec<(ListenerId, Filter, Handler)>,
+}
+
+impl TypeMapKey for ReactionListeners {
+    type Value = ReactionListeners;
+}
+
+impl ReactionListeners {
+    /// Registers a handler for reactions matching the filter.
+    pub fn subscribe(&mut self, filter: Filter, handler: Handler) -> ListenerId {
+        let id = ListenerId(self.next_id);
+        self.next_id += 1;
+        self.listeners.push((id, filter, handler));
+        id
+    }
+
+    /// Removes a subscription. Returns whether it existed.
+    pub fn unsubscribe(&mut self, id: ListenerId) -> bool {
+        let len = self.listeners.len();
+        self.listeners.retain(|&(listener_id, _, _)| listener_id != id);
+        self.listeners.len() < len
+    }
+}
+
+/// Calls the handlers of all listeners whose filters match the reaction, in the order they subscribed.
+///
+/// The `typemap` is only locked while looking up the listeners, so handlers can lock it themselves.
+pub async fn dispatch(ctx: &Context, reaction: Reaction, kind: Kind) -> Result<(), Error> {
+    let handlers = ctx.data.read().await.get::<ReactionListeners>().expect("missing reaction listeners").listeners.iter()
+        .filter(|(_, filter, _)| filter.matches(&reaction))
+        .map(|&(_, _, handler)| handler)
+        .collect::<Vec<_>>();
+    for handler in handlers {
+        handler(ctx.clone(), reaction.clone(), kind).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! Randomly reacting to messages with one of the emoji they contain.
+
+use {
+    std::{
+        collections::{
+            BTreeSet,
+            HashMap,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+    rand::{
+        Rng as _,
+        seq::SliceRandom as _,
+        thread_rng,
+    },
+    schemars::JsonSchema,
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    serenity::{
+        framework::standard::{
+            Args,
+            CommandResult,
+            macros::command,
+        },
+        model::prelude::*,
+        prelude::*,
+    },
+    crate::{
+        Error,
+        config::Config as BotConfig,
+        emoji,
+        paths::Paths,
+        setup::log_changes,
+    },
+};
+
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    /// The channels where Peter reacts to messages. Use `!reactions on` and `!reactions off` to change this.
+    #[serde(default)]
+    #[schemars(with = "BTreeSet<u64>")]
+    channels: BTreeSet<ChannelId>,
+    /// The chance of reacting to a message which contains emoji, between 0 and 1.
+    #[serde(default = "default_probability")]
+    probability: f64,
+    /// The minimum time between two reactions in the same channel.
+    #[serde(default = "default_cooldown")]
+    cooldown_secs: u64,
+}
+
+fn default_probability() -> f64 { 0.05 }
+fn default_cooldown() -> u64 { 10 * 60 }
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            channels: BTreeSet::default(),
+            probability: default_probability(),
+            cooldown_secs: default_cooldown(),
+        }
+    }
+}
+
+/// `typemap` key for the time of the last reaction in each channel.
+pub struct Cooldowns;
+
+impl TypeMapKey for Cooldowns {
+    type Value = HashMap<ChannelId, Instant>;
+}
+
+/// Possibly reacts to the message with one of the emoji it contains, according to the guild's settings.
+pub async fn handle_message(ctx: &Context, msg: &Message) -> Result<(), Error> {
+    let guild_id = if let Some(guild_id) = msg.guild_id { guild_id } else { return Ok(()) };
+    let config = if let Some(guild_config) = BotConfig::read(ctx).await.guild(guild_id) { guild_config.reactions.clone() } else { return Ok(()) };
+    if !config.channels.contains(&msg.channel_id) { return Ok(()) }
+    let paths = ctx.data.read().await.get::<Paths>().expect("missing paths").clone();
+    let emoji = emoji::Iter::new(&paths, msg.content.clone()).unique().collect::<Vec<_>>();
+    let reaction = {
+        let mut rng = thread_rng();
+        if !rng.gen_bool(config.probability.max(0.0).min(1.0)) { return Ok(()) }
+        if let Some(reaction) = emoji.choose(&mut rng) { reaction.clone() } else { return Ok(()) }
+    };
+    {
+        let mut data = ctx.data.write().await;
+        let cooldowns = data.entry::<Cooldowns>().or_insert_with(HashMap::default);
+        let now = Instant::now();
+        if cooldowns.get(&msg.channel_id).map_or(false, |&last| now.duration_since(last) < Duration::from_secs(config.cooldown_secs)) { return Ok(()) }
+        cooldowns.insert(msg.channel_id, now);
+    }
+    msg.react(ctx, reaction).await?;
+    Ok(())
+}
+
+/// Enables (`on`) or disables (`off`) random reactions to emoji in this channel.
+#[command]
+#[usage = "on|off"]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_CHANNELS)]
+pub async fn reactions(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.expect("not in guild but only_in(guilds) passed");
+    let enable = match args.rest().trim() {
+        "on" => true,
+        "off" => false,
+        _ => {
+            msg.reply(ctx, "benutze `on` oder `off`").await?;
+            return Ok(())
+        }
+    };
+    let old = BotConfig::update(ctx, |config| {
+        let old = serde_json::to_value(config.guild(guild_id))?;
+        let channels = &mut config.guilds.entry(guild_id).or_default().reactions.channels;
+        if enable { channels.insert(msg.channel_id); } else { channels.remove(&msg.channel_id); }
+        serde_json::Result::Ok(old)
+    }).await??;
+    msg.react(ctx, '✅').await?;
+    log_changes(ctx, guild_id, msg.author.id, &old).await?;
+    Ok(())
+}
@@ -0,0 +1,275 @@
+//! Commands for configuring Peter in a guild: the `!setup` wizard and `!config`.
+
+use {
+    std::{
+        collections::BTreeSet,
+        str::FromStr,
+        time::Duration,
+    },
+    serde::{
+        Serialize,
+        de::DeserializeOwned,
+    },
+    serenity::{
+        framework::standard::{
+            Args,
+            CommandResult,
+            macros::{
+                command,
+                group,
+            },
+        },
+        model::prelude::*,
+        prelude::*,
+        utils::MessageBuilder,
+    },
+    crate::{
+        FENHL,
+        config::{
+            self,
+            Config,
+            Feature,
+            GuildConfig,
+        },
+        lang::join,
+        log_channel,
+    },
+};
+
+/// How long the wizard waits for each answer before giving up.
+const TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Config dumps longer than this are sent as a file instead of a code block, to stay within Discord's message length limit.
+const MAX_INLINE_LEN: usize = 1900;
+
+/// Asks the author of the given message a question in the same channel and waits for a reply, repeating the question until `parse` accepts the reply.
+///
+/// Returns `None` if the author cancels the setup or doesn't reply in time.
+async fn ask<T>(ctx: &Context, msg: &Message, question: impl ToString, mut parse: impl FnMut(&str) -> Result<T, String> + Send) -> serenity::Result<Option<T>> {
+    msg.channel_id.say(ctx, format!("{} (oder `abbrechen`)", question.to_string())).await?;
+    loop {
+        let reply = if let Some(reply) = msg.channel_id.await_reply(ctx).author_id(msg.author.id).timeout(TIMEOUT).await {
+            reply
+        } else {
+            msg.channel_id.say(ctx, "Zeit abgelaufen, die Einrichtung wurde abgebrochen.").await?;
+            return Ok(None)
+        };
+        let answer = reply.content.trim();
+        if answer.eq_ignore_ascii_case("abbrechen") {
+            msg.channel_id.say(ctx, "Die Einrichtung wurde abgebrochen.").await?;
+            return Ok(None)
+        }
+        match parse(answer) {
+            Ok(value) => return Ok(Some(value)),
+            Err(e) => { reply.reply(ctx, format!("{}, bitte versuche es noch einmal", e)).await?; }
+        }
+    }
+}
+
+/// Parses a whitespace-separated list of mentions or IDs, or `keine` for the empty list.
+fn parse_ids<T: FromStr + Ord>(answer: &str, is_valid: impl Fn(&T) -> bool) -> Result<BTreeSet<T>, String> {
+    if ["keine", "keiner", "-"].iter().any(|none| answer.eq_ignore_ascii_case(none)) { return Ok(BTreeSet::default()) }
+    answer.split_whitespace()
+        .map(|word| T::from_str(word).ok().filter(|id| is_valid(id)).ok_or_else(|| format!("„{}“ gibt es auf diesem Server nicht", word)))
+        .collect()
+}
+
+/// Asks a few questions to set up Peter on this server: the log channel, moderator roles, ignored channels, and which features to enable.
+#[command]
+#[owners_only]
+#[only_in(guilds)]
+pub async fn setup(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+    let guild_id = msg.guild_id.expect("not in guild but only_in(guilds) passed");
+    let channels = guild_id.channels(ctx).await?;
+    let roles = guild_id.roles(ctx).await?;
+    let is_text_channel = |channel_id: &ChannelId| channels.get(channel_id).map_or(false, |channel| channel.kind == ChannelType::Text);
+    let log_channel = match ask(ctx, msg, "In welchen Channel soll ich Log-Meldungen schicken? Antworte mit einer Channel-Erwähnung oder `keiner`.", |answer| {
+        let mut log_channels = parse_ids(answer, is_text_channel)?.into_iter();
+        let log_channel = log_channels.next();
+        if log_channels.next().is_some() { return Err(format!("bitte gib höchstens einen Channel an")) }
+        Ok(log_channel)
+    }).await? {
+        Some(log_channel) => log_channel,
+        None => return Ok(()),
+    };
+    let mod_roles = match ask(ctx, msg, "Welche Rollen sollen Moderationsrechte haben? Antworte mit Rollen-Erwähnungen oder `keine`.", |answer| parse_ids(answer, |role_id| roles.contains_key(role_id))).await? {
+        Some(mod_roles) => mod_roles,
+        None => return Ok(()),
+    };
+    let ignored_channels = match ask(ctx, msg, "Welche Channels soll ich ignorieren? Antworte mit Channel-Erwähnungen oder `keine`.", |answer| parse_ids(answer, |channel_id| channels.contains_key(channel_id))).await? {
+        Some(ignored_channels) => ignored_channels,
+        None => return Ok(()),
+    };
+    let mut question = MessageBuilder::default();
+    question.push("Welche Funktionen sollen aktiviert werden? Antworte mit den Nummern, `alle` oder `keine`.");
+    for (i, feature) in Feature::ALL.iter().enumerate() {
+        question.push_line("").push(format!("{}: {}", i + 1, feature));
+    }
+    let features = match ask(ctx, msg, question.build(), |answer| if answer.eq_ignore_ascii_case("alle") {
+        Ok(Feature::ALL.iter().copied().collect())
+    } else {
+        parse_ids::<usize>(answer, |&n| n >= 1 && n <= Feature::ALL.len()).map(|numbers| numbers.into_iter().map(|n| Feature::ALL[n - 1]).collect::<BTreeSet<_>>())
+    }).await? {
+        Some(features) => features,
+        None => return Ok(()),
+    };
+    let mut summary = MessageBuilder::default();
+    summary.push("Einrichtung abgeschlossen. Aktivierte Funktionen: ");
+    summary.push_safe(join(Some(format!("keine")), features.iter().map(|feature| feature.to_string())));
+    let old = Config::update(ctx, |config| {
+        let old = serde_json::to_value(config.guild(guild_id))?;
+        let guild_config = config.guilds.entry(guild_id).or_default();
+        guild_config.channels.ignored = ignored_channels;
+        guild_config.features = features;
+        guild_config.channels.log = log_channel;
+        guild_config.mod_roles = mod_roles;
+        serde_json::Result::Ok(old)
+    }).await??;
+    msg.channel_id.say(ctx, summary).await?;
+    log_changes(ctx, guild_id, msg.author.id, &old).await?;
+    Ok(())
+}
+
+/// Parses a value given to `!config set` or `wwconfig set`. Mentions are converted to IDs, and if the current value is a list, whitespace-separated input is read as a list.
+fn parse_value(current: &serde_json::Value, input: &str) -> serde_json::Value {
+    fn parse_word(word: &str) -> serde_json::Value {
+        let id = word.trim_start_matches('<').trim_start_matches(&['#', '@', '&', '!'][..]).trim_end_matches('>');
+        id.parse::<u64>().map_or_else(|_| serde_json::Value::String(word.to_owned()), serde_json::Value::from)
+    }
+
+    if ["keine", "keiner", "-"].iter().any(|none| input.eq_ignore_ascii_case(none)) {
+        return if current.is_array() { serde_json::Value::Array(Vec::default()) } else { serde_json::Value::Null }
+    }
+    if let Ok(value) = serde_json::from_str(input) { return value }
+    if current.is_array() {
+        serde_json::Value::Array(input.split_whitespace().map(parse_word).collect())
+    } else {
+        parse_word(input)
+    }
+}
+
+/// Returns a copy of the given config with the setting at the given dot-separated path changed.
+pub(crate) fn set_key<T: Serialize + DeserializeOwned>(config: &T, key: &str, input: &str) -> Result<T, String> {
+    let mut json = serde_json::to_value(config).map_err(|e| e.to_string())?;
+    let mut target = &mut json;
+    for component in key.split('.') {
+        target = target.as_object_mut().and_then(|map| map.get_mut(component)).ok_or_else(|| format!("die Einstellung „{}“ gibt es nicht", key))?;
+    }
+    *target = parse_value(target, input);
+    serde_json::from_value(json).map_err(|e| format!("ungültiger Wert: {}", e))
+}
+
+/// Posts the changes to a guild's settings to the guild's log channel, if it has one.
+pub(crate) async fn log_changes(ctx: &Context, guild_id: GuildId, author: UserId, old: &serde_json::Value) -> CommandResult {
+    let new = serde_json::to_value(Config::read(ctx).await.guild(guild_id))?;
+    let changes = config::diff(old, &new);
+    if changes.is_empty() { return Ok(()) }
+    let mut builder = MessageBuilder::default();
+    builder.mention(&author);
+    builder.push(" hat die Einstellungen geändert:");
+    builder.push_codeblock_safe(changes.join("\n"), None);
+    log_channel::post(ctx, guild_id, builder).await?;
+    Ok(())
+}
+
+pub(crate) async fn send_json(ctx: &Context, msg: &Message, json: &serde_json::Value) -> CommandResult {
+    let text = serde_json::to_string_pretty(json)?;
+    if text.len() <= MAX_INLINE_LEN {
+        let mut reply = MessageBuilder::default();
+        reply.push_codeblock_safe(text, Some("json"));
+        msg.channel_id.say(ctx, reply).await?;
+    } else {
+        msg.channel_id.send_files(ctx, vec![(text.as_bytes(), "config.json")], |m| m).await?;
+    }
+    Ok(())
+}
+
+/// Shows or changes the command prefix for this server.
+///
+/// `!prefix` shows the current prefix, `!prefix <prefix>` changes it. Commands can always be used by mentioning the bot instead, e.g. `@peter prefix !` to reset a forgotten prefix.
+#[command]
+#[usage = "[<Präfix>]"]
+#[example = "?"]
+#[required_permissions(ADMINISTRATOR)]
+#[only_in(guilds)]
+pub async fn prefix(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.expect("not in guild but only_in(guilds) passed");
+    let new_prefix = args.rest().trim();
+    if new_prefix.is_empty() {
+        let reply = MessageBuilder::default().push("das Präfix auf diesem Server ist ").push_mono_safe(Config::read(ctx).await.prefix(Some(guild_id))).build();
+        msg.reply(ctx, reply).await?;
+        return Ok(())
+    }
+    if new_prefix.contains(char::is_whitespace) {
+        msg.reply(ctx, "das Präfix darf keine Leerzeichen enthalten").await?;
+        return Ok(())
+    }
+    let old = Config::update(ctx, |config| {
+        let old = serde_json::to_value(config.guild(guild_id))?;
+        config.guilds.entry(guild_id).or_default().prefix = new_prefix.to_owned();
+        serde_json::Result::Ok(old)
+    }).await??;
+    msg.react(ctx, '✅').await?;
+    log_changes(ctx, guild_id, msg.author.id, &old).await?;
+    Ok(())
+}
+
+/// Shows or changes the settings for this server.
+///
+/// `!config` shows the current settings. `!config set <setting> <value>` changes a setting, e.g. `!config set channels.voice #voice`. Use `keine` as the value to clear a setting.
+#[command]
+#[usage = "[set <Einstellung> <Wert>]"]
+#[example = "set channels.voice #voice"]
+#[example = "set modRoles keine"]
+#[required_permissions(ADMINISTRATOR)]
+#[only_in(guilds)]
+pub async fn config(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.expect("not in guild but only_in(guilds) passed");
+    match args.single::<String>().ok().as_deref() {
+        None | Some("show") => {
+            let json = serde_json::to_value(Config::read(ctx).await.guild(guild_id))?;
+            send_json(ctx, msg, &json).await?;
+        }
+        Some("global") if msg.author.id == FENHL => {
+            let json = Config::read(ctx).await.redacted()?;
+            send_json(ctx, msg, &json).await?;
+        }
+        Some("set") => {
+            let key = if let Ok(key) = args.single::<String>() { key } else {
+                msg.reply(ctx, "bitte gib an, welche Einstellung du ändern möchtest").await?;
+                return Ok(())
+            };
+            let input = args.rest().trim();
+            if input.is_empty() {
+                msg.reply(ctx, "bitte gib einen Wert an").await?;
+                return Ok(())
+            }
+            let result = Config::update(ctx, |config| {
+                let old = serde_json::to_value(config.guild(guild_id))?;
+                let default = GuildConfig::default();
+                serde_json::Result::Ok(set_key(config.guild(guild_id).unwrap_or(&default), &key, input).map(|guild_config| {
+                    config.guilds.insert(guild_id, guild_config);
+                    old
+                }))
+            }).await??;
+            match result {
+                Ok(old) => {
+                    msg.react(ctx, '✅').await?;
+                    log_changes(ctx, guild_id, msg.author.id, &old).await?;
+                }
+                Err(e) => { msg.reply(ctx, e).await?; }
+            }
+        }
+        Some(_) => { msg.reply(ctx, "unbekannter Befehl, benutze `!config` oder `!config set <Einstellung> <Wert>`").await?; }
+    }
+    Ok(())
+}
+
+/// Commands for configuring Peter on a server.
+#[group("Einrichtung")]
+#[commands(
+    config,
+    prefix,
+    setup,
+)]
+pub struct Setup;
@@ -0,0 +1,216 @@
+//! Usage statistics, kept as hourly counters which are rolled up into daily and monthly aggregates as they age.
+
+use {
+    std::{
+        collections::BTreeMap,
+        convert::Infallible as Never,
+        io,
+        mem,
+        time::Duration as StdDuration,
+    },
+    chrono::{
+        Duration,
+        prelude::*,
+    },
+    schemars::JsonSchema,
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    serenity::{
+        framework::standard::{
+            Args,
+            CommandResult,
+            macros::command,
+        },
+        model::prelude::*,
+        prelude::*,
+    },
+    serenity_utils::RwFuture,
+    tokio::{
+        fs,
+        time::sleep,
+    },
+    crate::{
+        Error,
+        atomic_file,
+        paths::Paths,
+    },
+};
+
+/// Bucket keys use these formats so that sorting them as strings sorts them chronologically, and a bucket's key is a prefix of the next coarser bucket's key.
+const HOUR_FORMAT: &str = "%Y-%m-%dT%H";
+const DAY_FORMAT: &str = "%Y-%m-%d";
+const MONTH_FORMAT: &str = "%Y-%m";
+/// The lengths of formatted day and month keys, used to find the coarser bucket for a key.
+const DAY_KEY_LEN: usize = 10;
+const MONTH_KEY_LEN: usize = 7;
+
+/// How long each granularity of counters is kept.
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    /// Hourly counters older than this are rolled up into daily counters.
+    #[serde(default = "default_hourly_retention")]
+    hourly_retention_hours: u32,
+    /// Daily counters older than this are rolled up into monthly counters.
+    #[serde(default = "default_daily_retention")]
+    daily_retention_days: u32,
+    /// Monthly counters older than this are deleted. If absent, they are kept forever.
+    #[serde(default)]
+    monthly_retention_months: Option<u32>,
+}
+
+fn default_hourly_retention() -> u32 { 48 }
+fn default_daily_retention() -> u32 { 90 }
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            hourly_retention_hours: default_hourly_retention(),
+            daily_retention_days: default_daily_retention(),
+            monthly_retention_months: None,
+        }
+    }
+}
+
+type Counters = BTreeMap<String, u64>;
+
+/// `typemap` key for the stats store. Counters are only written to disk by [`maintain`] and `!stats prune`.
+#[derive(Default, Deserialize, Serialize)]
+pub struct Stats {
+    #[serde(default)]
+    hourly: BTreeMap<String, Counters>,
+    #[serde(default)]
+    daily: BTreeMap<String, Counters>,
+    #[serde(default)]
+    monthly: BTreeMap<String, Counters>,
+}
+
+impl TypeMapKey for Stats {
+    type Value = Stats;
+}
+
+/// The number of buckets affected by a [`Stats::rollup`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Rollup {
+    pub hours_rolled_up: usize,
+    pub days_rolled_up: usize,
+    pub months_deleted: usize,
+}
+
+impl Stats {
+    pub async fn load(paths: &Paths) -> Result<Stats, Error> {
+        match fs::read_to_string(paths.stats()).await {
+            Ok(buf) => Ok(serde_json::from_str(&buf)?), //TODO use async-json
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Stats::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn save(&self, paths: &Paths) -> Result<(), Error> {
+        let buf = serde_json::to_vec(self)?; //TODO use async-json
+        atomic_file::write(&paths.stats(), &buf).await?;
+        Ok(())
+    }
+
+    /// Counts one occurrence of the given metric, e.g. `command:ping`.
+    pub fn increment(&mut self, metric: impl Into<String>) {
+        *self.hourly.entry(Utc::now().format(HOUR_FORMAT).to_string()).or_default().entry(metric.into()).or_insert(0) += 1;
+    }
+
+    /// Returns the total counts of all metrics starting with the given prefix since the given time (or all time if `None`), keyed by the rest of the metric name.
+    ///
+    /// Counters which have been rolled up are included if their day or month overlaps with the time frame, so older totals may include some earlier events.
+    pub fn totals(&self, prefix: &str, since: Option<DateTime<Utc>>) -> BTreeMap<String, u64> {
+        let mut totals = BTreeMap::<_, u64>::default();
+        for (buckets, format) in &[(&self.hourly, HOUR_FORMAT), (&self.daily, DAY_FORMAT), (&self.monthly, MONTH_FORMAT)] {
+            let buckets = if let Some(since) = since { buckets.range(since.format(format).to_string()..) } else { buckets.range::<String, _>(..) };
+            for (_, counters) in buckets {
+                for (metric, count) in counters {
+                    if let Some(suffix) = metric.strip_prefix(prefix) {
+                        *totals.entry(suffix.to_owned()).or_insert(0) += count;
+                    }
+                }
+            }
+        }
+        totals
+    }
+
+    /// Merges counters older than the configured retention periods into the next coarser granularity, and deletes monthly counters past their retention.
+    pub fn rollup(&mut self, config: &Config, now: DateTime<Utc>) -> Rollup {
+        fn merge(into: &mut BTreeMap<String, Counters>, key_len: usize, buckets: BTreeMap<String, Counters>) {
+            for (key, counters) in buckets {
+                let target = into.entry(key[..key_len].to_owned()).or_default();
+                for (metric, count) in counters {
+                    *target.entry(metric).or_insert(0) += count;
+                }
+            }
+        }
+
+        let hourly_cutoff = (now - Duration::hours(config.hourly_retention_hours.into())).format(HOUR_FORMAT).to_string();
+        let newer = self.hourly.split_off(&hourly_cutoff);
+        let old_hours = mem::replace(&mut self.hourly, newer);
+        let hours_rolled_up = old_hours.len();
+        merge(&mut self.daily, DAY_KEY_LEN, old_hours);
+        let daily_cutoff = (now - Duration::days(config.daily_retention_days.into())).format(DAY_FORMAT).to_string();
+        let newer = self.daily.split_off(&daily_cutoff);
+        let old_days = mem::replace(&mut self.daily, newer);
+        let days_rolled_up = old_days.len();
+        merge(&mut self.monthly, MONTH_KEY_LEN, old_days);
+        let months_deleted = if let Some(months) = config.monthly_retention_months {
+            let months_since_epoch = now.year() * 12 + now.month0() as i32 - months as i32;
+            let monthly_cutoff = Utc.ymd(months_since_epoch.div_euclid(12), months_since_epoch.rem_euclid(12) as u32 + 1, 1).format(MONTH_FORMAT).to_string();
+            let newer = self.monthly.split_off(&monthly_cutoff);
+            mem::replace(&mut self.monthly, newer).len()
+        } else {
+            0
+        };
+        Rollup { hours_rolled_up, days_rolled_up, months_deleted }
+    }
+}
+
+/// Rolls up and saves the stats once per hour.
+pub async fn maintain(ctx_fut: RwFuture<Context>) -> Result<Never, Error> {
+    loop {
+        sleep(StdDuration::from_secs(60 * 60)).await;
+        let ctx = ctx_fut.read().await;
+        let mut data = (*ctx).data.write().await;
+        let paths = data.get::<Paths>().expect("missing paths").clone();
+        let config = data.get::<crate::config::Config>().ok_or(Error::MissingConfig)?.read().await.stats.clone();
+        let stats = data.get_mut::<Stats>().expect("missing stats");
+        stats.rollup(&config, Utc::now());
+        stats.save(&paths).await?;
+    }
+}
+
+/// Shows how many counters are stored at each granularity.
+#[command]
+#[owners_only]
+#[sub_commands(prune)]
+pub async fn stats(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+    let reply = {
+        let data = ctx.data.read().await;
+        let stats = data.get::<Stats>().expect("missing stats");
+        format!("{} Stunden, {} Tage und {} Monate an Statistiken gespeichert", stats.hourly.len(), stats.daily.len(), stats.monthly.len())
+    };
+    msg.reply(ctx, reply).await?;
+    Ok(())
+}
+
+/// Rolls up old counters now instead of waiting for the hourly maintenance.
+#[command]
+#[owners_only]
+pub async fn prune(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+    let Rollup { hours_rolled_up, days_rolled_up, months_deleted } = {
+        let mut data = ctx.data.write().await;
+        let paths = data.get::<Paths>().expect("missing paths").clone();
+        let config = data.get::<crate::config::Config>().expect("missing config").read().await.stats.clone();
+        let stats = data.get_mut::<Stats>().expect("missing stats");
+        let rollup = stats.rollup(&config, Utc::now());
+        stats.save(&paths).await?;
+        rollup
+    };
+    msg.reply(ctx, format!("{} Stunden und {} Tage zusammengefasst, {} Monate gelöscht", hours_rolled_up, days_rolled_up, months_deleted)).await?;
+    Ok(())
+}
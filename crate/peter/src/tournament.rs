@@ -0,0 +1,237 @@
+//! Werewolf seasons, which track points across games.
+
+use {
+    std::{
+        collections::{
+            BTreeMap,
+            HashSet,
+        },
+        io,
+    },
+    schemars::JsonSchema,
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    serenity::{
+        framework::standard::{
+            Args,
+            CommandResult,
+            macros::command,
+        },
+        model::prelude::*,
+        prelude::*,
+        utils::MessageBuilder,
+    },
+    tokio::fs,
+    crate::{
+        Error,
+        atomic_file,
+        config::Config,
+        paths::Paths,
+    },
+};
+
+/// How many points players get for each game in a season.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Scoring {
+    #[serde(default = "default_participation")]
+    participation: i64,
+    #[serde(default = "default_win")]
+    win: i64,
+}
+
+fn default_participation() -> i64 { 1 }
+fn default_win() -> i64 { 3 }
+
+impl Default for Scoring {
+    fn default() -> Scoring {
+        Scoring {
+            participation: default_participation(),
+            win: default_win(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Season {
+    name: String,
+    /// Copied from the werewolf config when the season starts, so changing the config doesn't affect running seasons.
+    scoring: Scoring,
+    games: u32,
+    points: BTreeMap<UserId, i64>,
+    /// The players with the most points at the end of the season.
+    #[serde(default)]
+    champions: Vec<UserId>,
+}
+
+impl Season {
+    fn new(name: String, scoring: Scoring) -> Season {
+        Season {
+            name, scoring,
+            games: 0,
+            points: BTreeMap::default(),
+            champions: Vec::default(),
+        }
+    }
+
+    fn record_game(&mut self, players: &[UserId], winners: &HashSet<UserId>) {
+        self.games += 1;
+        for player in players {
+            *self.points.entry(*player).or_insert(0) += self.scoring.participation + if winners.contains(player) { self.scoring.win } else { 0 };
+        }
+    }
+
+    /// Players sorted by points, highest first.
+    fn standings(&self) -> Vec<(UserId, i64)> {
+        let mut standings = self.points.iter().map(|(&player, &points)| (player, points)).collect::<Vec<_>>();
+        standings.sort_by_key(|&(player, points)| (-points, player));
+        standings
+    }
+
+    fn standings_message(&self) -> MessageBuilder {
+        let mut builder = MessageBuilder::default();
+        builder.push("Stand der Saison ");
+        builder.push_bold_safe(&self.name);
+        builder.push(format!(" nach {} {}:", self.games, if self.games == 1 { "Spiel" } else { "Spielen" }));
+        for (rank, (player, points)) in self.standings().into_iter().enumerate() {
+            builder.push_line("");
+            builder.push(format!("{}. ", rank + 1));
+            builder.mention(&player);
+            builder.push(format!(": {} {}", points, if points == 1 { "Punkt" } else { "Punkte" }));
+        }
+        builder
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GuildSeasons {
+    #[serde(default)]
+    current: Option<Season>,
+    #[serde(default)]
+    past: Vec<Season>,
+}
+
+/// All seasons in all guilds, stored as a JSON file which is also read by gefolge.org.
+#[derive(Default, Deserialize, Serialize)]
+pub struct Seasons(BTreeMap<GuildId, GuildSeasons>);
+
+impl Seasons {
+    pub async fn load(paths: &Paths) -> Result<Seasons, Error> {
+        match fs::read_to_string(paths.werewolf_seasons()).await {
+            Ok(buf) => Ok(serde_json::from_str(&buf)?), //TODO use async-json
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Seasons::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn save(&self, paths: &Paths) -> Result<(), Error> {
+        let buf = serde_json::to_vec_pretty(self)?; //TODO use async-json
+        atomic_file::write(&paths.werewolf_seasons(), &buf).await?;
+        Ok(())
+    }
+}
+
+/// Adds the points for a completed game to the guild's current season, if any, and posts the standings to the given channel.
+pub(crate) async fn record_game(ctx: &Context, paths: &Paths, guild: GuildId, channel: ChannelId, players: &[UserId], winners: &HashSet<UserId>) -> Result<(), Error> {
+    let mut seasons = Seasons::load(paths).await?;
+    let season = if let Some(season) = seasons.0.get_mut(&guild).and_then(|guild_seasons| guild_seasons.current.as_mut()) { season } else { return Ok(()) };
+    season.record_game(players, winners);
+    let standings = season.standings_message();
+    seasons.save(paths).await?;
+    channel.say(ctx, standings).await?;
+    Ok(())
+}
+
+/// Shows the standings of the current werewolf season.
+#[command]
+#[only_in(guilds)]
+#[sub_commands(season_start, season_end)]
+pub async fn season(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+    let guild_id = msg.guild_id.expect("not in guild but only_in(guilds) passed");
+    let paths = ctx.data.read().await.get::<Paths>().expect("missing paths").clone();
+    let seasons = Seasons::load(&paths).await?;
+    if let Some(season) = seasons.0.get(&guild_id).and_then(|guild_seasons| guild_seasons.current.as_ref()) {
+        msg.channel_id.say(ctx, season.standings_message()).await?;
+    } else {
+        msg.reply(ctx, "aktuell läuft keine Saison").await?;
+    }
+    Ok(())
+}
+
+/// Starts a new werewolf season with the given name. Games on this server count towards the season until it is ended using `!season end`.
+#[command("start")]
+#[usage = "<Name>"]
+#[example = "Sommer 2021"]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+async fn season_start(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.expect("not in guild but only_in(guilds) passed");
+    let name = args.rest().trim();
+    if name.is_empty() {
+        msg.reply(ctx, "bitte gib einen Namen für die Saison an").await?;
+        return Ok(())
+    }
+    let (paths, scoring) = {
+        let data = ctx.data.read().await;
+        (
+            data.get::<Paths>().expect("missing paths").clone(),
+            {
+                // use the scoring of the game played in this channel, if any, otherwise that of the guild's first game
+                let config = data.get::<Config>().expect("missing config").read().await;
+                config.werewolf(guild_id, msg.channel_id)
+                    .or_else(|| config.guild(guild_id).and_then(|guild_config| guild_config.werewolf.first()))
+                    .map(|conf| conf.scoring)
+                    .unwrap_or_default()
+            },
+        )
+    };
+    let mut seasons = Seasons::load(&paths).await?;
+    let guild_seasons = seasons.0.entry(guild_id).or_default();
+    if let Some(ref current) = guild_seasons.current {
+        msg.reply(ctx, MessageBuilder::default().push("die Saison ").push_bold_safe(&current.name).push(" läuft noch")).await?;
+        return Ok(())
+    }
+    guild_seasons.current = Some(Season::new(name.to_owned(), scoring));
+    seasons.save(&paths).await?;
+    msg.react(ctx, '✅').await?;
+    Ok(())
+}
+
+/// Ends the current werewolf season and announces the winners.
+#[command("end")]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+async fn season_end(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+    let guild_id = msg.guild_id.expect("not in guild but only_in(guilds) passed");
+    let paths = ctx.data.read().await.get::<Paths>().expect("missing paths").clone();
+    let mut seasons = Seasons::load(&paths).await?;
+    let guild_seasons = seasons.0.entry(guild_id).or_default();
+    let mut season = if let Some(season) = guild_seasons.current.take() { season } else {
+        msg.reply(ctx, "aktuell läuft keine Saison").await?;
+        return Ok(())
+    };
+    let standings = season.standings();
+    season.champions = standings.iter().take_while(|&&(_, points)| Some(points) == standings.first().map(|&(_, max_points)| max_points)).map(|&(player, _)| player).collect();
+    let mut builder = season.standings_message();
+    builder.push_line("");
+    builder.push("Die Saison ist vorbei! ");
+    match season.champions.len() {
+        0 => { builder.push("Es wurden keine Spiele gespielt."); }
+        1 => { builder.mention(&season.champions[0]).push(" hat gewonnen!"); }
+        _ => {
+            for (i, champion) in season.champions.iter().enumerate() {
+                if i > 0 { builder.push(" "); }
+                builder.mention(champion);
+            }
+            builder.push(" haben gemeinsam gewonnen!");
+        }
+    }
+    guild_seasons.past.push(season);
+    seasons.save(&paths).await?;
+    msg.channel_id.say(ctx, builder).await?;
+    Ok(())
+}
@@ -10,6 +10,7 @@ use {
         prelude::*,
     },
     itertools::Itertools as _,
+    schemars::JsonSchema,
     serde::{
         Deserialize,
         Serialize,
@@ -31,19 +32,20 @@ use {
 const CHANNEL: ChannelId = ChannelId(668518137334857728);
 const ROLE: RoleId = RoleId(668534306515320833);
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
     #[serde(rename = "clientID")]
     client_id: String,
     client_secret: String,
+    #[schemars(with = "BTreeMap<String, String>")]
     users: BTreeMap<UserId, twitch_helix::model::UserId>,
 }
 
 async fn client_and_users(ctx_fut: &RwFuture<Context>) -> Result<(Client<'static>, BTreeMap<UserId, twitch_helix::model::UserId>), Error> {
     let ctx = ctx_fut.read().await;
     let ctx_data = (*ctx).data.read().await;
-    let config = ctx_data.get::<crate::config::Config>().ok_or(Error::MissingConfig)?;
+    let config = ctx_data.get::<crate::config::Config>().ok_or(Error::MissingConfig)?.read().await;
     Ok((Client::new(
         concat!("peter-discord/", env!("CARGO_PKG_VERSION")),
         config.twitch.client_id.clone(),
@@ -54,7 +56,7 @@ async fn client_and_users(ctx_fut: &RwFuture<Context>) -> Result<(Client<'static
 async fn get_users(ctx_fut: &RwFuture<Context>) -> Result<BTreeMap<UserId, twitch_helix::model::UserId>, Error> {
     let ctx = ctx_fut.read().await;
     let ctx_data = (*ctx).data.read().await;
-    let config = ctx_data.get::<crate::config::Config>().ok_or(Error::MissingConfig)?;
+    let config = ctx_data.get::<crate::config::Config>().ok_or(Error::MissingConfig)?.read().await;
     Ok(config.twitch.users.clone())
 }
 
@@ -89,6 +91,7 @@ pub async fn alerts(ctx_fut: RwFuture<Context>) -> Result<Never, Error> {
 /// Returns the set of Gefolge members who are currently live on Twitch.
 async fn status(client: &Client<'_>, users: BTreeMap<UserId, twitch_helix::model::UserId>) -> Result<BTreeMap<UserId, Stream>, Error> {
     let mut map = BTreeMap::default();
+    if users.is_empty() { return Ok(map) } // listing streams without a user filter would return arbitrary streams
     let stream_infos = Stream::list(client, None, Some(users.values().cloned().collect()), None);
     pin_mut!(stream_infos);
     while let Some(stream_info) = stream_infos.try_next().await? {
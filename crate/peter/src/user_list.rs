@@ -1,92 +1,957 @@
-//! Helper functions for maintaining the guild member list on disk, which is used by gefolge.org to verify logins.
+//! Helper functions for maintaining the guild member list, which is used by gefolge.org to verify logins.
+//!
+//! The list is stored either as a directory with one JSON file per member, or as an SQLite database with one row per member, see [`Config`]. Profiles of members who left are kept separately, see [`List::Former`].
 
 use {
     std::{
-        collections::BTreeSet,
+        collections::{
+            BTreeMap,
+            BTreeSet,
+            HashMap,
+        },
+        convert::Infallible as Never,
+        fmt,
         io,
+        num::NonZeroU64,
+        path::{
+            Path,
+            PathBuf,
+        },
+        str::FromStr,
+        sync::{
+            Arc,
+            Mutex,
+        },
+        time::Duration as StdDuration,
     },
+    async_trait::async_trait,
     chrono::prelude::*,
+    fs2::FileExt as _,
+    rand::{
+        Rng as _,
+        thread_rng,
+    },
+    reqwest::header::CONTENT_TYPE,
+    rusqlite::{
+        Connection,
+        OptionalExtension as _,
+        params,
+    },
+    schemars::JsonSchema,
     serde::{
         Deserialize,
         Serialize,
+        de::DeserializeOwned,
+    },
+    serenity::{
+        framework::standard::{
+            Args,
+            CommandResult,
+            macros::command,
+        },
+        model::prelude::*,
+        prelude::*,
     },
-    serenity::model::prelude::*,
+    serenity_utils::RwFuture,
     tokio::{
-        fs::File,
-        io::{
-            AsyncReadExt as _,
-            AsyncWriteExt as _,
+        fs::{
+            self,
+            File,
         },
+        io::AsyncReadExt as _,
+        sync::Mutex as AsyncMutex,
+        task::block_in_place,
+        time::sleep,
+    },
+    crate::{
+        Error,
+        GEFOLGE,
+        atomic_file,
+        log_channel,
+        paths::Paths,
+        webhook,
     },
-    crate::Error,
 };
 
-const PROFILES_DIR: &'static str = "/usr/local/share/fidera/profiles";
+/// The `userList` section of the config file.
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    #[serde(default)]
+    backend: Backend,
+    /// How often to compare the member list to the guild's members, to correct for missed events. Also how long to wait before retrying a failed comparison.
+    #[serde(default = "default_resync_interval")]
+    resync_interval_hours: NonZeroU64,
+    /// If present, gefolge.org is notified of each change to the member list.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    webhook: Option<WebhookConfig>,
+}
+
+fn default_resync_interval() -> NonZeroU64 { NonZeroU64::new(24).expect("nonzero literal") }
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            backend: Backend::default(),
+            resync_interval_hours: default_resync_interval(),
+            webhook: None,
+        }
+    }
+}
 
+/// Where to send member list change notifications, see [`Notifying`].
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct WebhookConfig {
+    url: String,
+    /// Used to sign notifications using the [`Peter`](webhook::Scheme::Peter) scheme.
+    secret: String,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+enum Backend {
+    /// One JSON file per member in the profiles directory.
+    Json,
+    /// A single SQLite database next to the profiles directory, see [`Paths::profiles_db`].
+    Sqlite,
+}
+
+impl Default for Backend {
+    fn default() -> Backend { Backend::Json }
+}
+
+/// A guild member's data as stored in the member list.
+///
+/// Kept up to date by the `guild_member_update` event, which Discord also sends when a member's user data like their avatar changes.
 #[derive(Deserialize, Serialize)]
-struct Profile {
+pub struct Profile {
+    /// The CDN URL of the user's avatar, or `None` if they use a default avatar.
+    #[serde(default)]
+    avatar: Option<String>,
+    /// The member's server boosts since boost history was introduced, oldest first.
+    #[serde(default)]
+    boost_history: Vec<Boost>,
     bot: bool,
     discriminator: u16,
     joined: Option<DateTime<Utc>>,
+    /// When the member left the guild. Only present in [`List::Former`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    left: Option<DateTime<Utc>>,
     nick: Option<String>,
+    /// Whether the member has yet to pass the guild's membership screening.
+    #[serde(default)]
+    pending: bool,
+    /// Extra data about the member stored by other modules, see [`ProfileField`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    peter: BTreeMap<String, serde_json::Value>,
+    /// When the member started boosting the guild, or `None` if they're not boosting it.
+    #[serde(default)]
+    premium_since: Option<DateTime<Utc>>,
+    /// Changes to `roles` since role history was introduced, oldest first.
+    #[serde(default)]
+    role_history: Vec<RoleChange>,
     roles: BTreeSet<RoleId>,
     snowflake: UserId,
     username: String,
 }
 
-/// Add a Discord account to the list of Gefolge guild members.
-pub async fn add(member: Member, join_date: Option<DateTime<Utc>>) -> Result<(), Error> {
-    let mut f = File::create(format!("{}/{}.json", PROFILES_DIR, member.user.id)).await?;
-    let buf = serde_json::to_vec_pretty(&Profile {
-        bot: member.user.bot,
-        discriminator: member.user.discriminator,
-        joined: member.joined_at.or(join_date),
-        nick: member.nick,
-        roles: member.roles.into_iter().collect(),
-        snowflake: member.user.id,
-        username: member.user.name,
-    })?;
-    f.write_all(&buf).await?;
-    Ok(())
+#[derive(Deserialize, Serialize)]
+struct Boost {
+    start: DateTime<Utc>,
+    /// `None` if the member is still boosting.
+    end: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct RoleChange {
+    timestamp: DateTime<Utc>,
+    added: BTreeSet<RoleId>,
+    removed: BTreeSet<RoleId>,
 }
 
-/// Remove a Discord account from the list of Gefolge guild members.
-pub async fn remove<U: Into<UserId>>(user: U) -> io::Result<Option<DateTime<Utc>>> {
-    let join_date = match File::open(format!("{}/{}.json", PROFILES_DIR, user.into())).await {
-        Ok(mut f) => {
-            let mut buf = Vec::default();
-            f.read_to_end(&mut buf).await?;
-            serde_json::from_slice::<Profile>(&buf)?.joined
+impl Profile {
+    /// Creates a profile from the given member data, keeping the join date and role history from the previous version of the profile if there is one.
+    fn new(member: Member, join_date: Option<DateTime<Utc>>, old: Option<Profile>) -> Profile {
+        let roles = member.roles.into_iter().collect::<BTreeSet<_>>();
+        let (old_joined, peter, boost_history, role_history) = if let Some(old) = old {
+            let mut role_history = old.role_history;
+            let added = roles.difference(&old.roles).copied().collect::<BTreeSet<_>>();
+            let removed = old.roles.difference(&roles).copied().collect::<BTreeSet<_>>();
+            if !added.is_empty() || !removed.is_empty() {
+                role_history.push(RoleChange { timestamp: Utc::now(), added, removed });
+            }
+            let mut boost_history = old.boost_history;
+            if old.premium_since != member.premium_since {
+                if let Some(old_since) = old.premium_since {
+                    match boost_history.last_mut() {
+                        Some(boost) if boost.end.is_none() => boost.end = Some(Utc::now()),
+                        _ => boost_history.push(Boost { start: old_since, end: Some(Utc::now()) }),
+                    }
+                }
+                if let Some(since) = member.premium_since {
+                    boost_history.push(Boost { start: since, end: None });
+                }
+            }
+            (old.joined, old.peter, boost_history, role_history)
+        } else {
+            let boost_history = member.premium_since.map(|since| Boost { start: since, end: None }).into_iter().collect();
+            (None, BTreeMap::default(), boost_history, Vec::default())
+        };
+        Profile {
+            avatar: member.user.avatar_url(),
+            boost_history,
+            bot: member.user.bot,
+            discriminator: member.user.discriminator,
+            joined: member.joined_at.or(join_date).or(old_joined),
+            left: None,
+            nick: member.nick,
+            pending: member.pending,
+            peter,
+            premium_since: member.premium_since,
+            role_history, roles,
+            snowflake: member.user.id,
+            username: member.user.name,
         }
-        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
-        Err(e) => return Err(e),
+    }
+}
+
+/// Extra data about a member which is stored in their profile under `peter.<KEY>`, so gefolge.org can find everything about a member in one document.
+///
+/// Implement this on a type in the module which owns the data, then use [`get_field`](dyn Store::get_field) and [`set_field`](dyn Store::set_field) to access it.
+pub trait ProfileField: Serialize + DeserializeOwned {
+    /// The key under `peter` in the profile JSON. Must be unique among all implementations.
+    const KEY: &'static str;
+}
+
+impl dyn Store {
+    /// Returns the given field from the member's profile, or `None` if the member or the field don't exist.
+    pub async fn get_field<F: ProfileField>(&self, user_id: UserId) -> Result<Option<F>, Error> {
+        Ok(if let Some(mut profile) = self.load(List::Current, user_id).await? {
+            profile.peter.remove(F::KEY).map(serde_json::from_value).transpose()?
+        } else {
+            None
+        })
+    }
+
+    /// Sets or, if `value` is `None`, removes the given field in the member's profile. Returns `false` without changing anything if the user isn't on the member list.
+    pub async fn set_field<F: ProfileField>(&self, user_id: UserId, value: Option<&F>) -> Result<bool, Error> {
+        let _guard = self.write_lock().lock().await;
+        let mut profile = if let Some(profile) = self.load(List::Current, user_id).await? { profile } else { return Ok(false) };
+        if let Some(value) = value {
+            profile.peter.insert(F::KEY.to_owned(), serde_json::to_value(value)?);
+        } else {
+            profile.peter.remove(F::KEY);
+        }
+        self.save(List::Current, &profile).await?;
+        Ok(true)
+    }
+}
+
+/// Which of the two member lists to access.
+#[derive(Debug, Clone, Copy)]
+pub enum List {
+    /// The current members of the guild.
+    Current,
+    /// Members who left or were banned, kept for gefolge.org's historical event pages.
+    Former,
+}
+
+/// A storage backend for the list of Gefolge guild members.
+#[async_trait]
+pub trait Store: Send + Sync {
+    /// Returns the stored profile for the given user, if any.
+    async fn load(&self, list: List, user_id: UserId) -> Result<Option<Profile>, Error>;
+
+    /// Stores the given profile, replacing the previous version if there is one.
+    async fn save(&self, list: List, profile: &Profile) -> Result<(), Error>;
+
+    /// Deletes the stored profile for the given user. No-op if there is none.
+    async fn delete(&self, list: List, user_id: UserId) -> Result<(), Error>;
+
+    /// Returns all stored entries, identified by their file name or database key, along with the profile or the error encountered while parsing it.
+    async fn scan(&self, list: List) -> Result<Vec<(String, serde_json::Result<Profile>)>, Error>;
+
+    /// Moves an unparseable entry returned by `scan` out of the way, keeping its contents for manual inspection.
+    async fn quarantine(&self, list: List, key: &str) -> Result<(), Error>;
+
+    /// Held by the provided methods which load, modify, and save profiles, so that concurrent event handlers don't overwrite each other's changes.
+    fn write_lock(&self) -> &AsyncMutex<()>;
+
+    /// Returns all stored profiles. Fails if any of them can't be parsed.
+    async fn all(&self, list: List) -> Result<Vec<Profile>, Error> {
+        Ok(self.scan(list).await?.into_iter().map(|(_, profile)| profile).collect::<serde_json::Result<_>>()?)
+    }
+
+    /// Add a Discord account to the list of Gefolge guild members. If the account is already on the list, its role changes are recorded in the role history. If the account is a former member, their profile is restored.
+    async fn add(&self, member: Member, join_date: Option<DateTime<Utc>>) -> Result<(), Error> {
+        let _guard = self.write_lock().lock().await;
+        add_unlocked(self, member, join_date).await
+    }
+
+    /// Move a Discord account from the list of Gefolge guild members to the list of former members. Returns the join date if the account was on the list.
+    async fn remove(&self, user_id: UserId) -> Result<Option<DateTime<Utc>>, Error> {
+        let _guard = self.write_lock().lock().await;
+        remove_unlocked(self, user_id).await
+    }
+
+    /// Updates the account data which isn't specific to a guild, i.e. username, discriminator, and avatar, in the user's current and former profiles. Returns whether any profile changed.
+    ///
+    /// Discord doesn't send member updates for these changes in all cases, so this should be called with the user data from any event which includes it.
+    async fn update_user(&self, user: &User) -> Result<bool, Error> {
+        let _guard = self.write_lock().lock().await;
+        let mut changed = false;
+        for &list in &[List::Current, List::Former] {
+            if let Some(mut profile) = self.load(list, user.id).await? {
+                let avatar = user.avatar_url();
+                if profile.username != user.name || profile.discriminator != user.discriminator || profile.avatar != avatar {
+                    profile.username = user.name.clone();
+                    profile.discriminator = user.discriminator;
+                    profile.avatar = avatar;
+                    self.save(list, &profile).await?;
+                    changed = true;
+                }
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Returns the IDs and display names of all current members with the given role, sorted by display name.
+    async fn members_with_role(&self, role_id: RoleId) -> Result<Vec<(UserId, String)>, Error> {
+        let mut members = self.all(List::Current).await?.into_iter()
+            .filter(|profile| profile.roles.contains(&role_id))
+            .map(|profile| (profile.snowflake, profile.nick.unwrap_or(profile.username)))
+            .collect::<Vec<_>>();
+        members.sort_by(|(_, name1), (_, name2)| name1.to_lowercase().cmp(&name2.to_lowercase()));
+        Ok(members)
+    }
+
+    /// Returns the IDs of all members currently boosting the guild along with when they started, longest-boosting first.
+    async fn boosters(&self) -> Result<Vec<(UserId, DateTime<Utc>)>, Error> {
+        let mut boosters = self.all(List::Current).await?.into_iter()
+            .filter_map(|profile| profile.premium_since.map(|since| (profile.snowflake, since)))
+            .collect::<Vec<_>>();
+        boosters.sort_by_key(|&(_, since)| since);
+        Ok(boosters)
+    }
+
+    /// Returns the profiles of everyone who left the guild, with the time they left.
+    async fn former(&self) -> Result<Vec<Profile>, Error> {
+        self.all(List::Former).await
+    }
+
+    /// (Re)initialize the list of Gefolge guild members. `members` must contain all guild members, anyone else is moved to the former members.
+    ///
+    /// The stored profiles are read once and compared to the given members, and only new, changed, or removed profiles are written, so readers never see a partial list.
+    async fn set(&self, members: Vec<Member>) -> Result<Resync, Error> {
+        let _guard = self.write_lock().lock().await;
+        let mut resync = Resync::default();
+        let mut stale = self.all(List::Current).await?.into_iter().map(|profile| (profile.snowflake, profile)).collect::<HashMap<_, _>>();
+        for member in members { //TODO parallel?
+            let user_id = member.user.id;
+            if let Some(old) = stale.remove(&user_id) {
+                let old_json = serde_json::to_value(&old)?;
+                let new = Profile::new(member, None, Some(old));
+                if serde_json::to_value(&new)? != old_json {
+                    self.save(List::Current, &new).await?;
+                    resync.updated.push(user_id);
+                }
+            } else {
+                add_unlocked(self, member, None).await?;
+                resync.added.push(user_id);
+            }
+        }
+        for user_id in stale.into_keys() {
+            remove_unlocked(self, user_id).await?;
+            resync.removed.push(user_id);
+        }
+        Ok(resync)
+    }
+
+    /// Update the data for a guild member, e.g. after their roles changed. Equivalent to `add`, which keeps the join date.
+    async fn update(&self, member: Member) -> Result<(), Error> {
+        self.add(member, None).await
+    }
+}
+
+/// [`Store::add`] for callers which already hold the store's [`write_lock`](Store::write_lock).
+async fn add_unlocked<S: Store + ?Sized>(store: &S, member: Member, join_date: Option<DateTime<Utc>>) -> Result<(), Error> {
+    let user_id = member.user.id;
+    let (old, rejoined) = match store.load(List::Current, user_id).await? {
+        Some(old) => (Some(old), false),
+        None => match store.load(List::Former, user_id).await? {
+            Some(old) => (Some(old), true),
+            None => (None, false),
+        },
     };
-    /*
-    match fs::remove_file(format!("{}/{}.json", PROFILES_DIR, user.into())).await {
-        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
-        r => r
+    store.save(List::Current, &Profile::new(member, join_date, old)).await?;
+    if rejoined { store.delete(List::Former, user_id).await? }
+    Ok(())
+}
+
+/// [`Store::remove`] for callers which already hold the store's [`write_lock`](Store::write_lock).
+async fn remove_unlocked<S: Store + ?Sized>(store: &S, user_id: UserId) -> Result<Option<DateTime<Utc>>, Error> {
+    Ok(if let Some(mut profile) = store.load(List::Current, user_id).await? {
+        profile.left = Some(Utc::now());
+        if let Some(boost) = profile.boost_history.last_mut().filter(|boost| boost.end.is_none()) {
+            boost.end = profile.left; // leaving the guild ends the boost
+        }
+        store.save(List::Former, &profile).await?;
+        store.delete(List::Current, user_id).await?;
+        profile.joined
+    } else {
+        None
+    })
+}
+
+/// The corrections made by [`Store::set`].
+#[derive(Debug, Default)]
+pub struct Resync {
+    pub added: Vec<UserId>,
+    pub updated: Vec<UserId>,
+    pub removed: Vec<UserId>,
+}
+
+impl Resync {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl fmt::Display for Resync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} hinzugefügt, {} aktualisiert, {} entfernt", self.added.len(), self.updated.len(), self.removed.len())
+    }
+}
+
+/// The problems found by [`verify`].
+#[derive(Debug, Default)]
+pub struct Verification {
+    /// Users with a profile who are no longer in the guild.
+    pub orphaned: Vec<UserId>,
+    /// Guild members without a profile.
+    pub missing: Vec<UserId>,
+    /// Keys of stored entries which couldn't be parsed, along with the parse errors.
+    pub unparseable: Vec<(String, String)>,
+    /// Whether the problems have been fixed.
+    pub fixed: bool,
+}
+
+impl Verification {
+    pub fn is_empty(&self) -> bool {
+        self.orphaned.is_empty() && self.missing.is_empty() && self.unparseable.is_empty()
+    }
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() { return write!(f, "Mitgliederliste ist in Ordnung") }
+        write!(f, "{} verwaiste Profile, {} fehlende Profile, {} unlesbare Einträge", self.orphaned.len(), self.missing.len(), self.unparseable.len())?;
+        if self.fixed { write!(f, " (behoben)")? }
+        for user_id in &self.orphaned { write!(f, "\nverwaist: {}", user_id)? }
+        for user_id in &self.missing { write!(f, "\nfehlt: {}", user_id)? }
+        for (key, e) in &self.unparseable { write!(f, "\nunlesbar: `{}` ({})", key, e)? }
+        Ok(())
+    }
+}
+
+/// Compares the stored profiles of current members to the Gefolge guild's members and checks for entries which can't be parsed.
+///
+/// If `fix` is true, unparseable entries are quarantined (see [`Store::quarantine`]), orphaned profiles are moved to the former members, and missing profiles are created.
+pub async fn verify(ctx: &Context, fix: bool) -> Result<Verification, Error> {
+    let store = UserList::get(ctx).await;
+    let mut members = fetch_members(ctx, GEFOLGE).await?.into_iter().map(|member| (member.user.id, member)).collect::<HashMap<_, _>>();
+    let mut verification = Verification::default();
+    let mut stored = BTreeSet::default();
+    for (key, profile) in store.scan(List::Current).await? {
+        match profile {
+            Ok(profile) => { stored.insert(profile.snowflake); }
+            Err(e) => verification.unparseable.push((key, e.to_string())),
+        }
+    }
+    verification.orphaned = stored.iter().copied().filter(|user_id| !members.contains_key(user_id)).collect();
+    verification.missing = members.keys().copied().filter(|user_id| !stored.contains(user_id)).collect();
+    verification.missing.sort();
+    if fix {
+        for (key, _) in &verification.unparseable {
+            store.quarantine(List::Current, key).await?;
+        }
+        for &user_id in &verification.orphaned {
+            store.remove(user_id).await?;
+        }
+        for user_id in &verification.missing {
+            let member = members.remove(user_id).expect("missing member not in guild");
+            store.add(backfill_join_date(ctx, member).await?, None).await?;
+        }
+        verification.fixed = true;
     }
-    */
-    Ok(join_date)
+    Ok(verification)
 }
 
-/// (Re)initialize the list of Gefolge guild members.
-pub async fn set<I: IntoIterator<Item=Member>>(members: I) -> Result<(), Error> {
-    /*
-    let mut read_dir = fs::read_dir(PROFILES_DIR).await?;
-    while let Some(entry) = read_dir.try_next().await? {
-        fs::remove_file(entry?.path()).await?;
+/// Fills in the member's join date via the REST API if the gateway didn't include it, so profiles don't end up without a join date.
+pub async fn backfill_join_date(ctx: &Context, mut member: Member) -> Result<Member, Error> {
+    if member.joined_at.is_none() {
+        member.joined_at = ctx.http.get_member(member.guild_id.0, member.user.id.0).await?.joined_at;
     }
-    */
-    for member in members.into_iter() { //TODO parallel?
-        add(member, None).await?;
+    Ok(member)
+}
+
+/// Fetches all members of the given guild via the REST API, since the gateway only sends them on connect.
+async fn fetch_members(ctx: &Context, guild_id: GuildId) -> serenity::Result<Vec<Member>> {
+    let mut members = Vec::<Member>::default();
+    loop {
+        let chunk = guild_id.members(ctx, Some(1000), members.last().map(|member| member.user.id)).await?;
+        let done = chunk.len() < 1000;
+        members.extend(chunk);
+        if done { return Ok(members) }
     }
+}
+
+/// Compares the member list to the Gefolge guild's members at the configured interval, and corrects any differences caused by missed events. If a comparison fails, it is retried after the next interval.
+pub async fn resync(ctx_fut: RwFuture<Context>) -> Never {
+    loop {
+        let interval = {
+            let ctx = ctx_fut.read().await;
+            let hours = crate::config::Config::read(&*ctx).await.user_list.resync_interval_hours;
+            StdDuration::from_secs(hours.get() * 60 * 60)
+        };
+        sleep(interval).await;
+        let result = async {
+            let ctx = ctx_fut.read().await;
+            let members = fetch_members(&*ctx, GEFOLGE).await?;
+            let resync = UserList::get(&*ctx).await.set(members).await?;
+            if !resync.is_empty() {
+                println!("{}: member list resynced: {:?}", Utc::now().format("%Y-%m-%d %H:%M:%S"), resync);
+                log_channel::post(&*ctx, GEFOLGE, format!("Mitgliederliste abgeglichen: {}", resync)).await?;
+            }
+            Ok::<_, Error>(())
+        }.await;
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            crate::notify_thread_crash(ctx_fut.clone(), format!("member list"), e, Some(interval)).await;
+        }
+    }
+}
+
+/// The file formats supported by [`export`].
+#[derive(Debug, Clone, Copy)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl FromStr for ExportFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<ExportFormat, ()> {
+        match &*s.to_ascii_lowercase() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// One row of an [`export`].
+#[derive(Serialize)]
+struct ExportRow {
+    id: UserId,
+    name: String,
+    nick: Option<String>,
+    joined: Option<DateTime<Utc>>,
+    roles: Vec<String>,
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Returns a document listing all current members of the Gefolge guild, with their roles resolved to names, sorted by username.
+pub async fn export(ctx: &Context, format: ExportFormat) -> Result<String, Error> {
+    let roles = GEFOLGE.roles(ctx).await?;
+    let mut profiles = UserList::get(ctx).await.all(List::Current).await?;
+    profiles.sort_by(|p1, p2| (&p1.username, p1.discriminator).cmp(&(&p2.username, p2.discriminator)));
+    let rows = profiles.into_iter().map(|profile| ExportRow {
+        id: profile.snowflake,
+        name: format!("{}#{:04}", profile.username, profile.discriminator),
+        nick: profile.nick,
+        joined: profile.joined,
+        roles: profile.roles.iter().filter_map(|role_id| roles.get(role_id)).map(|role| role.name.clone()).collect(),
+    });
+    Ok(match format {
+        ExportFormat::Csv => {
+            let mut csv = format!("id,name,nick,joined,roles\r\n");
+            for row in rows {
+                csv.push_str(&format!("{},{},{},{},{}\r\n",
+                    row.id,
+                    csv_field(&row.name),
+                    csv_field(row.nick.as_deref().unwrap_or_default()),
+                    row.joined.map(|joined| joined.to_rfc3339()).unwrap_or_default(),
+                    csv_field(&row.roles.join(", ")),
+                ));
+            }
+            csv
+        }
+        ExportFormat::Json => serde_json::to_string_pretty(&rows.collect::<Vec<_>>())?,
+    })
+}
+
+/// Sends a list of all server members as a CSV (default) or JSON file, e.g. for event planning.
+#[command]
+#[usage = "[csv|json]"]
+#[only_in(guilds)]
+#[required_permissions(ADMINISTRATOR)]
+#[sub_commands(members_verify)]
+pub async fn members(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    if msg.guild_id != Some(GEFOLGE) {
+        msg.reply(ctx, "die Mitgliederliste gibt es nur für das Gefolge").await?;
+        return Ok(())
+    }
+    let format = match args.rest().trim() {
+        "" => ExportFormat::Csv,
+        arg => if let Ok(format) = arg.parse() { format } else {
+            msg.reply(ctx, "unbekanntes Format, benutze `csv` oder `json`").await?;
+            return Ok(())
+        },
+    };
+    let doc = export(ctx, format).await?;
+    msg.channel_id.send_files(ctx, vec![(doc.as_bytes(), &*format!("mitglieder.{}", format.extension()))], |m| m).await?;
+    Ok(())
+}
+
+/// Checks the member list for orphaned, missing, and unparseable profiles. Use `!members verify fix` to also fix them.
+#[command("verify")]
+#[usage = "[fix]"]
+#[only_in(guilds)]
+#[required_permissions(ADMINISTRATOR)]
+async fn members_verify(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    if msg.guild_id != Some(GEFOLGE) {
+        msg.reply(ctx, "die Mitgliederliste gibt es nur für das Gefolge").await?;
+        return Ok(())
+    }
+    let fix = match args.rest().trim() {
+        "" => false,
+        "fix" => true,
+        _ => {
+            msg.reply(ctx, "unbekanntes Argument, benutze `fix` um die Probleme zu beheben").await?;
+            return Ok(())
+        }
+    };
+    let verification = verify(ctx, fix).await?;
+    msg.channel_id.say(ctx, verification).await?;
     Ok(())
 }
 
-/// Update the data for a guild member. Equivalent to `remove` followed by `add`.
-pub async fn update(member: Member) -> Result<(), Error> {
-    let join_date = remove(&member).await?;
-    add(member, join_date).await?;
+/// `typemap` key for the member list store selected in the config.
+pub struct UserList;
+
+impl TypeMapKey for UserList {
+    type Value = Arc<dyn Store>;
+}
+
+impl UserList {
+    /// Opens the store selected in the config.
+    pub fn open(config: &Config, paths: &Paths) -> Result<Arc<dyn Store>, Error> {
+        let store = match config.backend {
+            Backend::Json => Arc::new(JsonDir(paths.profiles.clone(), AsyncMutex::default())) as Arc<dyn Store>,
+            Backend::Sqlite => Arc::new(Sqlite::open(&paths.profiles_db())?),
+        };
+        Ok(if let Some(ref webhook) = config.webhook {
+            Arc::new(Notifying {
+                inner: store,
+                client: reqwest::Client::new(),
+                config: webhook.clone(),
+            })
+        } else {
+            store
+        })
+    }
+
+    /// Returns the store from the `typemap`.
+    pub async fn get(ctx: &Context) -> Arc<dyn Store> {
+        Arc::clone(ctx.data.read().await.get::<UserList>().expect("missing user list"))
+    }
+}
+
+/// The kind of change to the member list, see [`Notification`].
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum Change {
+    Add,
+    Update,
+    Remove,
+}
+
+/// The body of a member list change notification.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Notification {
+    change: Change,
+    user_id: UserId,
+}
+
+/// Wraps another store and notifies gefolge.org after each `add`, `update`, and `remove`, so the website can invalidate its caches immediately instead of polling the profiles.
+struct Notifying {
+    inner: Arc<dyn Store>,
+    client: reqwest::Client,
+    config: WebhookConfig,
+}
+
+impl Notifying {
+    /// Sends the notification in the background so an unreachable website doesn't hold up event handling. Failures are only logged since the website still sees the change on its next cache refresh.
+    fn notify(&self, change: Change, user_id: UserId) {
+        let client = self.client.clone();
+        let config = self.config.clone();
+        tokio::spawn(async move {
+            if let Err(e) = send_notification(&client, &config, Notification { change, user_id }).await {
+                eprintln!("failed to notify gefolge.org of member list change: {} ({:?})", e, e);
+            }
+        });
+    }
+}
+
+async fn send_notification(client: &reqwest::Client, config: &WebhookConfig, notification: Notification) -> Result<(), Error> {
+    let body = serde_json::to_vec(&notification)?;
+    let delivery_id = format!("{:016x}", thread_rng().gen::<u64>());
+    let timestamp = Utc::now();
+    let signature = webhook::sign(config.secret.as_bytes(), timestamp, &body);
+    client.post(&config.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header("X-Peter-Delivery", delivery_id)
+        .header("X-Peter-Timestamp", timestamp.timestamp().to_string())
+        .header("X-Peter-Signature", signature)
+        .body(body)
+        .send().await?
+        .error_for_status()?;
     Ok(())
 }
+
+#[async_trait]
+impl Store for Notifying {
+    async fn load(&self, list: List, user_id: UserId) -> Result<Option<Profile>, Error> { self.inner.load(list, user_id).await }
+    async fn save(&self, list: List, profile: &Profile) -> Result<(), Error> { self.inner.save(list, profile).await }
+    async fn delete(&self, list: List, user_id: UserId) -> Result<(), Error> { self.inner.delete(list, user_id).await }
+    async fn scan(&self, list: List) -> Result<Vec<(String, serde_json::Result<Profile>)>, Error> { self.inner.scan(list).await }
+    async fn quarantine(&self, list: List, key: &str) -> Result<(), Error> { self.inner.quarantine(list, key).await }
+    fn write_lock(&self) -> &AsyncMutex<()> { self.inner.write_lock() }
+
+    async fn add(&self, member: Member, join_date: Option<DateTime<Utc>>) -> Result<(), Error> {
+        let user_id = member.user.id;
+        self.inner.add(member, join_date).await?;
+        self.notify(Change::Add, user_id);
+        Ok(())
+    }
+
+    async fn remove(&self, user_id: UserId) -> Result<Option<DateTime<Utc>>, Error> {
+        let joined = self.inner.remove(user_id).await?;
+        self.notify(Change::Remove, user_id);
+        Ok(joined)
+    }
+
+    async fn set(&self, members: Vec<Member>) -> Result<Resync, Error> {
+        let resync = self.inner.set(members).await?;
+        for &user_id in &resync.added { self.notify(Change::Add, user_id) }
+        for &user_id in &resync.updated { self.notify(Change::Update, user_id) }
+        for &user_id in &resync.removed { self.notify(Change::Remove, user_id) }
+        Ok(resync)
+    }
+
+    async fn update(&self, member: Member) -> Result<(), Error> {
+        let user_id = member.user.id;
+        self.inner.update(member).await?;
+        self.notify(Change::Update, user_id);
+        Ok(())
+    }
+
+    async fn update_user(&self, user: &User) -> Result<bool, Error> {
+        let changed = self.inner.update_user(user).await?;
+        if changed { self.notify(Change::Update, user.id) }
+        Ok(changed)
+    }
+}
+
+/// The member list as a directory with one JSON file per member, named after their user ID.
+///
+/// Access to each directory is coordinated with other processes like gefolge.org using an advisory `flock` on a `.lock` file in the directory: shared for reading, exclusive for writing. See [`read_profile`] for a reader which follows this protocol.
+pub struct JsonDir(PathBuf, AsyncMutex<()>);
+
+/// The name of the lock file in a profiles directory, see [`JsonDir`].
+const LOCK_FILE: &str = ".lock";
+
+/// Takes an advisory lock on the given profiles directory, which is released when the returned file is dropped. The directory is created if it doesn't exist.
+fn lock_dir(dir: &Path, exclusive: bool) -> io::Result<std::fs::File> {
+    std::fs::create_dir_all(dir)?;
+    let f = std::fs::OpenOptions::new().create(true).write(true).open(dir.join(LOCK_FILE))?;
+    if exclusive { f.lock_exclusive()? } else { f.lock_shared()? }
+    Ok(f)
+}
+
+/// Reads a profile from the given profiles directory as JSON, holding a shared lock while reading. Returns `None` if there is no profile for the given user.
+///
+/// This is blocking and doesn't need a running bot, so it can be used by other processes which read the profiles.
+pub fn read_profile(dir: &Path, user_id: UserId) -> Result<Option<serde_json::Value>, Error> {
+    let _lock = lock_dir(dir, false)?;
+    match std::fs::read(dir.join(format!("{}.json", user_id))) {
+        Ok(buf) => Ok(Some(serde_json::from_slice(&buf)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+impl JsonDir {
+    /// Former members are stored in the `former` subdirectory.
+    fn dir(&self, list: List) -> PathBuf {
+        match list {
+            List::Current => self.0.clone(),
+            List::Former => self.0.join("former"),
+        }
+    }
+
+    fn profile_path(&self, list: List, user_id: UserId) -> PathBuf {
+        self.dir(list).join(format!("{}.json", user_id))
+    }
+}
+
+#[async_trait]
+impl Store for JsonDir {
+    async fn load(&self, list: List, user_id: UserId) -> Result<Option<Profile>, Error> {
+        let _lock = block_in_place(|| lock_dir(&self.dir(list), false))?;
+        match File::open(self.profile_path(list, user_id)).await {
+            Ok(mut f) => {
+                let mut buf = Vec::default();
+                f.read_to_end(&mut buf).await?;
+                Ok(Some(serde_json::from_slice(&buf)?))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn save(&self, list: List, profile: &Profile) -> Result<(), Error> {
+        let buf = serde_json::to_vec_pretty(profile)?;
+        let _lock = block_in_place(|| lock_dir(&self.dir(list), true))?;
+        atomic_file::write(&self.profile_path(list, profile.snowflake), &buf).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, list: List, user_id: UserId) -> Result<(), Error> {
+        let _lock = block_in_place(|| lock_dir(&self.dir(list), true))?;
+        match fs::remove_file(self.profile_path(list, user_id)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn scan(&self, list: List) -> Result<Vec<(String, serde_json::Result<Profile>)>, Error> {
+        let mut entries = Vec::default();
+        let _lock = block_in_place(|| lock_dir(&self.dir(list), false))?;
+        let mut read_dir = fs::read_dir(self.dir(list)).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let path = entry.path();
+            // skip subdirectories, temporary files, and quarantined files
+            if path.extension().map_or(true, |ext| ext != "json") || path.file_name().map_or(true, |name| name.to_string_lossy().starts_with('.')) { continue }
+            let key = entry.file_name().to_string_lossy().into_owned();
+            entries.push((key, serde_json::from_slice(&fs::read(path).await?)));
+        }
+        Ok(entries)
+    }
+
+    /// Renames the file to `<key>.invalid`.
+    async fn quarantine(&self, list: List, key: &str) -> Result<(), Error> {
+        let _lock = block_in_place(|| lock_dir(&self.dir(list), true))?;
+        let path = self.dir(list).join(key);
+        fs::rename(&path, path.with_extension("json.invalid")).await?;
+        Ok(())
+    }
+
+    fn write_lock(&self) -> &AsyncMutex<()> { &self.1 }
+}
+
+/// The member list as an SQLite database with `profiles` and `former_profiles` tables, which store each member's profile in the same JSON format as [`JsonDir`].
+pub struct Sqlite(Mutex<Connection>, AsyncMutex<()>);
+
+impl Sqlite {
+    fn open(path: &Path) -> rusqlite::Result<Sqlite> {
+        let conn = Connection::open(path)?;
+        for list in &[List::Current, List::Former] {
+            conn.execute(&format!("CREATE TABLE IF NOT EXISTS {} (snowflake INTEGER PRIMARY KEY, profile TEXT NOT NULL)", Sqlite::table(*list)), [])?;
+        }
+        conn.execute("CREATE TABLE IF NOT EXISTS invalid_profiles (list TEXT NOT NULL, snowflake INTEGER NOT NULL, profile TEXT NOT NULL)", [])?;
+        Ok(Sqlite(Mutex::new(conn), AsyncMutex::default()))
+    }
+
+    fn table(list: List) -> &'static str {
+        match list {
+            List::Current => "profiles",
+            List::Former => "former_profiles",
+        }
+    }
+}
+
+#[async_trait]
+impl Store for Sqlite {
+    async fn load(&self, list: List, user_id: UserId) -> Result<Option<Profile>, Error> {
+        let profile = block_in_place(|| self.0.lock().expect("SQLite connection poisoned").query_row(
+            &format!("SELECT profile FROM {} WHERE snowflake = ?1", Sqlite::table(list)),
+            params![user_id.0 as i64],
+            |row| row.get::<_, String>(0),
+        ).optional())?;
+        Ok(if let Some(profile) = profile { Some(serde_json::from_str(&profile)?) } else { None })
+    }
+
+    async fn save(&self, list: List, profile: &Profile) -> Result<(), Error> {
+        let json = serde_json::to_string(profile)?;
+        block_in_place(|| self.0.lock().expect("SQLite connection poisoned").execute(
+            &format!("INSERT INTO {} (snowflake, profile) VALUES (?1, ?2) ON CONFLICT (snowflake) DO UPDATE SET profile = excluded.profile", Sqlite::table(list)),
+            params![profile.snowflake.0 as i64, json],
+        ))?;
+        Ok(())
+    }
+
+    async fn delete(&self, list: List, user_id: UserId) -> Result<(), Error> {
+        block_in_place(|| self.0.lock().expect("SQLite connection poisoned").execute(
+            &format!("DELETE FROM {} WHERE snowflake = ?1", Sqlite::table(list)),
+            params![user_id.0 as i64],
+        ))?;
+        Ok(())
+    }
+
+    async fn scan(&self, list: List) -> Result<Vec<(String, serde_json::Result<Profile>)>, Error> {
+        let rows = block_in_place(|| {
+            let conn = self.0.lock().expect("SQLite connection poisoned");
+            let mut stmt = conn.prepare(&format!("SELECT snowflake, profile FROM {}", Sqlite::table(list)))?;
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()
+        })?;
+        Ok(rows.into_iter().map(|(snowflake, profile)| ((snowflake as u64).to_string(), serde_json::from_str(&profile))).collect())
+    }
+
+    /// Moves the row to the `invalid_profiles` table.
+    async fn quarantine(&self, list: List, key: &str) -> Result<(), Error> {
+        let snowflake = key.parse::<i64>().expect("SQLite profile keys are snowflakes");
+        block_in_place(|| {
+            let mut conn = self.0.lock().expect("SQLite connection poisoned");
+            let transaction = conn.transaction()?;
+            transaction.execute(&format!("INSERT INTO invalid_profiles (list, snowflake, profile) SELECT ?1, snowflake, profile FROM {} WHERE snowflake = ?2", Sqlite::table(list)), params![Sqlite::table(list), snowflake])?;
+            transaction.execute(&format!("DELETE FROM {} WHERE snowflake = ?1", Sqlite::table(list)), params![snowflake])?;
+            transaction.commit()
+        })?;
+        Ok(())
+    }
+
+    fn write_lock(&self) -> &AsyncMutex<()> { &self.1 }
+}
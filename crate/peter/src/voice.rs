@@ -17,6 +17,7 @@ use {
         fs::File,
         io::AsyncWriteExt as _,
     },
+    crate::paths::Paths,
 };
 
 /// `typemap` key for the voice state data required by the gefolge.org API: A mapping of voice channel names to users.
@@ -28,8 +29,8 @@ impl TypeMapKey for VoiceStates {
 }
 
 /// Takes a mapping from voice channel names to users and dumps the output for the gefolge.org API.
-pub async fn dump_info(VoiceStates(voice_states): &VoiceStates) -> io::Result<()> {
-    let mut f = File::create("/usr/local/share/fidera/discord/voice-state.json").await?;
+pub async fn dump_info(paths: &Paths, VoiceStates(voice_states): &VoiceStates) -> io::Result<()> {
+    let mut f = File::create(paths.voice_state()).await?;
     let buf = serde_json::to_vec(&json!({
         "channels": voice_states.into_iter()
             .map(|(channel_id, (channel_name, members))| json!({
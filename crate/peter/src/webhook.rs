@@ -0,0 +1,269 @@
+//! A shared receiver for incoming webhooks (e.g. Twitch EventSub, gefolge.org callbacks), with HMAC signature verification and replay protection.
+
+use {
+    std::{
+        collections::{
+            BTreeMap,
+            HashMap,
+        },
+        convert::Infallible as Never,
+        net::{
+            Ipv4Addr,
+            SocketAddr,
+        },
+        pin::Pin,
+        sync::Arc,
+    },
+    chrono::{
+        Duration,
+        prelude::*,
+    },
+    futures::future::Future,
+    hmac::{
+        Hmac,
+        Mac as _,
+        NewMac as _,
+    },
+    hyper::{
+        Body,
+        Request,
+        Response,
+        Server,
+        StatusCode,
+        body::HttpBody as _,
+        service::{
+            make_service_fn,
+            service_fn,
+        },
+    },
+    schemars::JsonSchema,
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    serenity::{
+        model::prelude::*,
+        prelude::*,
+    },
+    serenity_utils::RwFuture,
+    sha2::Sha256,
+    crate::Error,
+};
+
+/// Deliveries with a timestamp further than this from the current time are rejected.
+fn max_age() -> Duration { Duration::minutes(10) }
+
+/// Request bodies larger than this many bytes are rejected without being read completely.
+const MAX_BODY_LEN: usize = 1024 * 1024;
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    /// The port on localhost to listen on. Webhooks are expected to be forwarded by a reverse proxy.
+    port: u16,
+    /// Maps source names, which are also the URL paths webhooks are sent to, to their configuration.
+    #[serde(default)]
+    sources: BTreeMap<String, SourceConfig>,
+}
+
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct SourceConfig {
+    secret: String,
+    scheme: Scheme,
+}
+
+/// How a webhook source transmits the delivery ID, timestamp, and signature.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Scheme {
+    /// Peter's own scheme, also used for outbound webhooks: The headers `X-Peter-Delivery`, `X-Peter-Timestamp` (Unix seconds), and `X-Peter-Signature` (see [`sign`]).
+    Peter,
+    /// [Twitch EventSub](https://dev.twitch.tv/docs/eventsub/handling-webhook-events).
+    TwitchEventSub,
+}
+
+/// A webhook delivery whose signature has been verified.
+pub struct Delivery {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    /// The value of the `Twitch-Eventsub-Message-Type` header for Twitch EventSub deliveries.
+    pub kind: Option<String>,
+    pub body: Vec<u8>,
+}
+
+/// A handler for deliveries from a source. Returns the body of the response.
+type Handler = fn(Context, Delivery) -> Pin<Box<dyn Future<Output = Result<String, Error>> + Send>>;
+
+/// Returns the handler responsible for the given webhook source.
+fn route(source: &str) -> Option<Handler> {
+    match source {
+        "gefolge" => Some(handle_gefolge as Handler),
+        "twitch" => Some(handle_twitch as Handler),
+        _ => None,
+    }
+}
+
+/// Computes the signature header value for an outbound webhook using the [`Peter`](Scheme::Peter) scheme.
+///
+/// The signature is `sha256=` followed by the hex-encoded HMAC-SHA256 of the timestamp, a period, and the body.
+pub fn sign(secret: &[u8], timestamp: DateTime<Utc>, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.timestamp().to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Checks a `sha256=…` signature of the concatenation of the given parts in constant time.
+fn verify(secret: &[u8], parts: &[&[u8]], signature: &str) -> bool {
+    let tag = if let Some(tag) = signature.strip_prefix("sha256=").and_then(|hex_tag| hex::decode(hex_tag).ok()) { tag } else { return false };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.verify(&tag).is_ok()
+}
+
+struct Receiver {
+    ctx_fut: RwFuture<Context>,
+    /// IDs and signed timestamps of recently received deliveries, for replay protection.
+    seen: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl Receiver {
+    async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, Error> {
+        let source = req.uri().path().trim_matches('/').to_owned();
+        let ctx = (*self.ctx_fut.read().await).clone();
+        let (source_config, handler) = {
+            let data = ctx.data.read().await;
+            let config = data.get::<crate::config::Config>().ok_or(Error::MissingConfig)?.read().await;
+            match (config.webhooks.as_ref().and_then(|webhooks| webhooks.sources.get(&source)), route(&source)) {
+                (Some(source_config), Some(handler)) => (source_config.clone(), handler),
+                (_, _) => return Ok(status(StatusCode::NOT_FOUND)),
+            }
+        };
+        let header = |name: &str| req.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_owned);
+        let (id, timestamp_text, signature, kind) = match source_config.scheme {
+            Scheme::Peter => (header("X-Peter-Delivery"), header("X-Peter-Timestamp"), header("X-Peter-Signature"), None),
+            Scheme::TwitchEventSub => (header("Twitch-Eventsub-Message-Id"), header("Twitch-Eventsub-Message-Timestamp"), header("Twitch-Eventsub-Message-Signature"), header("Twitch-Eventsub-Message-Type")),
+        };
+        let (id, timestamp_text, signature) = match (id, timestamp_text, signature) {
+            (Some(id), Some(timestamp_text), Some(signature)) => (id, timestamp_text, signature),
+            (_, _, _) => return Ok(status(StatusCode::BAD_REQUEST)),
+        };
+        let timestamp = match source_config.scheme {
+            Scheme::Peter => timestamp_text.parse::<i64>().ok().and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single()),
+            Scheme::TwitchEventSub => DateTime::parse_from_rfc3339(&timestamp_text).ok().map(|timestamp| timestamp.with_timezone(&Utc)),
+        };
+        let timestamp = if let Some(timestamp) = timestamp { timestamp } else { return Ok(status(StatusCode::BAD_REQUEST)) };
+        let body = if let Some(body) = read_body(req.into_body()).await? { body } else { return Ok(status(StatusCode::PAYLOAD_TOO_LARGE)) };
+        // the signature covers the timestamp exactly as sent
+        let verified = match source_config.scheme {
+            Scheme::Peter => verify(source_config.secret.as_bytes(), &[timestamp_text.as_bytes(), b".", &body], &signature),
+            Scheme::TwitchEventSub => verify(source_config.secret.as_bytes(), &[id.as_bytes(), timestamp_text.as_bytes(), &body], &signature),
+        };
+        if !verified { return Ok(status(StatusCode::FORBIDDEN)) }
+        // replay protection
+        let now = Utc::now();
+        if timestamp < now - max_age() || timestamp > now + max_age() { return Ok(status(StatusCode::FORBIDDEN)) }
+        {
+            let mut seen = self.seen.lock().await;
+            // deliveries signed before this were rejected above, so their IDs no longer need to be remembered
+            seen.retain(|_, &mut signed| signed >= now - max_age());
+            if seen.insert(id.clone(), timestamp).is_some() {
+                return Ok(status(StatusCode::NO_CONTENT)) // already handled, but a 2xx status tells the sender to stop retrying
+            }
+        }
+        match handler(ctx, Delivery { id: id.clone(), timestamp, kind, body }).await {
+            Ok(response) => Ok(Response::new(Body::from(response))),
+            Err(e) => {
+                // the sender will retry after the error response, which must not be mistaken for a replay
+                self.seen.lock().await.remove(&id);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Reads a request body, or returns `None` if it's longer than [`MAX_BODY_LEN`].
+async fn read_body(mut body: Body) -> Result<Option<Vec<u8>>, hyper::Error> {
+    if body.size_hint().lower() > MAX_BODY_LEN as u64 { return Ok(None) }
+    let mut buf = Vec::default();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if buf.len() + chunk.len() > MAX_BODY_LEN { return Ok(None) }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(Some(buf))
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+/// Listens for webhooks on the port specified in the config. Does nothing if webhooks are not configured.
+pub async fn listen(ctx_fut: RwFuture<Context>) -> Result<(), Error> {
+    let port = {
+        let ctx = ctx_fut.read().await;
+        let data = ctx.data.read().await;
+        if let Some(webhooks) = &data.get::<crate::config::Config>().ok_or(Error::MissingConfig)?.read().await.webhooks { webhooks.port } else { return Ok(()) }
+    };
+    let receiver = Arc::new(Receiver {
+        ctx_fut,
+        seen: Mutex::default(),
+    });
+    Server::bind(&SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+        .serve(make_service_fn(move |_| {
+            let receiver = Arc::clone(&receiver);
+            async move {
+                Ok::<_, Never>(service_fn(move |req| {
+                    let receiver = Arc::clone(&receiver);
+                    async move {
+                        Ok::<_, Never>(match receiver.handle(req).await {
+                            Ok(response) => response,
+                            Err(e) => {
+                                eprintln!("error handling webhook: {} ({:?})", e, e);
+                                status(StatusCode::INTERNAL_SERVER_ERROR)
+                            }
+                        })
+                    }
+                }))
+            }
+        }))
+        .await?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChannelMsg {
+    channel: ChannelId,
+    msg: String,
+}
+
+/// gefolge.org callbacks: Sends the given message, unescaped, to the given channel, like the `channel-msg` IPC command.
+fn handle_gefolge(ctx: Context, delivery: Delivery) -> Pin<Box<dyn Future<Output = Result<String, Error>> + Send>> {
+    Box::pin(async move {
+        let ChannelMsg { channel, msg } = serde_json::from_slice(&delivery.body)?;
+        channel.say(&ctx, msg).await?;
+        Ok(String::default())
+    })
+}
+
+#[derive(Deserialize)]
+struct EventSubChallenge {
+    challenge: String,
+}
+
+/// Twitch EventSub: Answers subscription verification challenges.
+fn handle_twitch(_: Context, delivery: Delivery) -> Pin<Box<dyn Future<Output = Result<String, Error>> + Send>> {
+    Box::pin(async move {
+        Ok(match delivery.kind.as_deref() {
+            Some("webhook_callback_verification") => serde_json::from_slice::<EventSubChallenge>(&delivery.body)?.challenge,
+            _ => String::default(), //TODO replace polling in `twitch::alerts` with `stream.online` notifications
+        })
+    })
+}
@@ -4,19 +4,32 @@
 
 use {
     std::{
-        cmp::Ordering,
+        borrow::Cow,
+        cmp::Reverse,
         collections::{
             HashMap,
             HashSet,
         },
+        fmt,
+        io,
         iter,
         mem,
+        path::PathBuf,
         pin::Pin,
         str,
         time::Duration,
     },
+    async_trait::async_trait,
+    chrono::{
+        DateTime,
+        Local,
+        Utc,
+    },
     futures::{
-        future::Future,
+        future::{
+            self,
+            Future,
+        },
         stream::{
             self,
             StreamExt as _,
@@ -24,21 +37,34 @@ use {
         },
     },
     itertools::Itertools as _,
+    once_cell::sync::Lazy,
     quantum_werewolf::game::{
+        Faction,
         NightAction,
         NightActionResult,
         Role,
         state::*,
     },
     rand::{
-        Rng as _,
+        Rng,
+        SeedableRng as _,
+        rngs::StdRng,
+        seq::SliceRandom as _,
         thread_rng,
     },
+    regex::Regex,
+    schemars::JsonSchema,
+    serenity_utils::RwFuture,
     serde::{
         Deserialize,
         Serialize,
     },
+    sha2::{
+        Digest as _,
+        Sha256,
+    },
     serenity::{
+        builder::CreateEmbed,
         framework::standard::{
             Args,
             CommandOptions,
@@ -47,30 +73,479 @@ use {
             macros::{
                 check,
                 command,
+                group,
             },
         },
+        http::AttachmentType,
         model::prelude::*,
         prelude::*,
         utils::MessageBuilder,
     },
-    tokio::time::sleep,
+    tokio::{
+        fs::{
+            self,
+            File,
+        },
+        io::AsyncWriteExt as _,
+        time::sleep,
+    },
     crate::{
         Error,
+        atomic_file,
+        games::{
+            self,
+            GameId,
+            HiddenRoleGame,
+            Timeouts,
+            delegation_chain,
+            vote_counts,
+            vote_leads,
+        },
         lang::*,
+        log_channel,
         parse,
+        paths::Paths,
+        setup::{
+            log_changes,
+            send_json,
+            set_key,
+        },
+        reaction_listeners::{
+            self,
+            Filter,
+            ReactionListeners,
+        },
+        tournament::{
+            self,
+            SEASON_COMMAND,
+            Scoring,
+        },
         voice::VoiceStates,
+        werewolf_history::{
+            self,
+            Death,
+            WWSTATS_COMMAND,
+        },
+        werewolf_rating::WWRANK_COMMAND,
     },
 };
 
-#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+/// Time to allow players to sign up after the first player joins.
+fn default_signup_secs() -> u64 { 600 }
+fn default_min_players() -> usize { MIN_PLAYERS }
+/// 3 minute night time limit works for XylBot, may need to be adjusted up or down. Collect stats?
+fn default_night_secs() -> u64 { 180 }
+/// Not sure how long the day limit should be. Starting out with half an hour for now to be safe. Collect stats?
+fn default_day_secs() -> u64 { 1800 }
+fn default_reminder_secs() -> u64 { 300 }
+fn default_runoff_secs() -> u64 { 300 }
+fn default_no_lynch() -> bool { true }
+fn default_reveal_roles() -> bool { true }
+fn default_last_words_secs() -> u64 { 60 }
+
+/// The reactions for voting for the living players on the vote message, in the order of the players. Regional indicators are used because Discord doesn't require a variation selector for them, unlike keycaps.
+const VOTE_EMOJI: [&str; 19] = ["🇦", "🇧", "🇨", "🇩", "🇪", "🇫", "🇬", "🇭", "🇮", "🇯", "🇰", "🇱", "🇲", "🇳", "🇴", "🇵", "🇶", "🇷", "🇸"]; // Discord allows 20 different reactions per message, one is needed for no lynch
+const NO_LYNCH_EMOJI: &str = "❌";
+/// The reaction for signing up on the signup embed of a rematch, see `wwrematch`.
+const REMATCH_EMOJI: &str = "✅";
+
+/// How a day vote is resolved if multiple players (or a player and no lynch) are tied for the most votes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum TieBreak {
+    /// Nobody is lynched.
+    NoLynch,
+    /// One of the tied options is chosen at random.
+    Random,
+    /// The vote is repeated with only the tied options, for [`Config::runoff_secs`]. If that is tied again, nobody is lynched.
+    Runoff,
+}
+
+impl Default for TieBreak {
+    fn default() -> TieBreak {
+        TieBreak::NoLynch
+    }
+}
+
+/// The phases of a game in a channel. The quantum state decides what happens within a phase, this decides which phase comes next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Phase {
+    /// No game is running, players can sign up for the next one.
+    Signups,
+    /// The given night of the running game, counting from 1.
+    Night(usize),
+    /// The given day of the running game, counting from 1. Day `n` follows night `n`.
+    Day(usize),
+    /// The game has been won, and the results are being announced.
+    Ended,
+}
+
+/// The events that move a game from one [`Phase`] to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    /// Signups closed with enough players and the roles were distributed.
+    Start,
+    /// A night was resolved without ending the game.
+    Dawn,
+    /// A day was resolved without ending the game.
+    Dusk,
+    /// A win condition was reached at the end of a day or night.
+    End,
+    /// Signups open again, either after the game ended or because it was cancelled.
+    Reset,
+}
+
+impl Phase {
+    /// The phase after the given transition, or `None` if the transition isn't possible in this phase.
+    pub fn next(self, transition: Transition) -> Option<Phase> {
+        match (self, transition) {
+            (Phase::Signups, Transition::Start) => Some(Phase::Night(1)),
+            (Phase::Night(n), Transition::Dawn) => Some(Phase::Day(n)),
+            (Phase::Day(n), Transition::Dusk) => Some(Phase::Night(n + 1)),
+            (Phase::Night(_), Transition::End) | (Phase::Day(_), Transition::End) => Some(Phase::Ended),
+            (_, Transition::Reset) => Some(Phase::Signups),
+            _ => None,
+        }
+    }
+
+    /// The number of the current day or night, if a game is running.
+    pub fn number(self) -> Option<usize> {
+        match self {
+            Phase::Night(n) | Phase::Day(n) => Some(n),
+            Phase::Signups | Phase::Ended => None,
+        }
+    }
+
+    /// The phase reached when the quantum state of a game in this phase changes to the given state, or `None` if that change isn't possible in this phase. Staying in the same kind of state keeps the phase.
+    pub fn after(self, state: &State<UserId>) -> Option<Phase> {
+        match (self, state) {
+            (Phase::Signups, State::Signups(_)) | (Phase::Night(_), State::Night(_)) | (Phase::Day(_), State::Day(_)) | (Phase::Ended, State::Complete(_)) => Some(self),
+            (_, State::Signups(_)) => self.next(Transition::Reset),
+            (Phase::Signups, State::Night(_)) => self.next(Transition::Start),
+            (Phase::Signups, State::Day(_)) => self.next(Transition::Start)?.next(Transition::Dawn), // the game can start during the day
+            (_, State::Night(_)) => self.next(Transition::Dusk),
+            (_, State::Day(_)) => self.next(Transition::Dawn),
+            (_, State::Complete(_)) => self.next(Transition::End),
+        }
+    }
+
+    /// Moves to the phase matching the given quantum state, see [`Phase::after`]. The phase is left unchanged if the state can't follow it.
+    fn advance(&mut self, state: &State<UserId>) -> Result<(), Error> {
+        *self = self.after(state).ok_or(Error::PhaseTransition(*self))?;
+        Ok(())
+    }
+
+    /// A best guess for games saved before phases were recorded, which are assumed to be in their first day or night.
+    fn of(state: &State<UserId>) -> Phase {
+        match state {
+            State::Signups(_) => Phase::Signups,
+            State::Night(_) => Phase::Night(1),
+            State::Day(_) => Phase::Day(1),
+            State::Complete(_) => Phase::Ended,
+        }
+    }
+}
+
+/// What happens to a player who leaves the server during a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Departure {
+    /// The moderators are asked to replace the player using `wwreplace`. Until then, the player stays in the game without acting.
+    Substitute,
+    /// The player is lynched at the end of the current or next day, instead of the vote result.
+    Lynch,
+}
+
+impl Default for Departure {
+    fn default() -> Departure {
+        Departure::Substitute
+    }
+}
+
+/// The time until the local clock next shows the given full hour. `None` if the hour is out of range.
+fn until_local_hour(hour: u8) -> Option<Duration> {
+    let now = Local::now();
+    let today = now.date();
+    [today, today.succ()].iter()
+        .filter_map(|date| date.and_hms_opt(hour.into(), 0, 0)) // skips the hour on days where it's skipped for daylight saving time
+        .find(|&end| end > now)
+        .and_then(|end| (end - now).to_std().ok())
+}
+
+/// The point in time the given duration from now.
+fn end_time(duration: Duration) -> DateTime<Utc> {
+    Utc::now() + chrono::Duration::from_std(duration).expect("phase duration out of range")
+}
+
+/// Formats the given point in time as a Discord timestamp, which each player's client displays relative to the current time.
+fn timestamp(time: DateTime<Utc>) -> String {
+    format!("<t:{}:R>", time.timestamp())
+}
+
+/// Formats the point in time the given duration from now as a Discord timestamp.
+fn deadline(duration: Duration) -> String {
+    timestamp(end_time(duration))
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy)]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
+    /// Given to the players while signed up or playing. Games in different channels need different roles.
+    #[schemars(with = "u64")]
     role: RoleId,
+    #[schemars(with = "u64")]
     pub text_channel: ChannelId,
+    /// If present, votes only count in this channel, keeping the discussion channel free of vote tallies.
+    #[serde(default)]
+    #[schemars(with = "Option<u64>")]
+    action_channel: Option<ChannelId>,
+    #[schemars(with = "Option<u64>")]
     voice_channel: Option<ChannelId>,
+    /// Whether to server-mute everyone in the voice channel during the night, and dead players for the rest of the game.
+    #[serde(default)]
+    mute_voice: bool,
+    /// If present, this channel receives a log of the game including night actions. Players can't see it until they die.
+    #[serde(default)]
+    #[schemars(with = "Option<u64>")]
+    spectator_channel: Option<ChannelId>,
+    /// Whether to create a spectator channel for each game if `spectatorChannel` isn't set. It's deleted when the game ends.
+    #[serde(default)]
+    create_spectator_channel: bool,
+    /// Members with this role can use `!wwmod` in this game's channel, in addition to the server's moderators.
+    #[serde(default)]
+    #[schemars(with = "Option<u64>")]
+    moderator_role: Option<RoleId>,
+    /// The minimum number of players needed to start a game. Values below the minimum supported by the game engine are raised to that.
+    #[serde(default = "default_min_players")]
+    min_players: usize,
+    /// Once this many players have signed up, the game starts immediately.
+    #[serde(default)]
+    max_players: Option<usize>,
+    /// How long signups stay open after the first player joins, in seconds. When the time runs out, the game starts if enough players have signed up, and signups are cancelled otherwise.
+    #[serde(default = "default_signup_secs")]
+    signup_secs: u64,
+    /// Whether players may delegate their vote to another player for the rest of the day.
+    #[serde(default)]
+    vote_delegation: bool,
+    /// How long a day lasts, in seconds. When it ends, the vote is resolved with the votes cast so far.
+    #[serde(default = "default_day_secs")]
+    day_secs: u64,
+    /// How long a night lasts, in seconds. When it ends, the night is resolved with the night actions sent so far.
+    #[serde(default = "default_night_secs")]
+    night_secs: u64,
+    /// If present, days last until this full hour in the bot's local time zone instead of `daySecs`, for long-form games spanning several real days.
+    #[serde(default)]
+    day_end_hour: Option<u8>,
+    /// If present, nights last until this full hour in the bot's local time zone (e.g. 9 for a night that resolves the next morning) instead of `nightSecs`.
+    #[serde(default)]
+    night_end_hour: Option<u8>,
+    /// How long before the end of a day or night the players are reminded, in seconds. 0 disables reminders.
+    #[serde(default = "default_reminder_secs")]
+    reminder_secs: u64,
+    /// How long into a day or night to DM the players who haven't acted yet, in seconds. 0 disables this.
+    #[serde(default)]
+    prod_secs: u64,
+    /// After this many days or nights without acting, the player is removed from the game like a player who left the server, see [`Config::departure`].
+    #[serde(default)]
+    afk_limit: Option<usize>,
+    /// Whether to post a message each day on which players can vote by reacting, in addition to the `vote` command.
+    #[serde(default)]
+    reaction_votes: bool,
+    /// Whether day votes are sent to the bot by DM, with only the vote counts being shown. Since reactions and delegations would reveal who voted, `reactionVotes` and `voteDelegation` have no effect in this mode.
+    #[serde(default)]
+    anonymous_votes: bool,
+    /// With `anonymousVotes`, whether everyone's votes are listed in the game log, which is posted after the game.
+    #[serde(default)]
+    reveal_votes: bool,
+    #[serde(default)]
+    tie_break: TieBreak,
+    /// How long a runoff vote lasts, in seconds, see [`TieBreak::Runoff`].
+    #[serde(default = "default_runoff_secs")]
+    runoff_secs: u64,
+    /// Whether players can vote to lynch nobody. Nobody is lynched anyway if there are no votes.
+    #[serde(default = "default_no_lynch")]
+    no_lynch: bool,
+    /// Whether dead players' roles are announced. If disabled, the probability tables only show dead players as dead, and roles are only revealed in the game log after the game.
+    #[serde(default = "default_reveal_roles")]
+    reveal_roles: bool,
+    /// How long a lynched player may still post one last message in the discussion channel, in seconds. 0 disables this.
+    #[serde(default = "default_last_words_secs")]
+    last_words_secs: u64,
+    /// What happens when a player leaves the server during a game.
+    #[serde(default)]
+    departure: Departure,
+    /// A fixed role distribution to use instead of a random one, see `!roles`.
+    #[serde(default)]
+    roles: Option<RolePreset>,
+    /// Points awarded for games played during a season, see `!season`.
+    #[serde(default)]
+    pub(crate) scoring: Scoring,
+}
+
+impl Config {
+    /// The channels in which game actions are accepted.
+    pub fn is_game_channel(&self, channel_id: ChannelId) -> bool {
+        channel_id == self.text_channel || Some(channel_id) == self.action_channel
+    }
+
+    /// The discussion channel, followed by the action channel if there is one.
+    fn channels(&self) -> impl Iterator<Item = ChannelId> {
+        iter::once(self.text_channel).chain(self.action_channel)
+    }
+
+    /// The channel where votes are announced.
+    fn vote_channel(&self) -> ChannelId {
+        self.action_channel.unwrap_or(self.text_channel)
+    }
+
+    fn min_players(&self) -> usize {
+        self.min_players.max(MIN_PLAYERS)
+    }
+
+    fn vote_delegation(&self) -> bool {
+        self.vote_delegation && !self.anonymous_votes
+    }
+
+    fn reaction_votes(&self) -> bool {
+        self.reaction_votes && !self.anonymous_votes
+    }
+
+    fn max_players(&self) -> Option<usize> {
+        self.max_players.map(|max_players| max_players.max(self.min_players()))
+    }
+
+    fn signup_duration(&self) -> Duration {
+        Duration::from_secs(self.signup_secs)
+    }
+
+    /// The length of a day starting now, see [`Config::day_end_hour`].
+    fn day_duration(&self) -> Duration {
+        self.day_end_hour.and_then(until_local_hour).unwrap_or_else(|| Duration::from_secs(self.day_secs))
+    }
+
+    /// The length of a night starting now, see [`Config::night_end_hour`].
+    fn night_duration(&self) -> Duration {
+        self.night_end_hour.and_then(until_local_hour).unwrap_or_else(|| Duration::from_secs(self.night_secs))
+    }
+
+    fn runoff_duration(&self) -> Duration {
+        Duration::from_secs(self.runoff_secs)
+    }
+
+    fn last_words_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs(self.last_words_secs)).filter(|&duration| duration > Duration::default())
+    }
+
+    /// How long into the phase to DM inactive players, if enabled and the phase is long enough.
+    fn prod(&self, phase_duration: Duration) -> Option<Duration> {
+        Some(Duration::from_secs(self.prod_secs)).filter(|&prod| prod > Duration::default() && prod < phase_duration)
+    }
+
+    /// How long before the end of the phase to send a reminder, if the phase is long enough for one.
+    fn reminder(&self, phase_duration: Duration) -> Option<Duration> {
+        Some(Duration::from_secs(self.reminder_secs)).filter(|&reminder| reminder > Duration::default() && reminder < phase_duration)
+    }
+}
+
+/// A role distribution set using `!roles`. Players without a special role are villagers.
+///
+/// The variants are handled by the bot on top of the quantum roles, so they can only change who wins or force a lynch at the end of a day. Variants which change night resolution (e.g. a witch, a bodyguard, or werewolves having to agree on a victim) would have to be added to `quantum_werewolf`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RolePreset {
+    pub(crate) werewolves: usize,
+    #[serde(default)]
+    pub(crate) detective: bool,
+    #[serde(default)]
+    pub(crate) healer: bool,
+    /// A random player is secretly the tanner, who wins alone if lynched but never wins otherwise.
+    #[serde(default)]
+    pub(crate) tanner: bool,
+    /// Two random players are secretly in love. If one of them wins, so does the other.
+    #[serde(default)]
+    pub(crate) lovers: bool,
+    /// A random player is secretly Amor, who chooses the lovers during the first phase of the game. Can't be combined with [`RolePreset::lovers`].
+    #[serde(default)]
+    pub(crate) cupid: bool,
+    /// A random player is secretly the hunter. When they die, the player they're aiming at is lynched at the end of the next day.
+    #[serde(default)]
+    pub(crate) hunter: bool,
+}
+
+impl RolePreset {
+    pub fn roles(&self) -> Vec<Role> {
+        let mut roles = (0..self.werewolves).map(|i| Role::Werewolf(i)).collect::<Vec<_>>();
+        if self.detective { roles.push(Role::Detective); }
+        if self.healer { roles.push(Role::Healer); }
+        roles
+    }
+
+    /// The smallest number of players this distribution can be used with.
+    pub fn min_players(&self) -> usize {
+        MIN_PLAYERS
+            .max(2 * self.werewolves + 1) // werewolves have to start out as a minority
+            .max(self.roles().len())
+            .max(usize::from(self.tanner) + 2 * usize::from(self.lovers) + usize::from(self.cupid) + usize::from(self.hunter)) // randomly assigned variants go to different players
+    }
+}
+
+impl fmt::Display for RolePreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", role_preset(self))
+    }
+}
+
+impl str::FromStr for RolePreset {
+    type Err = String;
+
+    /// Parses a comma-separated list of roles and variants like `2 Werwölfe, Detektiv, Heiler, Gerber`.
+    fn from_str(s: &str) -> Result<RolePreset, String> {
+        let mut preset = RolePreset { werewolves: 0, detective: false, healer: false, tanner: false, lovers: false, cupid: false, hunter: false };
+        for item in s.split(',').flat_map(|item| item.split(" und ")).map(str::trim).filter(|item| !item.is_empty()) {
+            let mut words = item.splitn(2, ' ');
+            let first_word = words.next().expect("splitn always yields at least one item");
+            let (count, name) = match (first_word.parse::<usize>(), words.next()) {
+                (Ok(count), Some(name)) => (count, name.trim()),
+                (_, _) => (1, item),
+            };
+            match (parse_role(name), &*name.to_lowercase()) {
+                (Some(Role::Werewolf(_)), _) => preset.werewolves += count,
+                (Some(Role::Detective), _) => {
+                    if preset.detective || count != 1 { return Err(format!("es kann nur einen Detektiv geben")) }
+                    preset.detective = true;
+                }
+                (Some(Role::Healer), _) => {
+                    if preset.healer || count != 1 { return Err(format!("es kann nur einen Heiler geben")) }
+                    preset.healer = true;
+                }
+                (Some(Role::Villager), _) => return Err(format!("Dorfbewohner werden automatisch aufgefüllt")),
+                (None, "gerber") | (None, "gerberin") => {
+                    if preset.tanner || count != 1 { return Err(format!("es kann nur einen Gerber geben")) }
+                    preset.tanner = true;
+                }
+                (None, "liebespaar") => {
+                    if preset.lovers || count != 1 { return Err(format!("es kann nur ein Liebespaar geben")) }
+                    preset.lovers = true;
+                }
+                (None, "amor") => {
+                    if preset.cupid || count != 1 { return Err(format!("es kann nur einen Amor geben")) }
+                    preset.cupid = true;
+                }
+                (None, "jäger") | (None, "jägerin") => {
+                    if preset.hunter || count != 1 { return Err(format!("es kann nur einen Jäger geben")) }
+                    preset.hunter = true;
+                }
+                (None, _) => return Err(format!("unbekannte Rolle: {}", name)),
+            }
+        }
+        if preset.werewolves == 0 { return Err(format!("es muss mindestens einen Werwolf geben")) }
+        if preset.lovers && preset.cupid { return Err(format!("Amor wählt das Liebespaar selbst")) }
+        Ok(preset)
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
 pub enum Vote {
     Player(UserId),
     NoLynch,
@@ -81,266 +556,2028 @@ pub enum Action {
     Night(NightAction<UserId>),
     Vote(UserId, Vote),
     Unvote(UserId),
+    Delegate(UserId, UserId),
+    Undelegate(UserId),
+    /// Amor chooses the lovers, see [`RolePreset::cupid`].
+    Couple(UserId, UserId, UserId),
+    /// The hunter chooses who to take with them, see [`RolePreset::hunter`].
+    Aim(UserId, UserId),
 }
 
 impl Action {
     pub fn src(&self) -> UserId {
         match *self {
             Action::Night(ref a) => *a.src(),
-            Action::Vote(src, _) | Action::Unvote(src) => src,
+            Action::Vote(src, _) | Action::Unvote(src) | Action::Delegate(src, _) | Action::Undelegate(src) | Action::Couple(src, _, _) | Action::Aim(src, _) => src,
+        }
+    }
+
+    /// Whether the action has to be sent by DM so the other players don't see it.
+    fn is_secret(&self) -> bool {
+        match self {
+            Action::Night(_) | Action::Couple(_, _, _) | Action::Aim(_, _) => true,
+            Action::Vote(_, _) | Action::Unvote(_) | Action::Delegate(_, _) | Action::Undelegate(_) => false,
         }
     }
 }
 
-/// The global game state is tracked here. Also serves as `typemap` key for the global game.
-#[derive(Debug)]
+/// The message posted each day if [`Config::reaction_votes`] is enabled.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VoteMessage {
+    channel: ChannelId,
+    message: MessageId,
+    /// The living players at the start of the day, or the tied players in a runoff, in the order of [`VOTE_EMOJI`].
+    candidates: Vec<UserId>,
+    /// Whether the message has a reaction for voting to lynch nobody.
+    #[serde(default = "default_no_lynch")]
+    no_lynch: bool,
+}
+
+/// An event in the game log, see [`GameState::export_log`].
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogEntry {
+    time: DateTime<Utc>,
+    /// May contain mentions, which are replaced with usernames when the log is exported.
+    text: String,
+}
+
+impl LogEntry {
+    fn now(text: impl ToString) -> LogEntry {
+        LogEntry { time: Utc::now(), text: text.to_string() }
+    }
+}
+
+/// The embed listing the signed-up players, which is posted when the first player joins and edited as players join or leave.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SignupMessage {
+    message: MessageId,
+    end: DateTime<Utc>,
+}
+
+/// The embed posted in the text channel at the start of each day and night, which is edited as the phase progresses.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhaseMessage {
+    message: MessageId,
+    night: bool,
+    /// The living players at the start of the phase, in the order of [`GameState::players`].
+    alive: Vec<UserId>,
+    end: DateTime<Utc>,
+}
+
+/// The vote options on a vote message, along with their reactions.
+fn vote_options(candidates: &[UserId], no_lynch: bool) -> impl Iterator<Item = (&'static str, Vote)> + '_ {
+    VOTE_EMOJI.iter().copied().zip(candidates.iter().map(|&candidate| Vote::Player(candidate)))
+        .chain(if no_lynch { Some((NO_LYNCH_EMOJI, Vote::NoLynch)) } else { None })
+}
+
+/// The state of a game is tracked here. Also serves as `typemap` key for all running games.
+///
+/// Serialized to disk on every change so a game survives a restart, see [`resume`].
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct GameState {
+    guild: GuildId,
+    config: Config,
+    #[serde(skip)]
+    paths: Paths,
+    state: State<UserId>,
+    /// Everyone who was in the game when it started.
+    players: Vec<UserId>,
+    alive: Option<HashSet<UserId>>,
+    night_actions: Vec<NightAction<UserId>>,
+    #[serde(skip)]
+    timeouts: Timeouts,
+    votes: HashMap<UserId, Vote>,
+    /// Maps players who delegated their vote for the current day to the player they delegated it to.
+    delegations: HashMap<UserId, UserId>,
+    /// The player who opened signups for the current game, who can close them early using `wwstart` and pause the game using `wwpause`.
+    starter: Option<UserId>,
+    /// Whether the current signups were opened using `wwrematch`, so players can also sign up by reacting to the signup embed.
+    rematch: bool,
+    /// The players of the last finished game, who are invited by `wwrematch`.
+    last_players: Vec<UserId>,
+    signup_message: Option<SignupMessage>,
+    vote_message: Option<VoteMessage>,
+    phase_message: Option<PhaseMessage>,
+    /// The tied options if a runoff vote is in progress, see [`TieBreak::Runoff`].
+    runoff: Option<Vec<Vote>>,
+    /// The spectator channel created for this game, see [`Config::create_spectator_channel`].
+    created_spectator_channel: Option<ChannelId>,
+    /// When the roles were distributed, see [`werewolf_history`].
+    started: Option<DateTime<Utc>>,
+    deaths: Vec<Death>,
+    /// The role distribution of the running game, for sending the role DM to substitutes.
+    roles: Vec<Role>,
+    /// How many days and nights each player has let run out without acting, see [`Config::afk_limit`].
+    inactivity: HashMap<UserId, usize>,
+    /// Everything that happened in the running game, including hidden information.
+    log: Vec<LogEntry>,
+    /// The tanner of the running game, see [`RolePreset::tanner`].
+    tanner: Option<UserId>,
+    /// The lovers of the running game, see [`RolePreset::lovers`]. If the game has [`GameState::cupid`], this is `None` until they're chosen.
+    lovers: Option<(UserId, UserId)>,
+    /// Amor of the running game, see [`RolePreset::cupid`].
+    cupid: Option<UserId>,
+    /// The hunter of the running game, see [`RolePreset::hunter`].
+    hunter: Option<UserId>,
+    /// The player the hunter is aiming at, see [`Action::Aim`].
+    hunter_target: Option<UserId>,
+    /// The player the hunter took with them when they died, who is lynched at the end of the next day instead of the vote result.
+    revenge: Option<UserId>,
+    /// Members who were server-muted by the game, see [`Config::mute_voice`]. Kept after the game so members who left the voice channel while muted are unmuted when they rejoin voice.
+    voice_muted: HashSet<UserId>,
+    /// The seed for all randomness of the running game that's decided by the bot, see [`seeded_rng`]. Its hash is posted when the game starts and the seed itself when it ends.
+    seed: Option<[u8; 32]>,
+    /// A member who runs the games in this channel without playing, see `wwgm`. Kept between games.
+    game_master: Option<UserId>,
+    /// The game master's decision for the current day, overriding the vote result, see `wwgm lynch`.
+    game_master_lynch: Option<Vote>,
+    /// The remaining time of the current phase while the game is paused, see `wwpause`.
+    paused: Option<Duration>,
+    /// Messages registered by players using `wwlastwords`, which are posted when they die.
+    last_words: HashMap<UserId, String>,
+    /// The lynched player who may still post a last message in the discussion channel, see [`Config::last_words_secs`].
+    last_words_window: Option<UserId>,
+    /// Players of the running game who left the server or were removed for inactivity, see [`Config::departure`].
+    departed: HashSet<UserId>,
+    /// The players in [`GameState::departed`] who were removed for reaching [`Config::afk_limit`]. Unlike players who left, they're still on the server.
+    removed_for_inactivity: HashSet<UserId>,
+    /// Kept in sync with [`GameState::state`] by [`GameState::advance`].
+    phase: Phase,
+}
+
+/// A [`GameState`] as read back from disk.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SavedGame {
     guild: GuildId,
     config: Config,
     state: State<UserId>,
+    players: Vec<UserId>,
     alive: Option<HashSet<UserId>>,
     night_actions: Vec<NightAction<UserId>>,
-    timeouts: Vec<bool>,
     votes: HashMap<UserId, Vote>,
+    delegations: HashMap<UserId, UserId>,
+    #[serde(default)]
+    starter: Option<UserId>,
+    #[serde(default)]
+    rematch: bool,
+    #[serde(default)]
+    last_players: Vec<UserId>,
+    #[serde(default)]
+    signup_message: Option<SignupMessage>,
+    #[serde(default)]
+    vote_message: Option<VoteMessage>,
+    #[serde(default)]
+    phase_message: Option<PhaseMessage>,
+    #[serde(default)]
+    runoff: Option<Vec<Vote>>,
+    #[serde(default)]
+    created_spectator_channel: Option<ChannelId>,
+    #[serde(default)]
+    started: Option<DateTime<Utc>>,
+    #[serde(default)]
+    deaths: Vec<Death>,
+    #[serde(default)]
+    roles: Vec<Role>,
+    #[serde(default)]
+    inactivity: HashMap<UserId, usize>,
+    #[serde(default)]
+    log: Vec<LogEntry>,
+    #[serde(default)]
+    tanner: Option<UserId>,
+    #[serde(default)]
+    lovers: Option<(UserId, UserId)>,
+    #[serde(default)]
+    cupid: Option<UserId>,
+    #[serde(default)]
+    hunter: Option<UserId>,
+    #[serde(default)]
+    hunter_target: Option<UserId>,
+    #[serde(default)]
+    revenge: Option<UserId>,
+    #[serde(default)]
+    voice_muted: HashSet<UserId>,
+    #[serde(default)]
+    seed: Option<[u8; 32]>,
+    #[serde(default)]
+    game_master: Option<UserId>,
+    #[serde(default)]
+    game_master_lynch: Option<Vote>,
+    #[serde(default)]
+    paused: Option<Duration>,
+    #[serde(default)]
+    last_words: HashMap<UserId, String>,
+    #[serde(default)]
+    last_words_window: Option<UserId>,
+    #[serde(default)]
+    departed: HashSet<UserId>,
+    #[serde(default)]
+    removed_for_inactivity: HashSet<UserId>,
+    #[serde(default)]
+    phase: Option<Phase>,
+}
+
+impl SavedGame {
+    fn into_game_state(self, paths: Paths) -> GameState {
+        let SavedGame { guild, config, state, players, alive, night_actions, votes, delegations, starter, rematch, last_players, signup_message, vote_message, phase_message, runoff, created_spectator_channel, started, deaths, roles, inactivity, log, tanner, lovers, cupid, hunter, hunter_target, revenge, voice_muted, seed, game_master, game_master_lynch, paused, last_words, last_words_window, departed, removed_for_inactivity, phase } = self;
+        let phase = phase.unwrap_or_else(|| Phase::of(&state));
+        GameState {
+            guild, config, paths, state, players, alive, night_actions, votes, delegations, starter, rematch, last_players, signup_message, vote_message, phase_message, runoff, created_spectator_channel, started, deaths, roles, inactivity, log, tanner, lovers, cupid, hunter, hunter_target, revenge, voice_muted, seed, game_master, game_master_lynch, paused, last_words, last_words_window, departed, removed_for_inactivity, phase,
+            timeouts: Timeouts::default(),
+        }
+    }
 }
 
 impl GameState {
-    fn new(guild: GuildId, config: Config) -> GameState {
+    fn new(guild: GuildId, config: Config, paths: Paths) -> GameState {
         GameState {
-            guild, config,
+            guild, config, paths,
             state: State::default(),
+            players: Vec::default(),
             alive: None,
             night_actions: Vec::default(),
-            timeouts: Vec::default(),
+            timeouts: Timeouts::default(),
             votes: HashMap::default(),
+            delegations: HashMap::default(),
+            starter: None,
+            rematch: false,
+            last_players: Vec::default(),
+            signup_message: None,
+            vote_message: None,
+            phase_message: None,
+            runoff: None,
+            created_spectator_channel: None,
+            started: None,
+            deaths: Vec::default(),
+            roles: Vec::default(),
+            inactivity: HashMap::default(),
+            log: Vec::default(),
+            tanner: None,
+            lovers: None,
+            cupid: None,
+            hunter: None,
+            hunter_target: None,
+            revenge: None,
+            voice_muted: HashSet::default(),
+            seed: None,
+            game_master: None,
+            game_master_lynch: None,
+            paused: None,
+            last_words: HashMap::default(),
+            last_words_window: None,
+            departed: HashSet::default(),
+            removed_for_inactivity: HashSet::default(),
+            phase: Phase::Signups,
         }
     }
 
+    /// Opens signups for the next game. Unlike other state changes, this is possible in any phase, so the quantum state and phase are reset together.
+    fn reset(&mut self) {
+        self.state = State::default();
+        self.phase = Phase::Signups;
+    }
+
+    /// Forgets everything about the current or last game so nothing carries over into the next one. Only the settings of the channel, the phase timers, [`GameState::last_players`], [`GameState::voice_muted`], and [`GameState::created_spectator_channel`] (which is removed by [`GameState::clean_up`]) are kept. Messages have to be closed before calling this.
+    fn clear_game(&mut self) {
+        *self = GameState {
+            timeouts: mem::take(&mut self.timeouts),
+            last_players: mem::take(&mut self.last_players),
+            voice_muted: mem::take(&mut self.voice_muted),
+            created_spectator_channel: self.created_spectator_channel,
+            game_master: self.game_master,
+            ..GameState::new(self.guild, self.config, self.paths.clone())
+        };
+    }
+
+    /// The file this game is saved to, see [`GameState::save`].
+    fn save_path(&self) -> PathBuf {
+        self.paths.werewolf_games().join(format!("{}-{}.json", self.guild, self.config.text_channel))
+    }
+
     async fn announce_deaths(&mut self, ctx: &Context, new_alive: Option<HashSet<UserId>>) -> Result<(), Error> {
+        let mut anyone_died = false;
         self.alive = if let Some(new_alive) = new_alive {
-            let new_alive = new_alive.iter().cloned().collect();
+            let new_alive = new_alive.iter().cloned().collect::<HashSet<_>>();
             if let Some(ref old_alive) = self.alive {
                 let mut died = stream::iter(old_alive - &new_alive).then(|user_id| user_id.to_user(ctx)).try_collect::<Vec<_>>().await?;
                 if !died.is_empty() {
                     died.sort_by_key(|user| (user.name.clone(), user.discriminator));
-                    let mut builder = MessageBuilder::default();
-                    for (i, dead_player) in died.into_iter().enumerate() {
+                    let mut announcement = Vec::with_capacity(died.len());
+                    for dead_player in died {
                         // update permissions
-                        let roles = self.guild.member(ctx, dead_player.clone()).await?.roles.into_iter().filter(|&role| role != self.config.role);
-                        self.guild.edit_member(ctx, dead_player.clone(), |m| m.roles(roles)).await?;
-                        // add to announcement
-                        if i > 0 {
-                            builder.push(" ");
+                        if !self.left_server(dead_player.id) {
+                            let roles = self.guild.member(ctx, dead_player.clone()).await?.roles.into_iter().filter(|&role| role != self.config.role);
+                            self.guild.edit_member(ctx, dead_player.clone(), |m| m.roles(roles)).await?;
+                        }
+                        if let Some(spectator_channel) = self.spectator_channel() {
+                            spectator_channel.delete_permission(ctx, PermissionOverwriteType::Member(dead_player.id)).await?;
+                        }
+                        let role = self.state.role(&dead_player.id);
+                        self.deaths.push(Death {
+                            player: dead_player.id,
+                            role: role.map(|role| werewolf_history::role_key(role).to_owned()),
+                            time: Utc::now(),
+                        });
+                        announcement.push((dead_player.id, role));
+                    }
+                    let log_entry = deaths(&announcement);
+                    if self.config.reveal_roles {
+                        self.config.text_channel.say(ctx, &log_entry).await?;
+                    } else {
+                        self.config.text_channel.say(ctx, deaths(&announcement.iter().map(|&(player, _)| (player, None)).collect::<Vec<_>>())).await?;
+                    }
+                    self.log.push(LogEntry::now(log_entry));
+                    let hunter_died = self.hunter.map_or(false, |hunter| announcement.iter().any(|&(dead_player, _)| dead_player == hunter));
+                    for (dead_player, _) in announcement {
+                        if let Some(text) = self.last_words.remove(&dead_player) {
+                            let last_words = last_words(dead_player, &text);
+                            self.config.text_channel.say(ctx, &last_words).await?;
+                            self.log.push(LogEntry::now(last_words));
                         }
-                        builder.mention(&dead_player);
-                        builder.push(" ist tot");
-                        if let Some(role) = self.state.role(&dead_player.id) {
-                            builder.push(" und war ");
-                            builder.push_safe(role_name(role, Nom, false));
+                    }
+                    if hunter_died && matches!(self.state, State::Night(_) | State::Day(_)) {
+                        if let (Some(hunter), Some(target)) = (self.hunter, self.hunter_target.take().filter(|target| new_alive.contains(target))) {
+                            self.revenge = Some(target);
+                            let announcement = hunter_revenge(hunter, target);
+                            self.config.text_channel.say(ctx, &announcement).await?;
+                            self.log.push(LogEntry::now(announcement));
                         }
-                        builder.push(".");
                     }
-                    self.config.text_channel.say(ctx, builder).await?;
+                    anyone_died = true;
+                }
+            }
+            // delegations to or from dead players lapse
+            let mut lapsed = self.delegations.iter()
+                .filter(|&(delegator, delegate)| !new_alive.contains(delegator) || !new_alive.contains(delegate))
+                .map(|(&delegator, &delegate)| (delegator, delegate))
+                .collect::<Vec<_>>();
+            lapsed.sort();
+            for (delegator, delegate) in lapsed {
+                self.delegations.remove(&delegator);
+                if new_alive.contains(&delegator) {
+                    self.config.vote_channel().say(ctx, delegation_lapsed(delegator, delegate)).await?;
                 }
             }
             Some(new_alive)
         } else {
             None
         };
+        if anyone_died {
+            self.sync_voice_mutes(ctx, is_night(&self.state)).await?; // dead players stay muted for the rest of the game
+        }
         //TODO send new role DMs for remaining Quantum States
         Ok(())
     }
 
-    fn cancel_all_timeouts(&mut self) {
-        self.timeouts = vec![false; self.timeouts.len()];
+    /// Reads the games saved by [`GameState::save`]. Pending timeouts are not restored, see [`resume`].
+    ///
+    /// Save files which can't be read are renamed to `<name>.json.invalid` for manual inspection and skipped, so a single corrupted game doesn't keep the bot from starting.
+    pub async fn load_all(paths: &Paths) -> Result<HashMap<GameId, GameState>, Error> {
+        let mut games = HashMap::default();
+        let mut entries = match fs::read_dir(paths.werewolf_games()).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(games),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().map_or(true, |ext| ext != "json") { continue }
+            match serde_json::from_slice::<SavedGame>(&fs::read(&path).await?) { //TODO use async-json
+                Ok(game) => {
+                    let game = game.into_game_state(paths.clone());
+                    games.insert(game.id(), game);
+                }
+                Err(e) => {
+                    eprintln!("failed to read werewolf game from {}, moving it out of the way: {} ({:?})", path.display(), e, e);
+                    fs::rename(&path, path.with_extension("json.invalid")).await?;
+                }
+            }
+        }
+        Ok(games)
     }
 
-    fn cancel_timeout(&mut self, timeout_idx: usize) {
-        self.timeouts[timeout_idx] = false;
+    /// Replaces a player with a substitute everywhere in the game state, including the quantum state, night actions, and votes.
+    ///
+    /// `quantum_werewolf` has no API for this, so the state is serialized and every occurrence of the player's ID is replaced.
+    fn substitute(&mut self, leaving: UserId, substitute: UserId) -> Result<(), Error> {
+        fn replace(value: &mut serde_json::Value, leaving: UserId, substitute: UserId) {
+            match value {
+                serde_json::Value::Number(n) if n.as_u64() == Some(leaving.0) => *value = serde_json::Value::from(substitute.0),
+                serde_json::Value::String(s) if *s == leaving.to_string() => *s = substitute.to_string(),
+                serde_json::Value::Array(values) => for value in values { replace(value, leaving, substitute) },
+                serde_json::Value::Object(map) => {
+                    *map = mem::take(map).into_iter()
+                        .map(|(key, mut value)| {
+                            replace(&mut value, leaving, substitute);
+                            (if key == leaving.to_string() { substitute.to_string() } else { key }, value)
+                        })
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+
+        let mut value = serde_json::to_value(&*self)?;
+        replace(&mut value, leaving, substitute);
+        let timeouts = mem::take(&mut self.timeouts);
+        *self = serde_json::from_value::<SavedGame>(value)?.into_game_state(self.paths.clone());
+        self.timeouts = timeouts;
+        Ok(())
     }
 
-    async fn resolve_day(&mut self, ctx: &Context, day: Day<UserId>) -> Result<(), Error> {
-        self.cancel_all_timeouts();
-        // close discussion
-        self.config.text_channel.delete_permission(ctx, PermissionOverwriteType::Role(self.config.role)).await?;
-        self.config.text_channel.say(ctx, "Die Diskussion ist geschlossen.").await?;
-        // determine the players and/or game actions with the most votes
-        let (_, vote_result) = vote_leads(&self);
-        // if the result is a single player, lynch that player
-        self.state = if vote_result.len() == 1 {
-            match vote_result.into_iter().next().unwrap() {
-                Vote::Player(user_id) => day.lynch(user_id),
-                Vote::NoLynch => day.no_lynch(),
-            }
+    fn spectator_channel(&self) -> Option<ChannelId> {
+        self.config.spectator_channel.or(self.created_spectator_channel)
+    }
+
+    /// Creates a spectator channel next to the discussion channel which the players can't see, if configured. Otherwise, hides the configured spectator channel from the players.
+    async fn set_up_spectator_channel(&mut self, ctx: &Context) -> Result<(), Error> {
+        if let Some(spectator_channel) = self.config.spectator_channel {
+            self.hide_from_players(ctx, spectator_channel).await?;
+        } else if self.config.create_spectator_channel {
+            self.created_spectator_channel = Some(self.create_hidden_channel(ctx, "werwölfe-zuschauer").await?);
+        }
+        Ok(())
+    }
+
+    /// Makes the spectator channel visible to the players again, or deletes it if it was created for this game.
+    async fn tear_down_spectator_channel(&mut self, ctx: &Context) -> Result<(), Error> {
+        if let Some(spectator_channel) = self.created_spectator_channel.take() {
+            spectator_channel.delete(ctx).await?;
+        } else if let Some(spectator_channel) = self.config.spectator_channel {
+            spectator_channel.say(ctx, spectator_game_over()).await?;
+            self.show_to_players(ctx, spectator_channel).await?;
+        }
+        Ok(())
+    }
+
+    /// Unlocks the game channels and removes the spectator channel and game role after a game.
+    async fn clean_up(&mut self, ctx: &Context) -> Result<(), Error> {
+        self.close_last_words_window(ctx).await?;
+        self.unlock_channels(ctx).await?;
+        self.tear_down_spectator_channel(ctx).await?;
+        self.remove_game_role(ctx).await?;
+        Ok(())
+    }
+
+    /// A random number generator derived from the game's seed, see [`seeded_rng`]. Games saved before seeds were introduced use unseeded randomness.
+    fn rng(&self, purpose: &str) -> StdRng {
+        match self.seed {
+            Some(ref seed) => seeded_rng(seed, purpose),
+            None => StdRng::from_rng(thread_rng()).expect("failed to seed RNG"),
+        }
+    }
+
+    /// Randomly picks the tanner, lovers, Amor, and hunter if the preset has these variants, and tells them in a DM.
+    async fn assign_variants(&mut self, ctx: &Context, preset: Option<RolePreset>) -> Result<(), Error> {
+        let mut candidates = self.players.clone();
+        candidates.sort(); // make the choice independent of the engine's player order
+        candidates.shuffle(&mut self.rng("variants"));
+        self.tanner = preset.filter(|preset| preset.tanner).and_then(|_| candidates.pop());
+        self.lovers = if preset.map_or(false, |preset| preset.lovers) {
+            candidates.pop().and_then(|lover1| candidates.pop().map(|lover2| (lover1, lover2)))
         } else {
-            day.no_lynch()
+            None
         };
-        self.votes = HashMap::default();
-        let new_alive = self.state.alive().map(|new_alive| new_alive.into_iter().cloned().collect());
-        self.announce_deaths(ctx, new_alive).await?;
-        if let State::Night(ref night) = self.state {
-            self.start_night(ctx, night).await?;
+        self.cupid = preset.filter(|preset| preset.cupid).and_then(|_| candidates.pop());
+        self.hunter = preset.filter(|preset| preset.hunter).and_then(|_| candidates.pop());
+        self.hunter_target = None;
+        self.revenge = None;
+        if self.tanner.is_some() || self.lovers.is_some() || self.cupid.is_some() || self.hunter.is_some() {
+            self.spectate(ctx, variants_log(self.tanner, self.lovers, self.cupid, self.hunter)).await?;
+        }
+        for player in self.players.clone() {
+            self.send_variant_dms(ctx, player).await?;
         }
         Ok(())
     }
 
-    async fn resolve_night(&mut self, ctx: &Context, night: Night<UserId>) -> Result<State<UserId>, Error> {
-        self.cancel_all_timeouts();
-        let result = night.resolve_nar(&self.night_actions);
-        self.night_actions = Vec::default();
-        if let State::Day(ref day) = result {
-            // send night action results
-            for (player, result) in day.night_action_results() {
-                match result {
-                    NightActionResult::Investigation(target, faction) => {
-                        let dm = MessageBuilder::default()
-                            .push("Ergebnis deiner Nachtaktion: ")
-                            .dm_mention(&target.to_user(ctx).await?)
-                            .push(" gehört ")
-                            .push_safe(zu(faction_gender(faction)))
-                            .push(" ")
-                            .push_safe(faction_name(faction, Dat))
-                            .build();
-                        player.create_dm_channel(ctx).await?.say(ctx, &dm).await?;
+    /// Tells the player if they're the tanner, one of the lovers, Amor, or the hunter.
+    async fn send_variant_dms(&self, ctx: &Context, player: UserId) -> Result<(), Error> {
+        if self.tanner == Some(player) {
+            player.create_dm_channel(ctx).await?.say(ctx, tanner_dm()).await?;
+        }
+        if self.cupid == Some(player) {
+            player.create_dm_channel(ctx).await?.say(ctx, if self.lovers.is_some() { cupid_done_dm() } else { cupid_dm() }).await?;
+        }
+        if self.hunter == Some(player) {
+            player.create_dm_channel(ctx).await?.say(ctx, hunter_dm()).await?;
+        }
+        if let Some((lover1, lover2)) = self.lovers {
+            if player == lover1 {
+                player.create_dm_channel(ctx).await?.say(ctx, lover_dm(lover2)).await?;
+            } else if player == lover2 {
+                player.create_dm_channel(ctx).await?.say(ctx, lover_dm(lover1)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks whether Amor can make the given players the lovers.
+    fn check_couple(&self, src: UserId, lover1: UserId, lover2: UserId) -> Result<(UserId, UserId), Error> {
+        if self.cupid != Some(src) { return Err(Error::GameAction("du bist nicht Amor".into())) }
+        if self.lovers.is_some() { return Err(Error::GameAction("das Liebespaar steht schon fest".into())) }
+        if lover1 == lover2 { return Err(Error::GameAction("das Liebespaar muss aus zwei verschiedenen Spielern bestehen".into())) }
+        if self.alive.as_ref().map_or(false, |alive| !alive.contains(&lover1) || !alive.contains(&lover2)) { return Err(Error::GameAction("du kannst nur lebende Spieler verlieben".into())) }
+        Ok((lover1, lover2))
+    }
+
+    /// Checks whether the hunter can aim at the given player.
+    fn check_aim(&self, src: UserId, target: UserId) -> Result<UserId, Error> {
+        if self.hunter != Some(src) { return Err(Error::GameAction("du bist nicht der Jäger".into())) }
+        if self.alive.as_ref().map_or(false, |alive| !alive.contains(&src)) { return Err(Error::GameAction("tote Spieler können nicht zielen".into())) }
+        if src == target { return Err(Error::GameAction("du kannst nicht auf dich selbst zielen".into())) }
+        if self.alive.as_ref().map_or(false, |alive| !alive.contains(&target)) { return Err(Error::GameAction("du kannst nur auf lebende Spieler zielen".into())) }
+        Ok(target)
+    }
+
+    /// Makes the given players the lovers and tells them and the spectators. `chosen` is `false` if Amor didn't choose and they were picked randomly.
+    async fn set_lovers(&mut self, ctx: &Context, lover1: UserId, lover2: UserId, chosen: bool) -> Result<(), Error> {
+        self.lovers = Some((lover1, lover2));
+        if let Some(cupid) = self.cupid {
+            self.spectate(ctx, lovers_chosen_log(cupid, lover1, lover2, chosen)).await?;
+        }
+        lover1.create_dm_channel(ctx).await?.say(ctx, lover_dm(lover2)).await?;
+        lover2.create_dm_channel(ctx).await?.say(ctx, lover_dm(lover1)).await?;
+        Ok(())
+    }
+
+    /// Picks random lovers at the end of the first phase if Amor didn't choose any.
+    async fn ensure_lovers(&mut self, ctx: &Context) -> Result<(), Error> {
+        if self.cupid.is_none() || self.lovers.is_some() { return Ok(()) }
+        let mut candidates = self.alive.as_ref().map_or_else(|| self.players.clone(), |alive| alive.iter().copied().collect());
+        candidates.sort();
+        candidates.shuffle(&mut self.rng("lovers"));
+        if let (Some(lover1), Some(lover2)) = (candidates.pop(), candidates.pop()) {
+            self.set_lovers(ctx, lover1, lover2, false).await?;
+        }
+        Ok(())
+    }
+
+    /// Adjusts the winners of a finished game according to the variants: The tanner only wins if lynched, which is handled in [`GameState::resolve_day`], and lovers win together.
+    fn apply_variants(&self, state: State<UserId>) -> State<UserId> {
+        if let State::Complete(Complete { winners }) = state {
+            let mut winners = winners.into_iter().filter(|&winner| Some(winner) != self.tanner).collect::<Vec<_>>();
+            if let Some((lover1, lover2)) = self.lovers {
+                if winners.contains(&lover1) && !winners.contains(&lover2) { winners.push(lover2); }
+                if winners.contains(&lover2) && !winners.contains(&lover1) { winners.push(lover1); }
+            }
+            State::Complete(Complete { winners: winners.into_iter().collect() })
+        } else {
+            state
+        }
+    }
+
+    /// Whether the given member should be server-muted while in the given voice channel. `night` is `None` if no game is running.
+    fn should_mute(&self, user: UserId, voice_channel: ChannelId, night: Option<bool>) -> bool {
+        self.config.mute_voice && Some(voice_channel) == self.config.voice_channel && match night {
+            Some(true) => true,
+            Some(false) => self.players.contains(&user) && self.alive.as_ref().map_or(false, |alive| !alive.contains(&user)),
+            None => false,
+        }
+    }
+
+    /// Server-mutes or unmutes the members who are currently in voice according to [`GameState::should_mute`].
+    async fn sync_voice_mutes(&mut self, ctx: &Context, night: Option<bool>) -> Result<(), Error> {
+        if !self.config.mute_voice && self.voice_muted.is_empty() { return Ok(()) }
+        let voice_states = self.guild.to_guild_cached(ctx).await.map(|guild| guild.voice_states).unwrap_or_default();
+        for (user, voice_state) in voice_states {
+            let voice_channel = if let Some(voice_channel) = voice_state.channel_id { voice_channel } else { continue };
+            let mute = self.should_mute(user, voice_channel, night);
+            if mute != self.voice_muted.contains(&user) {
+                self.guild.edit_member(ctx, user, |m| m.mute(mute)).await?;
+                if mute { self.voice_muted.insert(user); } else { self.voice_muted.remove(&user); }
+            }
+        }
+        Ok(())
+    }
+
+    /// Posts to the spectator channel, if there is one, and adds to the game log.
+    async fn spectate(&mut self, ctx: &Context, content: impl fmt::Display) -> Result<(), Error> {
+        let content = content.to_string();
+        self.post_hidden(ctx, &content).await?;
+        self.log.push(LogEntry::now(content));
+        Ok(())
+    }
+
+    /// Sends hidden information to the spectator channel and the game master. Use [`GameState::spectate`] instead to also log it.
+    async fn post_hidden(&self, ctx: &Context, content: &str) -> Result<(), Error> {
+        if let Some(spectator_channel) = self.spectator_channel() {
+            spectator_channel.say(ctx, content).await?;
+        }
+        if let Some(game_master) = self.game_master {
+            game_master.create_dm_channel(ctx).await?.say(ctx, content).await?;
+        }
+        Ok(())
+    }
+
+    /// Posts the game log as a Markdown file in the text channel and saves it to disk, so the players can see everything that happened.
+    async fn export_log(&mut self, ctx: &Context) -> Result<(), Error> {
+        let start = self.started.unwrap_or_else(Utc::now);
+        let mut markdown = format!("# {}\n", game_log_title(&self.config.text_channel.name(ctx).await.unwrap_or_else(|| self.config.text_channel.to_string()), start));
+        for entry in mem::take(&mut self.log) {
+            let text = resolve_mentions(ctx, &entry.text).await?;
+            let mut lines = text.lines();
+            markdown.push_str(&format!("\n- **{}** {}", entry.time.format("%H:%M:%S"), lines.next().unwrap_or_default()));
+            for line in lines {
+                markdown.push_str(&format!("\n  {}", line));
+            }
+        }
+        markdown.push('\n');
+        let filename = format!("{}-{}-{}.md", self.guild, self.config.text_channel, start.format("%Y%m%d%H%M%S"));
+        fs::create_dir_all(self.paths.werewolf_logs()).await?;
+        File::create(self.paths.werewolf_logs().join(&filename)).await?.write_all(markdown.as_bytes()).await?;
+        self.config.text_channel.send_message(ctx, |m| m
+            .content(game_log_posted())
+            .add_file(AttachmentType::Bytes { data: Cow::Owned(markdown.into_bytes()), filename })
+        ).await?;
+        Ok(())
+    }
+
+    /// Lets the lynched player post one more message in the discussion channel, until the given time runs out.
+    async fn open_last_words_window(&mut self, ctx: &Context, player: UserId, duration: Duration) -> Result<(), Error> {
+        self.config.text_channel.create_permission(ctx, &PermissionOverwrite {
+            kind: PermissionOverwriteType::Member(player),
+            allow: Permissions::SEND_MESSAGES,
+            deny: Permissions::empty(),
+        }).await?;
+        self.last_words_window = Some(player);
+        self.config.text_channel.say(ctx, last_words_window(player, &deadline(duration))).await?;
+        let ctx = ctx.clone();
+        let game = self.id();
+        tokio::spawn(async move {
+            sleep(duration).await;
+            let mut data = ctx.data.write().await;
+            if let Some(state_ref) = data.get_mut::<GameState>().expect("missing Werewolf game state").get_mut(&game) {
+                if state_ref.last_words_window == Some(player) {
+                    if let Err(e) = async { state_ref.close_last_words_window(&ctx).await?; state_ref.save().await }.await {
+                        eprintln!("failed to close werewolf last words window: {} ({:?})", e, e);
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn close_last_words_window(&mut self, ctx: &Context) -> Result<(), Error> {
+        if let Some(player) = self.last_words_window.take() {
+            self.config.text_channel.delete_permission(ctx, PermissionOverwriteType::Member(player)).await?;
+        }
+        Ok(())
+    }
+
+    async fn resolve_day(&mut self, ctx: &Context, day: Day<UserId>) -> Result<(), Error> {
+        self.timeouts.cancel_all();
+        self.ensure_lovers(ctx).await?;
+        // close discussion
+        for channel in self.config.channels() {
+            channel.delete_permission(ctx, PermissionOverwriteType::Role(self.config.role)).await?;
+        }
+        self.config.text_channel.say(ctx, discussion_closed()).await?;
+        if let Some(action_channel) = self.config.action_channel {
+            action_channel.say(ctx, voting_closed()).await?;
+        }
+        self.close_vote_message(ctx).await?;
+        self.close_phase_message(ctx).await?;
+        self.log_vote_result();
+        let tie_break = if self.runoff.take().is_some() { TieBreak::NoLynch } else { self.config.tie_break };
+        let departed = if self.config.departure == Departure::Lynch {
+            self.departed.iter().copied().filter(|player| day.alive().contains(player)).min()
+        } else {
+            None
+        };
+        let revenge = if departed.is_none() { self.revenge.take().filter(|player| day.alive().contains(player)) } else { None };
+        let lynched = if let Some(player) = departed {
+            self.game_master_lynch = None;
+            let announcement = if self.removed_for_inactivity.contains(&player) { inactive_lynched(player) } else { departed_lynched(player) };
+            self.config.text_channel.say(ctx, &announcement).await?;
+            self.log.push(LogEntry::now(announcement));
+            Some(player)
+        } else if let Some(player) = revenge {
+            self.game_master_lynch = None;
+            let announcement = revenge_lynched(player);
+            self.config.text_channel.say(ctx, &announcement).await?;
+            self.log.push(LogEntry::now(announcement));
+            Some(player)
+        } else if let Some(vote) = self.game_master_lynch.take() {
+            let lynched = match vote {
+                Vote::Player(player) => Some(player),
+                Vote::NoLynch => None,
+            };
+            let announcement = game_master_decision(lynched);
+            self.config.text_channel.say(ctx, &announcement).await?;
+            self.log.push(LogEntry::now(announcement));
+            lynched
+        } else {
+            lynch_choice(&self.votes, &self.delegations, tie_break, &mut self.rng(&format!("lynch {}", self.deaths.len())))
+        };
+        self.state = self.apply_variants(match lynched {
+            Some(player) => day.lynch(player),
+            None => day.no_lynch(),
+        });
+        self.votes = HashMap::default();
+        self.delegations = HashMap::default();
+        let new_alive = self.state.alive().map(|new_alive| new_alive.into_iter().cloned().collect());
+        self.announce_deaths(ctx, new_alive).await?;
+        if let (Some(player), Some(duration)) = (lynched.filter(|player| !self.departed.contains(player)), self.config.last_words_duration()) {
+            if self.alive.as_ref().map_or(false, |alive| !alive.contains(&player)) {
+                self.open_last_words_window(ctx, player, duration).await?;
+            }
+        }
+        if let Some(tanner) = self.tanner.filter(|&tanner| lynched == Some(tanner) && departed.is_none() && revenge.is_none()) {
+            let announcement = tanner_won(tanner);
+            self.config.text_channel.say(ctx, &announcement).await?;
+            self.log.push(LogEntry::now(announcement));
+            self.state = State::Complete(Complete { winners: iter::once(tanner).collect() });
+            self.phase.advance(&self.state)?;
+            return Ok(())
+        }
+        self.phase.advance(&self.state)?;
+        let state = mem::replace(&mut self.state, State::default());
+        if let State::Night(ref night) = state {
+            self.start_night(ctx, night).await?;
+        }
+        self.state = state;
+        Ok(())
+    }
+
+    async fn resolve_night(&mut self, ctx: &Context, night: Night<UserId>) -> Result<State<UserId>, Error> {
+        self.timeouts.cancel_all();
+        self.close_phase_message(ctx).await?;
+        self.ensure_lovers(ctx).await?;
+        let result = self.apply_variants(night.resolve_nar(&self.night_actions));
+        self.night_actions = Vec::default();
+        self.phase.advance(&result)?;
+        if let State::Day(ref day) = result {
+            // send night action results
+            for (player, result) in day.night_action_results() {
+                match result {
+                    NightActionResult::Investigation(target, faction) => {
+                        let target = target.to_user(ctx).await?;
+                        let player = player.to_user(ctx).await?;
+                        player.create_dm_channel(ctx).await?.say(ctx, investigation_result(&target, faction)).await?;
+                        self.spectate(ctx, investigation_log(player.id, target.id, faction)).await?;
                     }
                 }
             }
             self.start_day(ctx, day).await?;
         }
-        Ok(result)
+        Ok(result)
+    }
+
+    async fn start_day(&mut self, ctx: &Context, day: &Day<UserId>) -> Result<(), Error> {
+        self.sync_voice_mutes(ctx, Some(false)).await?;
+        // announce probability table
+        self.config.text_channel.say(ctx, probability_table(percentages(day, self.config.reveal_roles))).await?;
+        self.log.push(LogEntry::now(format!("{}\n{}", phase_title(false, self.phase.number(), false), probability_table(percentages(day, true)))));
+        // open discussion
+        for channel in self.config.channels() {
+            channel.create_permission(ctx, &PermissionOverwrite {
+                kind: PermissionOverwriteType::Role(self.config.role),
+                allow: Permissions::SEND_MESSAGES | Permissions::ADD_REACTIONS,
+                deny: Permissions::empty(),
+            }).await?;
+        }
+        let day_end = end_time(self.config.day_duration());
+        if let (Some(action_channel), false) = (self.config.action_channel, self.config.anonymous_votes) {
+            action_channel.say(ctx, day_start_action_channel(self.config.text_channel, &timestamp(day_end))).await?;
+        }
+        let candidates = self.players.iter().copied().filter(|player| day.alive().contains(player)).collect::<Vec<_>>();
+        self.post_phase_message(ctx, false, candidates.clone(), day_end).await?;
+        self.post_vote_message(ctx, candidates, self.config.no_lynch).await?;
+        Ok(())
+    }
+
+    /// Whether the given vote is currently allowed, taking [`Config::no_lynch`] and runoff votes into account.
+    fn is_vote_option(&self, vote: Vote) -> bool {
+        match self.runoff {
+            Some(ref runoff) => runoff.contains(&vote),
+            None => vote != Vote::NoLynch || self.config.no_lynch,
+        }
+    }
+
+    /// Adds the vote counts to the game log, as well as everyone's votes if [`Config::reveal_votes`] is enabled.
+    fn log_vote_result(&mut self) {
+        self.log.push(LogEntry::now(vote_result_log(&self.vote_tally())));
+        if self.config.anonymous_votes && self.config.reveal_votes {
+            let mut votes = self.votes.iter()
+                .map(|(&voter, &vote)| (voter, match vote {
+                    Vote::Player(candidate) => Some(candidate),
+                    Vote::NoLynch => None,
+                }))
+                .collect::<Vec<_>>();
+            votes.sort();
+            self.log.push(LogEntry::now(anonymous_votes_log(&votes)));
+        }
+    }
+
+    /// Repeats the day's vote with only the tied options, see [`TieBreak::Runoff`].
+    async fn start_runoff(&mut self, ctx: &Context, mut tied: Vec<Vote>) -> Result<(), Error> {
+        tied.sort();
+        self.close_vote_message(ctx).await?;
+        self.votes = HashMap::default();
+        let runoff_end = end_time(self.config.runoff_duration());
+        if let Some(ref mut phase_message) = self.phase_message {
+            phase_message.end = runoff_end;
+        }
+        self.update_phase_message(ctx, false).await?;
+        let candidates = tied.iter().filter_map(|&vote| if let Vote::Player(candidate) = vote { Some(candidate) } else { None }).collect::<Vec<_>>();
+        let no_lynch = tied.contains(&Vote::NoLynch);
+        self.log_vote_result();
+        let announcement = runoff_start(&candidates, no_lynch, &timestamp(runoff_end));
+        self.config.vote_channel().say(ctx, &announcement).await?;
+        self.log.push(LogEntry::now(announcement));
+        self.runoff = Some(tied);
+        self.post_vote_message(ctx, candidates, no_lynch).await?;
+        Ok(())
+    }
+
+    /// Posts a message on which players can vote by reacting, if enabled.
+    async fn post_vote_message(&mut self, ctx: &Context, candidates: Vec<UserId>, no_lynch: bool) -> Result<(), Error> {
+        if self.config.reaction_votes() && candidates.len() <= VOTE_EMOJI.len() {
+            let msg = self.config.vote_channel().say(ctx, self.vote_message_content(&candidates, no_lynch, false)).await?;
+            for (emoji, _) in vote_options(&candidates, no_lynch) {
+                msg.react(ctx, ReactionType::Unicode(emoji.to_owned())).await?;
+            }
+            self.vote_message = Some(VoteMessage { channel: msg.channel_id, message: msg.id, candidates, no_lynch });
+        }
+        Ok(())
+    }
+
+    /// Marks the vote message, if any, as closed. It no longer counts reactions after this.
+    async fn close_vote_message(&mut self, ctx: &Context) -> Result<(), Error> {
+        if let Some(vote_message) = self.vote_message.take() {
+            vote_message.channel.edit_message(ctx, vote_message.message, |m| m.content(self.vote_message_content(&vote_message.candidates, vote_message.no_lynch, true))).await?;
+        }
+        Ok(())
+    }
+
+    /// The text of the vote message, with the current vote counts.
+    fn vote_message_content(&self, candidates: &[UserId], no_lynch: bool, closed: bool) -> String {
+        let counts = vote_counts(&self.votes, &self.delegations);
+        vote_message(closed, vote_options(candidates, no_lynch).map(|(emoji, vote)| {
+            let count = counts.get(&vote).copied().unwrap_or_default();
+            (emoji, match vote {
+                Vote::Player(candidate) => Some(candidate),
+                Vote::NoLynch => None,
+            }, count)
+        }))
+    }
+
+    /// The players signed up for the next game, in the order they joined.
+    fn signed_up(&self) -> Vec<UserId> {
+        match self.state {
+            State::Signups(_) => self.state.secret_ids().map(|secret_ids| secret_ids.iter().copied().collect()).unwrap_or_default(),
+            State::Night(_) | State::Day(_) | State::Complete(_) => Vec::default(),
+        }
+    }
+
+    /// Fills in the embed listing the signed-up players and when signups close.
+    fn signup_embed<'a>(&self, e: &'a mut CreateEmbed, players: &[UserId], end: DateTime<Utc>, closed: bool) -> &'a mut CreateEmbed {
+        e.title(signup_title(closed));
+        e.description(signup_description(self.config.min_players(), self.config.max_players(), &timestamp(end), self.rematch));
+        e.field(signed_up_field(players.len(), self.config.max_players()), signup_list(players), false)
+    }
+
+    /// Posts or updates the signup embed. The signup countdown starts when it's first posted.
+    async fn update_signup_message(&mut self, ctx: &Context) -> Result<(), Error> {
+        let players = self.signed_up();
+        if let Some(ref signup_message) = self.signup_message {
+            self.config.text_channel.edit_message(ctx, signup_message.message, |m| m.embed(|e| self.signup_embed(e, &players, signup_message.end, false))).await?;
+        } else {
+            let end = end_time(self.config.signup_duration());
+            let msg = self.config.text_channel.send_message(ctx, |m| {
+                if self.rematch {
+                    m.content(rematch_invitation(&self.last_players));
+                }
+                m.embed(|e| self.signup_embed(e, &players, end, false))
+            }).await?;
+            if self.rematch {
+                msg.react(ctx, ReactionType::Unicode(REMATCH_EMOJI.to_owned())).await?;
+            }
+            self.signup_message = Some(SignupMessage { message: msg.id, end });
+        }
+        Ok(())
+    }
+
+    /// Marks the signup embed, if any, as closed, showing the given final list of players.
+    async fn close_signup_message(&mut self, ctx: &Context, players: &[UserId]) -> Result<(), Error> {
+        if let Some(signup_message) = self.signup_message.take() {
+            self.config.text_channel.edit_message(ctx, signup_message.message, |m| m.embed(|e| self.signup_embed(e, players, signup_message.end, true))).await?;
+        }
+        self.rematch = false;
+        Ok(())
+    }
+
+    /// Fills in the embed showing the current phase, the living players, the vote status, and when the phase ends.
+    fn phase_embed<'a>(&self, e: &'a mut CreateEmbed, night: bool, alive: &[UserId], end: DateTime<Utc>, closed: bool) -> &'a mut CreateEmbed {
+        let alive = alive.iter()
+            .map(|&player| (self.players.iter().position(|&iter_player| iter_player == player).map_or(0, |idx| idx + 1), player))
+            .collect::<Vec<_>>();
+        e.title(phase_title(night, self.phase.number(), closed));
+        if night {
+            e.color((0x2c, 0x3e, 0x50));
+            e.description(night_start());
+        } else {
+            e.color((0xf1, 0xc4, 0x0f));
+            e.description(day_start(alive.len() / 2 + 1, self.config.vote_delegation(), self.config.action_channel, self.config.anonymous_votes));
+        }
+        e.field(alive_players_field(), player_list(&alive), false);
+        if !night {
+            e.field(votes_field(), vote_status(&self.vote_tally()), false);
+        }
+        e.field(phase_end_field(), if let Some(remaining) = self.paused { phase_paused((remaining.as_secs() + 59) / 60) } else { timestamp(end) }, false)
+    }
+
+    /// The current vote counts, most votes first. `None` stands for no lynch.
+    fn vote_tally(&self) -> Vec<(Option<UserId>, usize)> {
+        let mut counts = vote_counts(&self.votes, &self.delegations).into_iter()
+            .map(|(vote, count)| (match vote {
+                Vote::Player(candidate) => Some(candidate),
+                Vote::NoLynch => None,
+            }, count))
+            .collect::<Vec<_>>();
+        counts.sort_by_key(|&(vote, count)| (Reverse(count), vote));
+        counts
+    }
+
+    /// Posts the embed for a new day or night in the text channel.
+    async fn post_phase_message(&mut self, ctx: &Context, night: bool, alive: Vec<UserId>, end: DateTime<Utc>) -> Result<(), Error> {
+        let msg = self.config.text_channel.send_message(ctx, |m| m.embed(|e| self.phase_embed(e, night, &alive, end, false))).await?;
+        self.phase_message = Some(PhaseMessage { message: msg.id, night, alive, end });
+        Ok(())
+    }
+
+    /// Edits the phase embed, if any, to show the current state.
+    async fn update_phase_message(&self, ctx: &Context, closed: bool) -> Result<(), Error> {
+        if let Some(ref phase_message) = self.phase_message {
+            self.config.text_channel.edit_message(ctx, phase_message.message, |m| m.embed(|e| self.phase_embed(e, phase_message.night, &phase_message.alive, phase_message.end, closed))).await?;
+        }
+        Ok(())
+    }
+
+    /// Marks the phase embed, if any, as ended. It's no longer edited after this.
+    async fn close_phase_message(&mut self, ctx: &Context) -> Result<(), Error> {
+        self.update_phase_message(ctx, true).await?;
+        self.phase_message = None;
+        Ok(())
+    }
+
+    /// Shows the current vote counts on the vote message, if any.
+    async fn update_vote_message(&self, ctx: &Context) -> Result<(), Error> {
+        if let Some(ref vote_message) = self.vote_message {
+            vote_message.channel.edit_message(ctx, vote_message.message, |m| m.content(self.vote_message_content(&vote_message.candidates, vote_message.no_lynch, false))).await?;
+        }
+        Ok(())
+    }
+
+    async fn start_night(&mut self, ctx: &Context, _: &Night<UserId>) -> Result<(), Error> {
+        self.log.push(LogEntry::now(phase_title(true, self.phase.number(), false)));
+        self.sync_voice_mutes(ctx, Some(true)).await?;
+        let night_end = end_time(self.config.night_duration());
+        // before the first deaths are announced, everyone is alive
+        let alive = self.players.iter().copied().enumerate().filter(|(_, player)| self.alive.as_ref().map_or(true, |alive| alive.contains(player))).collect::<Vec<_>>();
+        self.post_phase_message(ctx, true, alive.iter().map(|&(_, player)| player).collect(), night_end).await?;
+        let night_end = timestamp(night_end);
+        // prompt each living player for night actions, listing the targets by the numbers from the probability table
+        let mut targets = Vec::with_capacity(alive.len());
+        for &(idx, player) in &alive {
+            targets.push((idx + 1, player.to_user(ctx).await?.name));
+        }
+        let prompt = night_prompt(&night_end, &targets);
+        for &(_, player) in &alive {
+            player.create_dm_channel(ctx).await?.say(ctx, &prompt).await?;
+        }
+        Ok(())
+    }
+
+    /// The living players who haven't sent a night action yet, or haven't voted or delegated their vote yet, in player number order.
+    fn inactive_players(&self) -> Vec<UserId> {
+        let mut inactive = match self.state {
+            State::Night(_) => self.players.iter().copied()
+                .filter(|player| self.alive.as_ref().map_or(true, |alive| alive.contains(player)))
+                .filter(|player| !self.night_actions.iter().any(|night_action| night_action.src() == player))
+                .collect(),
+            State::Day(ref day) => self.players.iter().copied()
+                .filter(|player| day.alive().contains(player) && !self.votes.contains_key(player) && !self.delegations.contains_key(player))
+                .collect(),
+            State::Signups(_) | State::Complete(_) => Vec::default(),
+        };
+        inactive.retain(|player| !self.departed.contains(player)); // already removed from the game
+        inactive
+    }
+
+    /// Counts a missed day or night for each of the given inactive players, and removes players who reach [`Config::afk_limit`] from the game.
+    async fn record_inactivity(&mut self, ctx: &Context, inactive: Vec<UserId>) -> Result<(), Error> {
+        for player in inactive {
+            let missed = self.inactivity.entry(player).or_insert(0);
+            *missed += 1;
+            let missed = *missed;
+            if Some(missed) == self.config.afk_limit {
+                let announcement = match self.config.departure {
+                    Departure::Substitute => afk_limit_reached(player, missed, self.config.moderator_role),
+                    Departure::Lynch => afk_limit_reached_lynch(player, missed),
+                };
+                if self.remove_from_game(ctx, player, announcement).await? {
+                    self.removed_for_inactivity.insert(player);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes a living player out of the running game, because they left the server or were inactive for too long, see [`Config::departure`]. Their vote and any votes delegated to them are dropped.
+    ///
+    /// Returns `false` without doing anything if the player isn't alive or was already removed.
+    async fn remove_from_game(&mut self, ctx: &Context, player: UserId, announcement: String) -> Result<bool, Error> {
+        if !self.alive.as_ref().map_or(self.players.contains(&player), |alive| alive.contains(&player)) || !self.departed.insert(player) { return Ok(false) }
+        self.votes.remove(&player);
+        self.delegations.remove(&player);
+        let mut lapsed = self.delegations.iter().filter(|&(_, &delegate)| delegate == player).map(|(&delegator, _)| delegator).collect::<Vec<_>>();
+        lapsed.sort();
+        for delegator in lapsed {
+            self.delegations.remove(&delegator);
+            self.config.vote_channel().say(ctx, delegation_lapsed(delegator, player)).await?;
+        }
+        self.config.text_channel.say(ctx, &announcement).await?;
+        self.spectate(ctx, announcement).await?;
+        Ok(true)
+    }
+
+    /// Whether the player left the server during the running game, so their roles can't be edited anymore.
+    fn left_server(&self, player: UserId) -> bool {
+        self.departed.contains(&player) && !self.removed_for_inactivity.contains(&player)
+    }
+}
+
+impl TypeMapKey for GameState {
+    type Value = HashMap<GameId, GameState>;
+}
+
+#[async_trait]
+impl HiddenRoleGame for GameState {
+    fn id(&self) -> GameId {
+        (self.guild, self.config.text_channel)
+    }
+
+    fn channels(&self) -> Vec<ChannelId> {
+        self.config.channels().collect()
+    }
+
+    fn game_role(&self) -> RoleId {
+        self.config.role
+    }
+
+    fn players(&self) -> &[UserId] {
+        &self.players
+    }
+
+    fn timeouts(&self) -> &Timeouts {
+        &self.timeouts
+    }
+
+    fn timeouts_mut(&mut self) -> &mut Timeouts {
+        &mut self.timeouts
+    }
+
+    /// Writes this game to disk, or removes the file if no game is running or being signed up for.
+    async fn save(&self) -> Result<(), Error> {
+        let path = self.save_path();
+        if let State::Signups(ref signups) = self.state {
+            if signups.num_players() == 0 {
+                return match fs::remove_file(path).await {
+                    Ok(()) => Ok(()),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+                    Err(e) => Err(e.into()),
+                }
+            }
+        }
+        let buf = serde_json::to_vec_pretty(self)?; //TODO use async-json
+        fs::create_dir_all(self.paths.werewolf_games()).await?;
+        atomic_file::write(&path, &buf).await?;
+        Ok(())
+    }
+
+    async fn handle_timeout(&mut self, ctx: &Context) -> Result<Option<Duration>, Error> {
+        handle_timeout(ctx, self).await
+    }
+
+    fn reminder(&self, phase_duration: Duration) -> Option<Duration> {
+        self.config.reminder(phase_duration)
+    }
+
+    /// Reminds the players that the current day or night ends soon.
+    async fn remind(&self, ctx: &Context, remaining: Duration) -> Result<(), Error> {
+        let minutes = (remaining.as_secs() + 59) / 60;
+        match self.state {
+            State::Night(_) => {
+                self.config.text_channel.say(ctx, night_reminder(minutes)).await?;
+            }
+            State::Day(_) => {
+                self.config.vote_channel().say(ctx, day_reminder(minutes)).await?;
+            }
+            State::Signups(_) | State::Complete(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Starts the timer for the current phase, and schedules DMs to inactive players if enabled.
+    fn start_phase_timeout(&mut self, ctx: &Context, phase_duration: Duration) -> usize {
+        let timeout_idx = self.timeouts.start();
+        if let (State::Night(_), Some(delay)) | (State::Day(_), Some(delay)) = (&self.state, self.config.prod(phase_duration)) {
+            let ctx = ctx.clone();
+            let game = self.id();
+            tokio::spawn(async move {
+                if let Err(e) = prod(&ctx, game, timeout_idx, delay).await {
+                    eprintln!("failed to prod inactive werewolf players: {} ({:?})", e, e);
+                }
+            });
+        }
+        timeout_idx
+    }
+}
+
+#[check]
+#[name = "Werwölfe-Channel"]
+async fn channel_check(ctx: &Context, msg: &Message, _: &mut Args, _: &CommandOptions) -> Result<(), Reason> {
+    if let Some(guild_id) = msg.guild_id {
+        let data = ctx.data.read().await;
+        let config = data.get::<crate::config::Config>().expect("missing config").read().await;
+        if !config.feature_enabled(guild_id, crate::config::Feature::Werewolf) {
+            Err(Reason::User(format!("Werwölfe ist auf diesem Server deaktiviert.")))
+        } else if config.werewolf(guild_id, msg.channel_id).map_or(false, |conf| msg.channel_id == conf.text_channel) {
+            Ok(())
+        } else if config.guild(guild_id).map_or(false, |guild_config| !guild_config.werewolf.is_empty()) {
+            Err(Reason::User(format!("Dieser Befehl funktioniert nur in einem Werwölfe-Channel.")))
+        } else {
+            Err(Reason::User(format!("Werwölfe ist auf diesem Server noch nicht eingerichtet.")))
+        }
+    } else {
+        Err(Reason::User(format!("Dieser Befehl funktioniert nur in einem Channel.")))
+    }
+}
+
+#[check]
+#[name = "Werwölfe-Moderation"]
+async fn moderator_check(ctx: &Context, msg: &Message, _: &mut Args, _: &CommandOptions) -> Result<(), Reason> {
+    let guild_id = if let Some(guild_id) = msg.guild_id { guild_id } else { return Err(Reason::User(format!("Dieser Befehl funktioniert nur in einem Channel."))) };
+    if is_moderator(ctx, (guild_id, msg.channel_id), msg.author.id).await.map_err(|e| Reason::Log(format!("failed to get member data: {}", e)))? {
+        Ok(())
+    } else {
+        Err(Reason::User(format!("Dieser Befehl ist nur für die Werwölfe-Moderation.")))
+    }
+}
+
+/// Whether the user has a moderator role for the given game channel or is its game master.
+async fn is_moderator(ctx: &Context, (guild, channel): GameId, user: UserId) -> Result<bool, Error> {
+    let game_master = ctx.data.read().await.get::<GameState>().expect("missing Werewolf game state").get(&(guild, channel)).and_then(|state_ref| state_ref.game_master);
+    if game_master == Some(user) { return Ok(true) }
+    let (moderator_role, mod_roles) = {
+        let config = crate::config::Config::read(ctx).await;
+        (
+            config.werewolf(guild, channel).and_then(|conf| conf.moderator_role),
+            config.guild(guild).map(|guild_config| guild_config.mod_roles.clone()).unwrap_or_default(),
+        )
+    };
+    let member = guild.member(ctx, user).await?;
+    Ok(member.roles.iter().any(|role| Some(*role) == moderator_role || mod_roles.contains(role)))
+}
+
+/// Unmutes everyone in this channel's voice channel, for games played in voice chat.
+#[command("day")]
+#[checks(channel_check)]
+pub async fn command_day(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+    let guild = msg.guild_id.expect("not in channel but check passed");
+    let data = ctx.data.read().await;
+    let conf = *data.get::<crate::config::Config>().expect("missing config").read().await.werewolf(guild, msg.channel_id).expect("unconfigured channel but check passed");
+    if let Some(voice_channel) = conf.voice_channel {
+        let voice_states = data.get::<VoiceStates>().expect("missing voice states map");
+        let VoiceStates(ref chan_map) = voice_states;
+        if let Some((_, users)) = chan_map.get(&voice_channel) {
+            for user in users {
+                guild.edit_member(ctx, user, |m| m.mute(false)).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Signs up for the next game in this channel. The first player to sign up opens signups, see `wwstart`.
+#[command("in")]
+#[aliases("wwjoin")]
+#[checks(channel_check)]
+pub async fn command_in(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+    let game = (msg.guild_id.expect("not in channel but check passed"), msg.channel_id);
+    if let Err(reason) = sign_up(ctx, game, msg.author.id).await? {
+        msg.reply(ctx, reason).await?;
+        return Ok(())
+    }
+    msg.react(ctx, '✅').await?;
+    continue_game(ctx, game).await?;
+    Ok(())
+}
+
+/// Signs the user up for the next game in the given channel, or returns why they can't sign up. The caller should call [`continue_game`] afterwards.
+async fn sign_up(ctx: &Context, (guild, channel): GameId, user: UserId) -> Result<Result<(), &'static str>, Error> {
+    let mut data = ctx.data.write().await;
+    let conf = *data.get::<crate::config::Config>().expect("missing config").read().await.werewolf(guild, channel).expect("unconfigured channel but check passed");
+    let paths = data.get::<Paths>().expect("missing paths").clone();
+    let state = data.get_mut::<GameState>().expect("missing Werewolf game state");
+    if state.iter().any(|(&iter_game, iter_state)| iter_game != (guild, channel) && iter_state.state.secret_ids().map_or(false, |secret_ids| secret_ids.contains(&user))) {
+        return Ok(Err("du bist schon in einem anderen Spiel"))
+    }
+    let state = state.entry((guild, channel)).or_insert_with(|| GameState::new(guild, conf, paths));
+    if state.game_master == Some(user) {
+        return Ok(Err("du bist die Spielleitung und kannst nicht mitspielen"))
+    }
+    if let State::Complete(_) = state.state {
+        state.reset();
+    }
+    if let State::Signups(ref mut signups) = state.state {
+        if conf.max_players().map_or(false, |max_players| signups.num_players() >= max_players) {
+            return Ok(Err("das Spiel ist schon voll"))
+        }
+        let opens_signups = signups.num_players() == 0;
+        // sign up for game
+        if !signups.sign_up(user) {
+            return Ok(Err("du bist schon angemeldet"))
+        }
+        if opens_signups {
+            state.starter = Some(user);
+        }
+        state.add_game_role(ctx, user).await?;
+        Ok(Ok(()))
+    } else {
+        Ok(Err("bitte warte, bis das aktuelle Spiel vorbei ist"))
+    }
+}
+
+/// Closes signups early and starts the game, if enough players have signed up. Only the player who opened signups can do this, moderators can use `wwmod advance` instead.
+#[command]
+#[checks(channel_check)]
+pub async fn wwstart(ctx: &Context, msg: &Message) -> CommandResult {
+    let game = (msg.guild_id.expect("not in channel but check passed"), msg.channel_id);
+    {
+        let mut data = ctx.data.write().await;
+        let state_ref = if let Some(state_ref) = data.get_mut::<GameState>().expect("missing Werewolf game state").get_mut(&game) { state_ref } else {
+            msg.reply(ctx, "aktuell läuft keine Anmeldung").await?;
+            return Ok(())
+        };
+        match state_ref.state {
+            State::Signups(ref signups) if signups.num_players() > 0 => {
+                if let Some(starter) = state_ref.starter.filter(|&starter| starter != msg.author.id) {
+                    msg.reply(ctx, MessageBuilder::default().push("nur ").mention(&starter).push(" kann die Anmeldung vorzeitig schließen")).await?;
+                    return Ok(())
+                }
+                if signups.num_players() < state_ref.config.min_players() {
+                    msg.reply(ctx, "es sind noch nicht genug Spieler angemeldet").await?;
+                    return Ok(())
+                }
+            }
+            State::Signups(_) | State::Night(_) | State::Day(_) | State::Complete(_) => {
+                msg.reply(ctx, "aktuell läuft keine Anmeldung").await?;
+                return Ok(())
+            }
+        }
+        state_ref.timeouts.cancel_all();
+        handle_timeout(ctx, state_ref).await?;
+        state_ref.save().await?;
+    }
+    msg.react(ctx, '✅').await?;
+    continue_game(ctx, game).await?;
+    Ok(())
+}
+
+/// Pauses the running game, stopping the timer of the current phase until `wwresume`. Night actions and votes aren't accepted while the game is paused. Only the player who opened signups and moderators can do this.
+#[command]
+#[checks(channel_check)]
+pub async fn wwpause(ctx: &Context, msg: &Message) -> CommandResult {
+    let game = (msg.guild_id.expect("not in channel but check passed"), msg.channel_id);
+    let is_moderator = is_moderator(ctx, game, msg.author.id).await?;
+    let mut data = ctx.data.write().await;
+    let state_ref = if let Some(state_ref) = data.get_mut::<GameState>().expect("missing Werewolf game state").get_mut(&game) { state_ref } else {
+        msg.reply(ctx, "aktuell läuft kein Spiel").await?;
+        return Ok(())
+    };
+    match state_ref.state {
+        State::Night(_) | State::Day(_) => {}
+        State::Signups(_) | State::Complete(_) => {
+            msg.reply(ctx, "aktuell läuft kein Spiel").await?;
+            return Ok(())
+        }
+    }
+    if !is_moderator && state_ref.starter != Some(msg.author.id) {
+        msg.reply(ctx, "nur die Moderation oder wer die Anmeldung geöffnet hat kann das Spiel pausieren").await?;
+        return Ok(())
+    }
+    if state_ref.paused.is_some() {
+        msg.reply(ctx, "das Spiel ist schon pausiert").await?;
+        return Ok(())
+    }
+    state_ref.timeouts.cancel_all();
+    let end = state_ref.phase_message.as_ref().map_or_else(Utc::now, |phase_message| phase_message.end);
+    state_ref.paused = Some((end - Utc::now()).to_std().unwrap_or_default());
+    state_ref.update_phase_message(ctx, false).await?;
+    state_ref.save().await?;
+    state_ref.config.text_channel.say(ctx, game_paused()).await?;
+    Ok(())
+}
+
+/// Continues a game paused using `wwpause` with the time that was left in the current phase.
+#[command]
+#[checks(channel_check)]
+pub async fn wwresume(ctx: &Context, msg: &Message) -> CommandResult {
+    let game = (msg.guild_id.expect("not in channel but check passed"), msg.channel_id);
+    let is_moderator = is_moderator(ctx, game, msg.author.id).await?;
+    let (timeout_idx, remaining) = {
+        let mut data = ctx.data.write().await;
+        let state_ref = if let Some(state_ref) = data.get_mut::<GameState>().expect("missing Werewolf game state").get_mut(&game) { state_ref } else {
+            msg.reply(ctx, "aktuell läuft kein Spiel").await?;
+            return Ok(())
+        };
+        if !is_moderator && state_ref.starter != Some(msg.author.id) {
+            msg.reply(ctx, "nur die Moderation oder wer die Anmeldung geöffnet hat kann das Spiel fortsetzen").await?;
+            return Ok(())
+        }
+        let remaining = if let Some(remaining) = state_ref.paused.take() { remaining } else {
+            msg.reply(ctx, "das Spiel ist nicht pausiert").await?;
+            return Ok(())
+        };
+        let end = end_time(remaining);
+        if let Some(ref mut phase_message) = state_ref.phase_message {
+            phase_message.end = end;
+        }
+        state_ref.update_phase_message(ctx, false).await?;
+        state_ref.update_vote_message(ctx).await?;
+        let timeout_idx = state_ref.start_phase_timeout(ctx, remaining);
+        state_ref.save().await?;
+        state_ref.config.text_channel.say(ctx, game_unpaused(&timestamp(end))).await?;
+        (timeout_idx, remaining)
+    };
+    games::run_timeouts::<GameState>(ctx, game, timeout_idx, remaining).await?;
+    Ok(())
+}
+
+/// Each player's probabilities of being in the village, being a werewolf, and being dead, in percent, or their faction if they're dead (`None` if roles aren't revealed, see [`Config::reveal_roles`]). Rows are in player number order.
+fn percentages(day: &Day<UserId>, reveal_roles: bool) -> Vec<Result<(u8, u8, u8), Option<Faction>>> {
+    day.probability_table().into_iter().map(|probabilities| probabilities
+        .map(|(village_ratio, werewolves_ratio, dead_ratio)| (
+            (village_ratio * 100.0).round() as u8,
+            (werewolves_ratio * 100.0).round() as u8,
+            (dead_ratio * 100.0).round() as u8,
+        ))
+        .map_err(|faction| Some(faction).filter(|_| reveal_roles))
+    ).collect()
+}
+
+/// Opens signups for a new game with the same settings and role distribution, inviting the players of the last game in this channel to sign up by reacting. Signs you up as well.
+#[command]
+#[checks(channel_check)]
+pub async fn wwrematch(ctx: &Context, msg: &Message) -> CommandResult {
+    let game = (msg.guild_id.expect("not in channel but check passed"), msg.channel_id);
+    {
+        let mut data = ctx.data.write().await;
+        let state_ref = if let Some(state_ref) = data.get_mut::<GameState>().expect("missing Werewolf game state").get_mut(&game) { state_ref } else {
+            msg.reply(ctx, "in diesem Channel wurde noch kein Spiel gespielt").await?;
+            return Ok(())
+        };
+        match state_ref.state {
+            State::Signups(ref signups) if signups.num_players() == 0 => {}
+            State::Complete(_) => {}
+            State::Signups(_) => {
+                msg.reply(ctx, "die Anmeldung läuft schon").await?;
+                return Ok(())
+            }
+            State::Night(_) | State::Day(_) => {
+                msg.reply(ctx, "bitte warte, bis das aktuelle Spiel vorbei ist").await?;
+                return Ok(())
+            }
+        }
+        if !state_ref.last_players.contains(&msg.author.id) {
+            msg.reply(ctx, "nur wer beim letzten Spiel in diesem Channel mitgespielt hat, kann eine Revanche starten").await?;
+            return Ok(())
+        }
+        state_ref.reset();
+        state_ref.rematch = true;
+    }
+    if let Err(reason) = sign_up(ctx, game, msg.author.id).await? {
+        msg.reply(ctx, reason).await?;
+        return Ok(())
+    }
+    continue_game(ctx, game).await?;
+    Ok(())
+}
+
+/// Explains the rules of Quantum Werewolf, or the given role or variant, e.g. `wwhelp Detektiv`.
+#[command]
+#[usage = "[<Rolle|Variante>]"]
+#[example = "Detektiv"]
+pub async fn wwhelp(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let arg = args.rest().trim();
+    let help = if arg.is_empty() {
+        Cow::Owned(rules_help())
+    } else if let Some(role) = parse_role(arg) {
+        Cow::Owned(role_help(role))
+    } else {
+        match &*arg.to_lowercase() {
+            "gerber" | "gerberin" => Cow::Borrowed(tanner_help()),
+            "liebespaar" => Cow::Borrowed(lovers_help()),
+            "amor" => Cow::Borrowed(cupid_help()),
+            "jäger" | "jägerin" => Cow::Borrowed(hunter_help()),
+            _ => {
+                msg.reply(ctx, format!("unbekannte Rolle: {}", arg)).await?;
+                return Ok(())
+            }
+        }
+    };
+    msg.channel_id.say(ctx, help).await?;
+    Ok(())
+}
+
+/// Shows the current probabilities of each player being in the village, being a werewolf, or being dead, across all remaining universes. Only available during the day, since night actions change them.
+#[command]
+#[checks(channel_check)]
+pub async fn wwprob(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild_id.expect("not in channel but check passed");
+    let rows = {
+        let data = ctx.data.read().await;
+        let state_ref = if let Some(state_ref) = data.get::<GameState>().expect("missing Werewolf game state").get(&(guild, msg.channel_id)) { state_ref } else {
+            msg.reply(ctx, "aktuell läuft kein Spiel").await?;
+            return Ok(())
+        };
+        match state_ref.state {
+            State::Day(ref day) => state_ref.players.iter().copied().zip(percentages(day, state_ref.config.reveal_roles)).collect::<Vec<_>>(),
+            State::Night(_) => {
+                msg.reply(ctx, "die Wahrscheinlichkeiten werden erst am Morgen neu berechnet").await?;
+                return Ok(())
+            }
+            State::Signups(_) | State::Complete(_) => {
+                msg.reply(ctx, "aktuell läuft kein Spiel").await?;
+                return Ok(())
+            }
+        }
+    };
+    let mut table = Vec::with_capacity(rows.len());
+    for (player, probabilities) in rows {
+        table.push((player.to_user(ctx).await?.name, probabilities));
+    }
+    msg.channel_id.send_message(ctx, |m| m.embed(|e| e
+        .title(probability_embed_title())
+        .description(probability_embed_table(&table))
+    )).await?;
+    Ok(())
+}
+
+/// Shows or changes the role distribution for games in this channel, e.g. `roles 2 Werwölfe, Detektiv, Heiler`. The variants `Gerber`, `Liebespaar`, `Amor`, and `Jäger` can be added to the list as well, see [`RolePreset`]. Use `roles zufällig` to go back to random distributions.
+///
+/// Changes can be made by moderators or by players signed up for the next game, but not while a game is running.
+#[command("roles")]
+#[usage = "[<Rollen|zufällig>]"]
+#[example = "2 Werwölfe, Detektiv, Heiler"]
+#[example = "zufällig"]
+#[checks(channel_check)]
+pub async fn command_roles(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild = msg.guild_id.expect("not in channel but check passed");
+    let arg = args.rest().trim();
+    if arg.is_empty() {
+        let conf = *crate::config::Config::read(ctx).await.werewolf(guild, msg.channel_id).expect("unconfigured channel but check passed");
+        msg.reply(ctx, match conf.roles {
+            Some(preset) => format!("Rollenverteilung: {} (ab {} Spielern)", preset, preset.min_players()),
+            None => format!("Rollenverteilung: zufällig"),
+        }).await?;
+        return Ok(())
+    }
+    let preset = if arg.eq_ignore_ascii_case("zufällig") || arg.eq_ignore_ascii_case("random") {
+        None
+    } else {
+        match arg.parse::<RolePreset>() {
+            Ok(preset) => Some(preset),
+            Err(e) => {
+                msg.reply(ctx, e).await?;
+                return Ok(())
+            }
+        }
+    };
+    let is_mod = {
+        let mod_roles = crate::config::Config::read(ctx).await.guild(guild).map(|guild_config| guild_config.mod_roles.clone()).unwrap_or_default();
+        guild.member(ctx, msg.author.id).await?.roles.iter().any(|role| mod_roles.contains(role))
+    };
+    {
+        let mut data = ctx.data.write().await;
+        if let Some(state) = data.get_mut::<GameState>().expect("missing Werewolf game state").get_mut(&(guild, msg.channel_id)) {
+            match state.state {
+                State::Signups(_) | State::Complete(_) => {}
+                State::Night(_) | State::Day(_) => {
+                    msg.reply(ctx, "bitte warte, bis das aktuelle Spiel vorbei ist").await?;
+                    return Ok(())
+                }
+            }
+            if !is_mod && !state.state.secret_ids().map_or(false, |secret_ids| secret_ids.contains(&msg.author.id)) {
+                msg.reply(ctx, "nur Moderatoren und angemeldete Spieler können die Rollenverteilung ändern").await?;
+                return Ok(())
+            }
+            state.config.roles = preset; // the config is copied into the game state when the first player signs up
+        } else if !is_mod {
+            msg.reply(ctx, "nur Moderatoren und angemeldete Spieler können die Rollenverteilung ändern").await?;
+            return Ok(())
+        }
+    }
+    let old = crate::config::Config::update(ctx, |config| {
+        let old = serde_json::to_value(config.guild(guild))?;
+        if let Some(conf) = config.guilds.get_mut(&guild).and_then(|guild_config| guild_config.werewolf.iter_mut().find(|conf| conf.text_channel == msg.channel_id)) {
+            conf.roles = preset;
+        }
+        serde_json::Result::Ok(old)
+    }).await??;
+    if let Some(preset) = preset {
+        msg.reply(ctx, format!("Rollenverteilung gespeichert, sie braucht mindestens {} Spieler.", preset.min_players())).await?;
+    } else {
+        msg.react(ctx, '✅').await?;
+    }
+    log_changes(ctx, guild, msg.author.id, &old).await?;
+    Ok(())
+}
+
+/// Shows or changes the settings for games in this channel: `wwconfig` shows them, `wwconfig set <setting> <value>` changes one, e.g. `wwconfig set daySecs 600`, `wwconfig set tieBreak runoff`, `wwconfig set revealRoles false`, or `wwconfig set textChannel #werwölfe` to move the game to another channel. Use `keine` as the value to clear a setting.
+///
+/// Only moderators can change settings, and not while a game is running. Changes made during signups apply to the upcoming game.
+#[command]
+#[checks(channel_check)]
+#[sub_commands(wwconfig_set)]
+pub async fn wwconfig(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+    let guild = msg.guild_id.expect("not in channel but check passed");
+    let json = serde_json::to_value(crate::config::Config::read(ctx).await.werewolf(guild, msg.channel_id).expect("unconfigured channel but check passed"))?;
+    send_json(ctx, msg, &json).await?;
+    Ok(())
+}
+
+/// Changes a setting for games in this channel, see `wwconfig`.
+#[command("set")]
+#[usage = "<Einstellung> <Wert>"]
+#[example = "daySecs 600"]
+#[example = "tieBreak runoff"]
+#[example = "textChannel #werwölfe"]
+#[checks(channel_check, moderator_check)]
+async fn wwconfig_set(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild_id.expect("not in channel but check passed");
+    let game = (guild, msg.channel_id);
+    let key = if let Ok(key) = args.single::<String>() { key } else {
+        msg.reply(ctx, "bitte gib an, welche Einstellung du ändern möchtest").await?;
+        return Ok(())
+    };
+    let input = args.rest().trim();
+    if input.is_empty() {
+        msg.reply(ctx, "bitte gib einen Wert an").await?;
+        return Ok(())
+    }
+    let (conf, new_conf, channel_taken) = {
+        let config = crate::config::Config::read(ctx).await;
+        let conf = *config.werewolf(guild, msg.channel_id).expect("unconfigured channel but check passed");
+        let new_conf = match set_key(&conf, &key, input) {
+            Ok(new_conf) => new_conf,
+            Err(e) => {
+                msg.reply(ctx, e).await?;
+                return Ok(())
+            }
+        };
+        let channel_taken = new_conf.text_channel != conf.text_channel && config.werewolf(guild, new_conf.text_channel).is_some();
+        (conf, new_conf, channel_taken)
+    };
+    if channel_taken {
+        msg.reply(ctx, "in diesem Channel gibt es schon ein Werwölfe-Spiel").await?;
+        return Ok(())
+    }
+    {
+        let mut data = ctx.data.write().await;
+        let games = data.get_mut::<GameState>().expect("missing Werewolf game state");
+        if let Some(state_ref) = games.get_mut(&game) {
+            match state_ref.state {
+                State::Signups(ref signups) if new_conf.text_channel != conf.text_channel && signups.num_players() > 0 => {
+                    msg.reply(ctx, "der Channel kann nicht während der Anmeldung geändert werden").await?;
+                    return Ok(())
+                }
+                State::Signups(_) | State::Complete(_) => {}
+                State::Night(_) | State::Day(_) => {
+                    msg.reply(ctx, "bitte warte, bis das aktuelle Spiel vorbei ist").await?;
+                    return Ok(())
+                }
+            }
+        }
+        if new_conf.text_channel != conf.text_channel {
+            if let Some(mut state) = games.remove(&game) {
+                match fs::remove_file(state.save_path()).await {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+                state.config = new_conf;
+                state.save().await?;
+                games.insert(state.id(), state);
+            }
+        } else if let Some(state_ref) = games.get_mut(&game) {
+            state_ref.config = new_conf; // the config is copied into the game state when the first player signs up
+            state_ref.save().await?;
+        }
+    }
+    let old = crate::config::Config::update(ctx, |config| {
+        let old = serde_json::to_value(config.guild(guild))?;
+        if let Some(conf) = config.guilds.get_mut(&guild).and_then(|guild_config| guild_config.werewolf.iter_mut().find(|conf| conf.text_channel == msg.channel_id)) {
+            *conf = new_conf;
+        }
+        serde_json::Result::Ok(old)
+    }).await??;
+    msg.react(ctx, '✅').await?;
+    log_changes(ctx, guild, msg.author.id, &old).await?;
+    Ok(())
+}
+
+/// Game master commands: `set`, `lynch`, and `say`. The game master of a channel doesn't play, receives all hidden information by DM, and can use the `wwmod` commands.
+#[command]
+#[sub_commands(wwgm_lynch, wwgm_say, wwgm_set)]
+pub async fn wwgm(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+    msg.reply(ctx, "Unterbefehle: `set <Mitglied|none>`, `lynch <Spieler|nolynch|auto>`, `say <Text>`").await?;
+    Ok(())
+}
+
+/// Makes someone the game master for games in this channel, or removes the game master using `wwgm set none`. Moderators can do this between games, the player who opened signups can also do it during signups.
+#[command("set")]
+#[usage = "<Mitglied|none>"]
+#[example = "@Peter"]
+#[example = "none"]
+#[checks(channel_check)]
+async fn wwgm_set(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild = msg.guild_id.expect("not in channel but check passed");
+    let game = (guild, msg.channel_id);
+    let mut rest = args.rest();
+    let game_master = if rest.trim().eq_ignore_ascii_case("none") {
+        None
+    } else if let Some(game_master) = parse::eat_user_mention(&mut rest) {
+        Some(game_master)
+    } else {
+        msg.reply(ctx, "benutze `wwgm set <Mitglied|none>`").await?;
+        return Ok(())
+    };
+    let is_moderator = is_moderator(ctx, game, msg.author.id).await?;
+    let mut data = ctx.data.write().await;
+    let conf = *data.get::<crate::config::Config>().expect("missing config").read().await.werewolf(guild, msg.channel_id).expect("unconfigured channel but check passed");
+    let paths = data.get::<Paths>().expect("missing paths").clone();
+    let state_ref = data.get_mut::<GameState>().expect("missing Werewolf game state").entry(game).or_insert_with(|| GameState::new(guild, conf, paths));
+    match state_ref.state {
+        State::Signups(_) | State::Complete(_) => {}
+        State::Night(_) | State::Day(_) => {
+            msg.reply(ctx, "die Spielleitung kann nur zwischen Spielen geändert werden").await?;
+            return Ok(())
+        }
+    }
+    if !is_moderator && (state_ref.starter != Some(msg.author.id) || state_ref.signed_up().is_empty()) {
+        msg.reply(ctx, "nur die Moderation oder wer die Anmeldung geöffnet hat kann die Spielleitung festlegen").await?;
+        return Ok(())
     }
+    if game_master.map_or(false, |game_master| state_ref.signed_up().contains(&game_master)) {
+        msg.reply(ctx, "die Spielleitung kann nicht mitspielen, bitte melde sie zuerst ab").await?;
+        return Ok(())
+    }
+    state_ref.game_master = game_master;
+    state_ref.save().await?;
+    msg.channel_id.say(ctx, game_master_changed(game_master)).await?;
+    Ok(())
+}
 
-    async fn start_day(&self, ctx: &Context, day: &Day<UserId>) -> Result<(), Error> {
-        // announce probability table
-        let mut builder = MessageBuilder::default();
-        builder.push("Die aktuelle Wahrscheinlichkeitsverteilung:");
-        for (player_idx, probabilities) in day.probability_table().into_iter().enumerate() {
-            builder.push_line("").push_safe(match probabilities {
-                Ok((village_ratio, werewolves_ratio, dead_ratio)) => {
-                    format!("{}: {}% Dorf, {}% Werwolf, {}% tot", player_idx + 1, (village_ratio * 100.0).round() as u8, (werewolves_ratio * 100.0).round() as u8, (dead_ratio * 100.0).round() as u8)
-                }
-                Err(faction) => {
-                    format!("{}: tot (war {})", player_idx + 1, faction_name_sg(faction, Nom))
-                }
-            });
+/// Decides the outcome of the current day instead of the vote, e.g. `wwgm lynch @player`, `wwgm lynch nolynch`, or `wwgm lynch auto` to go back to the vote result. The decision is announced when the day ends. Best sent by DM.
+#[command("lynch")]
+#[usage = "<Spieler|nolynch|auto>"]
+#[example = "@Peter"]
+#[example = "nolynch"]
+async fn wwgm_lynch(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    if !msg.is_private() {
+        // don't reveal the decision before the day ends
+        msg.delete(ctx).await?;
+    }
+    let mut rest = args.rest();
+    let decision = match &*rest.trim().to_ascii_lowercase() {
+        "auto" => Some(None),
+        "nolynch" | "no lynch" | "nl" => Some(Some(Vote::NoLynch)),
+        _ => parse::eat_user_mention(&mut rest).map(|target| Some(Vote::Player(target))),
+    };
+    let decision = if let Some(decision) = decision { decision } else {
+        msg.author.create_dm_channel(ctx).await?.say(ctx, "benutze `wwgm lynch <Spieler|nolynch|auto>`").await?;
+        return Ok(())
+    };
+    let mut data = ctx.data.write().await;
+    let state_ref = match game_master_game(data.get_mut::<GameState>().expect("missing Werewolf game state"), msg.author.id) {
+        Ok(state_ref) => state_ref,
+        Err(reason) => {
+            msg.author.create_dm_channel(ctx).await?.say(ctx, reason).await?;
+            return Ok(())
         }
-        self.config.text_channel.say(ctx, builder).await?;
-        // open discussion
-        self.config.text_channel.create_permission(ctx, &PermissionOverwrite {
-            kind: PermissionOverwriteType::Role(self.config.role),
-            allow: Permissions::SEND_MESSAGES | Permissions::ADD_REACTIONS,
-            deny: Permissions::empty(),
-        }).await?;
-        let lynch_votes = day.alive().len() / 2 + 1;
-        let mut builder = MessageBuilder::default();
-        builder.push("Es wird Tag. Die Diskussion ist eröffnet. Absolute Mehrheit besteht aus ");
-        builder.push_safe(cardinal(lynch_votes, Dat, F));
-        builder.push(if lynch_votes == 1 { " Stimme." } else { " Stimmen." });
-        self.config.text_channel.say(ctx, builder).await?;
-        Ok(())
+    };
+    if let State::Day(ref day) = state_ref.state {
+        if let Some(Vote::Player(target)) = decision {
+            if !day.alive().contains(&target) {
+                msg.author.create_dm_channel(ctx).await?.say(ctx, "das Ziel lebt nicht").await?;
+                return Ok(())
+            }
+        }
+    } else {
+        msg.author.create_dm_channel(ctx).await?.say(ctx, "aktuell ist nicht Tag").await?;
+        return Ok(())
     }
-
-    async fn start_night(&self, ctx: &Context, _: &Night<UserId>) -> Result<(), Error> {
-        self.config.text_channel.say(ctx, "Es wird Nacht. Bitte schickt mir innerhalb der nächsten 3 Minuten eure Nachtaktionen.").await?; //TODO adjust for night timeout changes
-        Ok(())
+    state_ref.game_master_lynch = decision;
+    state_ref.log.push(LogEntry::now(game_master_lynch_log(decision.map(|vote| match vote {
+        Vote::Player(player) => Some(player),
+        Vote::NoLynch => None,
+    }))));
+    state_ref.save().await?;
+    if msg.is_private() {
+        msg.react(ctx, '✅').await?;
     }
+    Ok(())
+}
 
-    fn start_timeout(&mut self) -> usize {
-        let idx = self.timeouts.len();
-        self.timeouts.push(true);
-        idx
+/// Posts narration by the game master in the game channel, e.g. `wwgm say Der Nebel lichtet sich…`.
+#[command("say")]
+#[usage = "<Text>"]
+#[example = "Der Nebel lichtet sich…"]
+async fn wwgm_say(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let text = args.rest().trim();
+    if text.is_empty() {
+        msg.reply(ctx, "benutze `wwgm say <Text>`").await?;
+        return Ok(())
     }
-
-    fn timeout_cancelled(&self, timeout_idx: usize) -> bool {
-        !self.timeouts[timeout_idx]
+    let mut data = ctx.data.write().await;
+    let state_ref = match game_master_game(data.get_mut::<GameState>().expect("missing Werewolf game state"), msg.author.id) {
+        Ok(state_ref) => state_ref,
+        Err(reason) => {
+            msg.reply(ctx, reason).await?;
+            return Ok(())
+        }
+    };
+    let announcement = narration(text);
+    state_ref.config.text_channel.say(ctx, &announcement).await?;
+    state_ref.log.push(LogEntry::now(announcement));
+    state_ref.save().await?;
+    if msg.is_private() {
+        msg.react(ctx, '✅').await?;
+    } else if msg.channel_id == state_ref.config.text_channel {
+        msg.delete(ctx).await?;
     }
+    Ok(())
+}
 
-    fn timeouts_active(&self) -> bool {
-        self.timeouts.iter().any(|&active| active)
-    }
+/// The running game led by the given game master.
+fn game_master_game(games: &mut HashMap<GameId, GameState>, game_master: UserId) -> Result<&mut GameState, &'static str> {
+    games.values_mut()
+        .filter(|state_ref| state_ref.game_master == Some(game_master) && matches!(state_ref.state, State::Night(_) | State::Day(_)))
+        .exactly_one()
+        .map_err(|_| "du leitest kein laufendes Spiel oder mehrere gleichzeitig")
 }
 
-impl TypeMapKey for GameState {
-    type Value = HashMap<GuildId, GameState>;
+/// Moderator commands for the game in this channel: `advance`, `remove`, `vote`, and `cancel`.
+#[command]
+#[checks(channel_check, moderator_check)]
+#[sub_commands(wwmod_advance, wwmod_cancel, wwmod_remove, wwmod_vote)]
+pub async fn wwmod(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+    msg.reply(ctx, "Unterbefehle: `advance`, `remove <Spieler>`, `vote <Spieler> <Ziel|nolynch|none>`, `cancel`").await?;
+    Ok(())
 }
 
-#[check]
-#[name = "channel_check"]
-async fn channel_check(ctx: &Context, msg: &Message, _: &mut Args, _: &CommandOptions) -> Result<(), Reason> {
-    if let Some(guild_id) = msg.guild_id {
-        if let Some(conf) = ctx.data.read().await.get::<crate::config::Config>().expect("missing config").werewolf.get(&guild_id) {
-            if msg.channel_id == conf.text_channel {
-                Ok(())
-            } else {
-                Err(Reason::User(format!("Dieser Befehl funktioniert nur im Werwölfe-Channel.")))
+/// Ends the current signup period, day, or night immediately, as if its time had run out.
+#[command("advance")]
+#[checks(channel_check, moderator_check)]
+async fn wwmod_advance(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+    let game = (msg.guild_id.expect("not in channel but check passed"), msg.channel_id);
+    {
+        let mut data = ctx.data.write().await;
+        let state_ref = if let Some(state_ref) = data.get_mut::<GameState>().expect("missing Werewolf game state").get_mut(&game) { state_ref } else {
+            msg.reply(ctx, "aktuell läuft kein Spiel").await?;
+            return Ok(())
+        };
+        match state_ref.state {
+            State::Signups(ref signups) if signups.num_players() < state_ref.config.min_players() => {
+                msg.reply(ctx, "es sind noch nicht genug Spieler angemeldet").await?;
+                return Ok(())
             }
-        } else {
-            Err(Reason::User(format!("Werwölfe ist auf diesem Server noch nicht eingerichtet.")))
+            State::Complete(_) => {
+                msg.reply(ctx, "aktuell läuft kein Spiel").await?;
+                return Ok(())
+            }
+            State::Signups(_) | State::Night(_) | State::Day(_) => {}
         }
-    } else {
-        Err(Reason::User(format!("Dieser Befehl funktioniert nur in einem Channel.")))
+        if state_ref.paused.is_some() {
+            msg.reply(ctx, "das Spiel ist pausiert, bitte setze es zuerst mit `wwresume` fort").await?;
+            return Ok(())
+        }
+        state_ref.timeouts.cancel_all();
+        handle_timeout(ctx, state_ref).await?;
+        state_ref.save().await?;
     }
+    msg.react(ctx, '✅').await?;
+    continue_game(ctx, game).await?;
+    Ok(())
 }
 
-#[command("day")]
-#[checks(channel_check)]
-pub async fn command_day(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+/// Cancels the game in this channel without a winner. It doesn't count towards seasons or statistics.
+#[command("cancel")]
+#[checks(channel_check, moderator_check)]
+async fn wwmod_cancel(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+    let game = (msg.guild_id.expect("not in channel but check passed"), msg.channel_id);
+    let mut data = ctx.data.write().await;
+    let state_ref = if let Some(state_ref) = data.get_mut::<GameState>().expect("missing Werewolf game state").get_mut(&game) { state_ref } else {
+        msg.reply(ctx, "aktuell läuft kein Spiel").await?;
+        return Ok(())
+    };
+    state_ref.timeouts.cancel_all();
+    let signed_up = state_ref.signed_up();
+    state_ref.close_signup_message(ctx, &signed_up).await?;
+    match state_ref.state {
+        State::Night(_) | State::Day(_) => state_ref.clean_up(ctx).await?,
+        State::Signups(_) | State::Complete(_) => state_ref.remove_game_role(ctx).await?,
+    }
+    state_ref.close_phase_message(ctx).await?;
+    let seed = state_ref.seed.take();
+    state_ref.clear_game();
+    state_ref.sync_voice_mutes(ctx, None).await?;
+    state_ref.save().await?;
+    state_ref.config.text_channel.say(ctx, game_cancelled()).await?;
+    if let Some(seed) = seed {
+        state_ref.config.text_channel.say(ctx, seed_revealed(&hex::encode(seed), &seed_commitment(&seed))).await?;
+    }
+    Ok(())
+}
+
+/// Removes a player who signed up for the next game. Players can't be removed from a running game, use `wwmod cancel` instead if necessary.
+#[command("remove")]
+#[usage = "<Spieler>"]
+#[example = "@Peter"]
+#[checks(channel_check, moderator_check)]
+async fn wwmod_remove(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let guild = msg.guild_id.expect("not in channel but check passed");
-    let data = ctx.data.read().await;
-    let conf = *data.get::<crate::config::Config>().expect("missing config").werewolf.get(&guild).expect("unconfigured guild but check passed");
-    if let Some(voice_channel) = conf.voice_channel {
-        let voice_states = data.get::<VoiceStates>().expect("missing voice states map");
-        let VoiceStates(ref chan_map) = voice_states;
-        if let Some((_, users)) = chan_map.get(&voice_channel) {
-            for user in users {
-                guild.edit_member(ctx, user, |m| m.mute(false)).await?;
+    let mut rest = args.rest();
+    let player = if let Some(player) = parse::eat_user_mention(&mut rest) { player } else {
+        msg.reply(ctx, "bitte erwähne den Spieler, der entfernt werden soll").await?;
+        return Ok(())
+    };
+    {
+        let mut data = ctx.data.write().await;
+        let state_ref = if let Some(state_ref) = data.get_mut::<GameState>().expect("missing Werewolf game state").get_mut(&(guild, msg.channel_id)) { state_ref } else {
+            msg.reply(ctx, "aktuell läuft kein Spiel").await?;
+            return Ok(())
+        };
+        if let State::Signups(ref mut signups) = state_ref.state {
+            if !signups.remove_player(&player) {
+                msg.reply(ctx, "dieser Spieler ist nicht angemeldet").await?;
+                return Ok(())
             }
+        } else {
+            msg.reply(ctx, "Spieler können nur während der Anmeldung entfernt werden").await?;
+            return Ok(())
         }
+        state_ref.remove_game_role_from(ctx, player).await?;
     }
+    msg.react(ctx, '✅').await?;
+    continue_game(ctx, (guild, msg.channel_id)).await?;
     Ok(())
 }
 
-#[command("in")]
-#[checks(channel_check)]
-pub async fn command_in(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
-    let guild = msg.guild_id.expect("not in channel but check passed");
+/// Sets or removes a player's vote for the current day, e.g. `wwmod vote @player @target`, `wwmod vote @player nolynch`, or `wwmod vote @player none`.
+#[command("vote")]
+#[usage = "<Spieler> <Ziel|nolynch|none>"]
+#[example = "@Peter @Paul"]
+#[example = "@Peter none"]
+#[checks(channel_check, moderator_check)]
+async fn wwmod_vote(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let game = (msg.guild_id.expect("not in channel but check passed"), msg.channel_id);
+    let mut rest = args.rest();
+    let voter = parse::eat_user_mention(&mut rest);
+    parse::eat_whitespace(&mut rest);
+    let vote = match &*rest.trim().to_ascii_lowercase() {
+        "none" => Some(None),
+        "nolynch" | "no lynch" | "nl" => Some(Some(Vote::NoLynch)),
+        _ => parse::eat_user_mention(&mut rest).map(|target| Some(Vote::Player(target))),
+    };
+    let (voter, vote) = if let (Some(voter), Some(vote)) = (voter, vote) { (voter, vote) } else {
+        msg.reply(ctx, "benutze `wwmod vote <Spieler> <Ziel|nolynch|none>`").await?;
+        return Ok(())
+    };
     {
         let mut data = ctx.data.write().await;
-        let conf = *data.get::<crate::config::Config>().expect("missing config").werewolf.get(&guild).expect("unconfigured guild but check passed");
-        let state = data.get_mut::<GameState>().expect("missing Werewolf game state");
-        if state.iter().any(|(&iter_guild, iter_state)| iter_guild != guild && iter_state.state.secret_ids().map_or(false, |secret_ids| secret_ids.contains(&msg.author.id))) {
-            msg.reply(&ctx, "du bist schon in einem Spiel auf einem anderen Server").await?;
+        let state_ref = if let Some(state_ref) = data.get_mut::<GameState>().expect("missing Werewolf game state").get_mut(&game) { state_ref } else {
+            msg.reply(ctx, "aktuell läuft kein Spiel").await?;
             return Ok(())
-        }
-        let state = state.entry(guild).or_insert_with(|| GameState::new(guild, conf));
-        if let State::Complete(_) = state.state {
-            state.state = State::default();
-        }
-        if let State::Signups(ref mut signups) = state.state {
-            // sign up for game
-            if !signups.sign_up(msg.author.id) {
-                msg.reply(&ctx, "du bist schon angemeldet").await?;
+        };
+        if let State::Day(ref day) = state_ref.state {
+            if !day.alive().contains(&voter) {
+                msg.reply(ctx, "dieser Spieler lebt nicht").await?;
+                return Ok(())
+            }
+            if let Some(Vote::Player(target)) = vote {
+                if !day.alive().contains(&target) {
+                    msg.reply(ctx, "das Ziel lebt nicht").await?;
+                    return Ok(())
+                }
+            }
+            if vote.map_or(false, |vote| !state_ref.is_vote_option(vote)) {
+                msg.reply(ctx, "das steht aktuell nicht zur Wahl").await?;
                 return Ok(())
             }
-            // add DISCUSSION_ROLE
-            let roles = iter::once(conf.role).chain(guild.member(&ctx, msg.author.clone()).await?.roles.into_iter());
-            guild.edit_member(&ctx, msg.author.clone(), |m| m.roles(roles)).await?;
-            msg.react(&ctx, '✅').await?;
         } else {
-            msg.reply(&ctx, "bitte warte, bis das aktuelle Spiel vorbei ist").await?;
+            msg.reply(ctx, "aktuell läuft keine Abstimmung").await?;
+            return Ok(())
+        }
+        if let Some(vote) = vote {
+            state_ref.delegations.remove(&voter);
+            state_ref.votes.insert(voter, vote);
+        } else {
+            state_ref.votes.remove(&voter);
+        }
+        state_ref.config.vote_channel().say(ctx, vote_changed_by_moderator(voter)).await?;
+    }
+    msg.react(ctx, '✅').await?;
+    continue_game(ctx, game).await?;
+    Ok(())
+}
+
+/// Replaces a player who has to leave a running game, e.g. `wwreplace @leaving @substitute`. The substitute takes over the player's role, night actions, and vote, and is sent the role DM.
+#[command]
+#[usage = "<ausscheidender Spieler> <Ersatz>"]
+#[example = "@Peter @Paul"]
+#[checks(channel_check, moderator_check)]
+pub async fn wwreplace(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild = msg.guild_id.expect("not in channel but check passed");
+    let game = (guild, msg.channel_id);
+    let mut rest = args.rest();
+    let leaving = parse::eat_user_mention(&mut rest);
+    parse::eat_whitespace(&mut rest);
+    let substitute = parse::eat_user_mention(&mut rest);
+    let (leaving, substitute) = if let (Some(leaving), Some(substitute)) = (leaving, substitute) { (leaving, substitute) } else {
+        msg.reply(ctx, "benutze `wwreplace <ausscheidender Spieler> <Ersatz>`").await?;
+        return Ok(())
+    };
+    let mut data = ctx.data.write().await;
+    let games = data.get_mut::<GameState>().expect("missing Werewolf game state");
+    if games.values().any(|state| state.state.secret_ids().map_or(false, |secret_ids| secret_ids.contains(&substitute))) {
+        msg.reply(ctx, "der Ersatz ist schon in einem Spiel").await?;
+        return Ok(())
+    }
+    let state_ref = if let Some(state_ref) = games.get_mut(&game) { state_ref } else {
+        msg.reply(ctx, "aktuell läuft kein Spiel").await?;
+        return Ok(())
+    };
+    match state_ref.state {
+        State::Night(_) | State::Day(_) => {}
+        State::Signups(_) | State::Complete(_) => {
+            msg.reply(ctx, "aktuell läuft kein Spiel, ausscheidende Spieler können sich mit `out` abmelden").await?;
             return Ok(())
         }
     }
-    continue_game(&ctx, guild).await?;
+    if state_ref.game_master == Some(substitute) {
+        msg.reply(ctx, "die Spielleitung kann nicht mitspielen").await?;
+        return Ok(())
+    }
+    if !state_ref.alive.as_ref().map_or(state_ref.players.contains(&leaving), |alive| alive.contains(&leaving)) {
+        msg.reply(ctx, "dieser Spieler lebt nicht oder spielt nicht mit").await?;
+        return Ok(())
+    }
+    let left_server = state_ref.left_server(leaving);
+    state_ref.substitute(leaving, substitute)?;
+    state_ref.departed.remove(&substitute);
+    state_ref.removed_for_inactivity.remove(&substitute);
+    state_ref.save().await?;
+    // hand over the game role and spectator channel permissions
+    if !left_server {
+        state_ref.remove_game_role_from(ctx, leaving).await?;
+    }
+    state_ref.add_game_role(ctx, substitute).await?;
+    if let Some(spectator_channel) = state_ref.spectator_channel() {
+        spectator_channel.delete_permission(ctx, PermissionOverwriteType::Member(leaving)).await?;
+        spectator_channel.create_permission(ctx, &PermissionOverwrite {
+            kind: PermissionOverwriteType::Member(substitute),
+            allow: Permissions::empty(),
+            deny: Permissions::READ_MESSAGES,
+        }).await?;
+    }
+    let secret_id = state_ref.players.iter().position(|&player| player == substitute).expect("substitute not in player list");
+    let dm = quantum_role_dm(&state_ref.roles, state_ref.players.len(), secret_id);
+    substitute.create_dm_channel(ctx).await?.say(ctx, substitute_dm(leaving)).await?;
+    substitute.create_dm_channel(ctx).await?.say(ctx, &dm).await?;
+    state_ref.send_variant_dms(ctx, substitute).await?;
+    let announcement = substitute_announcement(substitute, leaving);
+    state_ref.config.text_channel.say(ctx, &announcement).await?;
+    state_ref.spectate(ctx, announcement).await?;
     Ok(())
 }
 
+/// Mutes everyone but you in this channel's voice channel, for games played in voice chat.
 #[command("night")]
 #[checks(channel_check)]
 pub async fn command_night(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
     let guild = msg.guild_id.expect("not in channel but check passed");
     let data = ctx.data.read().await;
-    let conf = *data.get::<crate::config::Config>().expect("missing config").werewolf.get(&guild).expect("unconfigured guild but check passed");
+    let conf = *data.get::<crate::config::Config>().expect("missing config").read().await.werewolf(guild, msg.channel_id).expect("unconfigured channel but check passed");
     if let Some(voice_channel) = conf.voice_channel {
         let voice_states = data.get::<VoiceStates>().expect("missing voice states map");
         let VoiceStates(ref chan_map) = voice_states;
@@ -355,60 +2592,195 @@ pub async fn command_night(ctx: &Context, msg: &Message, _: Args) -> CommandResu
     Ok(())
 }
 
+/// Withdraws your signup for the next game in this channel.
 #[command("out")]
 #[checks(channel_check)]
 pub async fn command_out(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
-    let guild = msg.guild_id.expect("not in channel but check passed");
-    {
+    let game = (msg.guild_id.expect("not in channel but check passed"), msg.channel_id);
+    if let Err(reason) = sign_out(ctx, game, msg.author.id).await? {
+        msg.reply(ctx, reason).await?;
+        return Ok(())
+    }
+    msg.react(ctx, '✅').await?;
+    continue_game(ctx, game).await?;
+    Ok(())
+}
+
+/// Withdraws the user's signup for the next game in the given channel, or returns why they can't. The caller should call [`continue_game`] afterwards.
+async fn sign_out(ctx: &Context, (guild, channel): GameId, user: UserId) -> Result<Result<(), &'static str>, Error> {
+    let mut data = ctx.data.write().await;
+    let conf = *data.get::<crate::config::Config>().expect("missing config").read().await.werewolf(guild, channel).expect("unconfigured channel but check passed");
+    let paths = data.get::<Paths>().expect("missing paths").clone();
+    let state = data.get_mut::<GameState>().expect("missing Werewolf game state").entry((guild, channel)).or_insert_with(|| GameState::new(guild, conf, paths));
+    if let State::Complete(_) = state.state {
+        state.reset();
+    }
+    if let State::Signups(ref mut signups) = state.state {
+        if !signups.remove_player(&user) {
+            return Ok(Err("du warst nicht angemeldet"))
+        }
+        state.remove_game_role_from(ctx, user).await?;
+        Ok(Ok(()))
+    } else {
+        Ok(Err("bitte warte, bis das aktuelle Spiel vorbei ist")) //TODO implement forfeiting
+    }
+}
+
+async fn continue_game(ctx: &Context, game: GameId) -> Result<(), Error> {
+    let (timeout_idx, sleep_duration) = {
         let mut data = ctx.data.write().await;
-        let conf = *data.get::<crate::config::Config>().expect("missing config").werewolf.get(&guild).expect("unconfigured guild but check passed");
-        let state = data.get_mut::<GameState>().expect("missing Werewolf game state").entry(guild).or_insert_with(|| GameState::new(guild, conf));
-        if let State::Complete(_) = state.state {
-            state.state = State::default();
-        }
-        if let State::Signups(ref mut signups) = state.state {
-            if !signups.remove_player(&msg.author.id) {
-                msg.reply(&ctx, "du warst nicht angemeldet").await?;
-                return Ok(())
-            }
-            // remove DISCUSSION_ROLE
-            let roles = guild.member(&ctx, msg.author.clone()).await?.roles.into_iter().filter(|&role| role != conf.role);
-            guild.edit_member(&ctx, msg.author.clone(), |m| m.roles(roles)).await?;
-            msg.react(&ctx, '✅').await?;
+        let state_ref = data.get_mut::<GameState>().expect("missing Werewolf game state").get_mut(&game).expect("tried to continue game that hasn't started");
+        if state_ref.paused.is_some() {
+            // the game continues when it's resumed, see `wwresume`
+            state_ref.update_vote_message(ctx).await?;
+            state_ref.save().await?;
+            return Ok(())
+        }
+        let duration = handle_game_state(ctx, state_ref).await?;
+        state_ref.save().await?;
+        if let Some(duration) = duration {
+            if state_ref.timeouts.any_active() { return Ok(()) }
+            (state_ref.start_phase_timeout(ctx, duration), duration)
         } else {
-            msg.reply(&ctx, "bitte warte, bis das aktuelle Spiel vorbei ist").await?; //TODO implement forfeiting
             return Ok(())
         }
+    };
+    games::run_timeouts::<GameState>(ctx, game, timeout_idx, sleep_duration).await
+}
+
+/// DMs the players who haven't acted yet, unless the phase has ended in the meantime.
+async fn prod(ctx: &Context, game: GameId, timeout_idx: usize, delay: Duration) -> Result<(), Error> {
+    sleep(delay).await;
+    let data = ctx.data.read().await;
+    let state_ref = if let Some(state_ref) = data.get::<GameState>().expect("missing Werewolf game state").get(&game) { state_ref } else { return Ok(()) };
+    if state_ref.timeouts.is_cancelled(timeout_idx) { return Ok(()) }
+    let night = if let State::Night(_) = state_ref.state { true } else { false };
+    for player in state_ref.inactive_players() {
+        player.create_dm_channel(ctx).await?.say(ctx, prod_dm(night, if state_ref.config.anonymous_votes { None } else { Some(state_ref.config.vote_channel()) })).await?;
     }
-    continue_game(&ctx, guild).await?;
     Ok(())
 }
 
-async fn continue_game(ctx: &Context, guild: GuildId) -> Result<(), Error> {
-    let (mut timeout_idx, mut sleep_duration) = {
+/// Continues the games restored by [`GameState::load_all`] once the bot is connected.
+///
+/// The timer of the current phase or signup period is restarted, since the time spent offline shouldn't count against the players.
+pub async fn resume(ctx_fut: RwFuture<Context>) -> Result<(), Error> {
+    let ctx = ctx_fut.read().await;
+    let games = {
+        let mut data = (*ctx).data.write().await;
+        let mut games = Vec::default();
+        for (&game, state) in data.get_mut::<GameState>().expect("missing Werewolf game state") {
+            state.close_last_words_window(&*ctx).await?; // its timer didn't survive the restart
+            if state.paused.is_some() { continue } // stays paused until `wwresume`
+            let phase_end = match state.state {
+                State::Signups(ref signups) if signups.num_players() > 0 => Some(end_time(state.config.signup_duration())),
+                State::Signups(_) | State::Complete(_) => continue,
+                State::Night(_) => Some(end_time(state.config.night_duration())),
+                State::Day(_) => Some(end_time(if state.runoff.is_some() { state.config.runoff_duration() } else { state.config.day_duration() })),
+            };
+            if let Some(phase_end) = phase_end {
+                if let Some(ref mut signup_message) = state.signup_message {
+                    signup_message.end = phase_end;
+                }
+                if let Some(ref mut phase_message) = state.phase_message {
+                    phase_message.end = phase_end;
+                }
+            }
+            if let State::Signups(_) = state.state {
+                state.update_signup_message(&*ctx).await?;
+            } else {
+                state.update_phase_message(&*ctx, false).await?;
+            }
+            state.config.text_channel.say(&*ctx, game_resumed(phase_end.map(timestamp).as_deref())).await?;
+            games.push(game);
+        }
+        games
+    };
+    future::try_join_all(games.into_iter().map(|game| continue_game(&*ctx, game))).await?;
+    Ok(())
+}
+
+/// Registers a message which is posted in the discussion channel when you die, e.g. `wwlastwords Ich war's nicht!`. Send this by DM to keep it secret. Use `wwlastwords` without a message to remove it.
+#[command]
+#[aliases("letzteswort")]
+#[usage = "[<Nachricht>]"]
+#[example = "Ich war's nicht!"]
+pub async fn wwlastwords(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    if !msg.is_private() {
+        msg.delete(ctx).await?;
+    }
+    let text = args.rest().trim();
+    let reply = {
         let mut data = ctx.data.write().await;
-        let state_ref = data.get_mut::<GameState>().expect("missing Werewolf game state").get_mut(&guild).expect("tried to continue game that hasn't started");
-        if let Some(duration) = handle_game_state(ctx, state_ref).await? {
-            if state_ref.timeouts_active() { return Ok(()) }
-            (state_ref.start_timeout(), duration)
-        } else {
-            return Ok(())
+        let state_ref = data.get_mut::<GameState>().expect("missing Werewolf game state").values_mut()
+            .filter(|state_ref| state_ref.alive.as_ref().map_or(false, |alive| alive.contains(&msg.author.id)) && matches!(state_ref.state, State::Night(_) | State::Day(_)))
+            .exactly_one();
+        match state_ref {
+            Ok(state_ref) => {
+                if text.is_empty() {
+                    state_ref.last_words.remove(&msg.author.id);
+                } else {
+                    state_ref.last_words.insert(msg.author.id, text.to_owned());
+                }
+                state_ref.save().await?;
+                last_words_saved(!text.is_empty())
+            }
+            Err(_) => "du lebst in keinem laufenden Spiel oder bist in mehreren Spielen gleichzeitig",
         }
     };
-    loop {
-        sleep(sleep_duration).await;
+    msg.author.create_dm_channel(ctx).await?.say(ctx, reply).await?;
+    Ok(())
+}
+
+/// Handles messages in game channels which aren't game actions: A lynched player's last message closes their window for posting it, see [`Config::last_words_secs`].
+pub async fn handle_message(ctx: &Context, msg: &Message) -> Result<(), Error> {
+    let mut data = ctx.data.write().await;
+    for state_ref in data.get_mut::<GameState>().expect("missing Werewolf game state").values_mut() {
+        if state_ref.config.text_channel == msg.channel_id && state_ref.last_words_window == Some(msg.author.id) {
+            state_ref.close_last_words_window(ctx).await?;
+            state_ref.log.push(LogEntry::now(last_words(msg.author.id, &msg.content)));
+            state_ref.save().await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles a member leaving the server: They're removed from signups, and if they're playing, [`Config::departure`] decides what happens to them. If they're a game master, the channel continues without one.
+pub async fn handle_member_removal(ctx: &Context, guild: GuildId, user: UserId) -> Result<(), Error> {
+    let mut changed = Vec::default();
+    {
         let mut data = ctx.data.write().await;
-        let state_ref = data.get_mut::<GameState>().expect("missing Werewolf game state").get_mut(&guild).expect("tried to continue game that hasn't started");
-        if state_ref.timeout_cancelled(timeout_idx) { break }
-        state_ref.cancel_timeout(timeout_idx);
-        if let Some(duration) = handle_timeout(ctx, state_ref).await? {
-            if state_ref.timeouts_active() { break }
-            timeout_idx = state_ref.start_timeout();
-            sleep_duration = duration;
-        } else {
-            break
+        for (&game, state_ref) in data.get_mut::<GameState>().expect("missing Werewolf game state") {
+            let (game_guild, _) = game;
+            if game_guild != guild { continue }
+            if state_ref.game_master == Some(user) {
+                state_ref.game_master = None;
+                state_ref.game_master_lynch = None;
+                state_ref.config.text_channel.say(ctx, game_master_changed(None)).await?;
+            }
+            match state_ref.state {
+                State::Signups(ref mut signups) => if signups.remove_player(&user) {
+                    changed.push(game);
+                },
+                State::Night(_) | State::Day(_) => if state_ref.removed_for_inactivity.remove(&user) {
+                    // already out of the game, but now their roles can't be edited anymore
+                } else {
+                    let announcement = match state_ref.config.departure {
+                        Departure::Substitute => player_left(user, state_ref.config.moderator_role),
+                        Departure::Lynch => player_left_lynch(user),
+                    };
+                    if state_ref.remove_from_game(ctx, user, announcement).await? {
+                        changed.push(game);
+                    }
+                },
+                State::Complete(_) => {}
+            }
+            state_ref.save().await?;
         }
     }
+    for game in changed {
+        continue_game(ctx, game).await?;
+    }
     Ok(())
 }
 
@@ -418,42 +2790,89 @@ async fn continue_game(ctx: &Context, guild: GuildId) -> Result<(), Error> {
 ///
 /// A return value of `Error::GameAction` indicates an invalid action. Other return values are internal errors.
 pub async fn handle_action(ctx: &Context, msg: &Message, action: Action) -> Result<(), Error> {
-    let guild = {
+    if action.is_secret() && !msg.is_private() {
+        // don't leave night actions visible to the other players
+        msg.delete(ctx).await?;
+        msg.author.create_dm_channel(ctx).await?.say(ctx, "Bitte schick mir Nachtaktionen und andere geheime Aktionen nur per DM.").await?;
+        return Ok(())
+    }
+    let game = {
         let mut data = ctx.data.write().await;
-        let (guild, state_ref) = data
+        let (game, state_ref) = data
             .get_mut::<GameState>()
             .expect("missing Werewolf game state")
             .iter_mut()
             .filter(|(_, state)| state.state.secret_ids().map_or(false, |secret_ids| secret_ids.contains(&action.src())))
             .exactly_one()
             .map_err(|_| Error::GameAction("du spielst nicht mit oder bist in mehreren Spielen gleichzeitig".into()))?;
+        if state_ref.paused.is_some() { return Err(Error::GameAction("das Spiel ist pausiert".into())) }
+        if state_ref.departed.contains(&action.src()) { return Err(Error::GameAction("du bist aus dem Spiel ausgeschieden".into())) }
+        let mut couple = None;
         match state_ref.state {
             State::Night(ref night) => {
                 match action {
                     Action::Night(night_action) => {
                         if !night.secret_ids().contains(night_action.src()) { return Err(Error::GameAction("du spielst nicht mit".into())) }
+                        let log_entry = night_action_log(&night_action);
+                        state_ref.post_hidden(ctx, &log_entry).await?;
+                        state_ref.log.push(LogEntry::now(log_entry)); // can't use `spectate` since the night is borrowed
                         state_ref.night_actions.push(night_action);
                     }
-                    Action::Vote(_, _) | Action::Unvote(_) => return Err(Error::GameAction("aktuell läuft keine Abstimmung".into())),
+                    Action::Couple(src_id, lover1, lover2) => couple = Some(state_ref.check_couple(src_id, lover1, lover2)?),
+                    Action::Aim(src_id, tgt_id) => state_ref.hunter_target = Some(state_ref.check_aim(src_id, tgt_id)?),
+                    Action::Vote(_, _) | Action::Unvote(_) | Action::Delegate(_, _) | Action::Undelegate(_) => return Err(Error::GameAction("aktuell läuft keine Abstimmung".into())),
                 }
             }
-            State::Day(ref day) => match action {
-                Action::Vote(src_id, vote) => {
-                    if !day.alive().contains(&src_id) { return Err(Error::GameAction("tote Spieler können nicht abstimmen".into())) }
-                    state_ref.votes.insert(src_id, vote);
+            State::Day(ref day) => {
+                if state_ref.config.anonymous_votes && !action.is_secret() {
+                    if !msg.is_private() {
+                        // don't reveal the vote to the other players
+                        msg.delete(ctx).await?;
+                        msg.author.create_dm_channel(ctx).await?.say(ctx, "Die Abstimmung ist geheim, bitte schick mir deine Stimme per DM.").await?;
+                        return Ok(())
+                    }
+                } else if let Some(action_channel) = state_ref.config.action_channel {
+                    if !action.is_secret() && msg.channel_id != action_channel { return Err(Error::GameAction(MessageBuilder::default().push("Stimmen zählen nur in ").channel(action_channel).build())) }
                 }
-                Action::Unvote(src_id) => {
-                    if !day.alive().contains(&src_id) { return Err(Error::GameAction("tote Spieler können nicht abstimmen".into())) }
-                    state_ref.votes.remove(&src_id);
+                match action {
+                    Action::Vote(src_id, vote) => {
+                        if !day.alive().contains(&src_id) { return Err(Error::GameAction("tote Spieler können nicht abstimmen".into())) }
+                        if !state_ref.is_vote_option(vote) { return Err(Error::GameAction(if state_ref.runoff.is_some() { "in der Stichwahl stehen nur die Gleichstände zur Wahl" } else { "in diesem Spiel kann nicht dafür gestimmt werden, niemanden zu lynchen" }.into())) }
+                        state_ref.delegations.remove(&src_id); // voting yourself revokes a delegation
+                        state_ref.votes.insert(src_id, vote);
+                    }
+                    Action::Unvote(src_id) => {
+                        if !day.alive().contains(&src_id) { return Err(Error::GameAction("tote Spieler können nicht abstimmen".into())) }
+                        state_ref.votes.remove(&src_id);
+                    }
+                    Action::Delegate(src_id, tgt_id) => {
+                        if !state_ref.config.vote_delegation() { return Err(Error::GameAction("in diesem Spiel können Stimmen nicht übertragen werden".into())) }
+                        if !day.alive().contains(&src_id) { return Err(Error::GameAction("tote Spieler können nicht abstimmen".into())) }
+                        if !day.alive().contains(&tgt_id) || state_ref.departed.contains(&tgt_id) { return Err(Error::GameAction("du kannst deine Stimme nur an lebende Spieler übertragen".into())) }
+                        if src_id == tgt_id { return Err(Error::GameAction("du kannst deine Stimme nicht an dich selbst übertragen".into())) }
+                        if delegation_chain(&state_ref.delegations, tgt_id).any(|player| player == src_id) { return Err(Error::GameAction("diese Stimmübertragung würde einen Kreis ergeben".into())) }
+                        state_ref.votes.remove(&src_id);
+                        state_ref.delegations.insert(src_id, tgt_id);
+                        state_ref.config.vote_channel().say(ctx, vote_delegated(src_id, tgt_id)).await?;
+                    }
+                    Action::Undelegate(src_id) => {
+                        if state_ref.delegations.remove(&src_id).is_none() { return Err(Error::GameAction("du hast deine Stimme nicht übertragen".into())) }
+                        state_ref.config.vote_channel().say(ctx, vote_undelegated(src_id)).await?;
+                    }
+                    Action::Couple(src_id, lover1, lover2) => couple = Some(state_ref.check_couple(src_id, lover1, lover2)?),
+                    Action::Aim(src_id, tgt_id) => state_ref.hunter_target = Some(state_ref.check_aim(src_id, tgt_id)?),
+                    Action::Night(_) => return Err(Error::GameAction("es ist Tag".into())),
                 }
-                Action::Night(_) => return Err(Error::GameAction("es ist Tag".into())),
             }
             State::Signups(_) | State::Complete(_) => return Err(Error::GameAction("aktuell läuft kein Spiel".into())),
         }
-        *guild
+        if let Some((lover1, lover2)) = couple {
+            state_ref.set_lovers(ctx, lover1, lover2, true).await?;
+        }
+        *game
     };
     msg.react(ctx, '👀').await?;
-    continue_game(ctx, guild).await?;
+    continue_game(ctx, game).await?;
     Ok(())
 }
 
@@ -464,15 +2883,21 @@ fn handle_game_state<'a>(ctx: &'a Context, state_ref: &'a mut GameState) -> Pin<
         let state = mem::replace(&mut state_ref.state, State::default());
         Ok(match state {
             State::Signups(signups) => {
-                if signups.num_players() < MIN_PLAYERS {
-                    state_ref.state = State::Signups(signups);
+                let num_players = signups.num_players();
+                state_ref.state = State::Signups(signups);
+                if num_players == 0 {
+                    // everyone left, so close signups
+                    state_ref.timeouts.cancel_all();
+                    state_ref.close_signup_message(ctx, &[]).await?;
                     None
+                } else if state_ref.config.max_players().map_or(false, |max_players| num_players >= max_players) {
+                    // the game is full, so start it right away
+                    state_ref.timeouts.cancel_all();
+                    handle_timeout(ctx, state_ref).await?
                 } else {
-                    if !state_ref.timeouts_active() {
-                        state_ref.config.text_channel.say(ctx, "das Spiel startet in einer Minute").await?;
-                    }
-                    state_ref.state = State::Signups(signups);
-                    Some(Duration::from_secs(60)) // allow more players to sign up
+                    state_ref.update_signup_message(ctx).await?;
+                    let end = state_ref.signup_message.as_ref().expect("just posted signup message").end;
+                    Some((end - Utc::now()).to_std().unwrap_or_default())
                 }
             }
             State::Night(night) => {
@@ -481,75 +2906,121 @@ fn handle_game_state<'a>(ctx: &'a Context, state_ref: &'a mut GameState) -> Pin<
                     handle_game_state(ctx, state_ref).await?
                 } else {
                     state_ref.state = State::Night(night);
-                    Some(Duration::from_secs(180)) // 3 minute night time limit works for XylBot, may need to be adjusted up or down. Collect stats?
+                    Some(state_ref.config.night_duration())
                 }
             }
             State::Day(day) => {
-                let (max_votes, vote_result) = vote_leads(&state_ref);
+                let (max_votes, vote_result) = vote_leads(&state_ref.votes, &state_ref.delegations);
                 if max_votes > day.alive().len() / 2 && vote_result.len() == 1 {
                     state_ref.resolve_day(ctx, day).await?;
                     handle_game_state(ctx, state_ref).await?
                 } else {
                     state_ref.state = State::Day(day);
-                    Some(Duration::from_secs(1800)) // Not sure how long the day limit should be. Starting out with half an hour for now to be safe. Collect stats?
+                    state_ref.update_vote_message(ctx).await?;
+                    state_ref.update_phase_message(ctx, false).await?;
+                    Some(if state_ref.runoff.is_some() { state_ref.config.runoff_duration() } else { state_ref.config.day_duration() })
                 }
             }
             State::Complete(Complete { winners }) => {
+                state_ref.reset(); // before anything that can fail, so a failed announcement doesn't leave the phase behind
+                let winner_ids = winners.iter().copied().collect::<HashSet<_>>();
                 let mut winners = stream::iter(winners).then(|user_id| user_id.to_user(ctx)).try_collect::<Vec<_>>().await?;
                 winners.sort_by_key(|user| (user.name.clone(), user.discriminator));
-                let mut builder = MessageBuilder::default();
-                builder.push("das Spiel ist vorbei: ");
-                state_ref.config.text_channel.say(ctx, match winners.len() {
-                    0 => builder.push("niemand hat gewonnen"),
-                    1 => builder.mention(&winners.swap_remove(0)).push(" hat gewonnen"),
-                    _ => {
-                        builder.mention(&winners.remove(0));
-                        for winner in winners {
-                            builder.push(" ").mention(&winner);
-                        }
-                        builder.push(" haben gewonnen")
-                    }
-                }).await?;
-                // unlock channel
-                let everyone = RoleId(state_ref.guild.0); // Gefolge @everyone role, same ID as the guild
-                state_ref.config.text_channel.delete_permission(ctx, PermissionOverwriteType::Role(everyone)).await?;
-                for mut member in state_ref.guild.members(ctx, None, None).await? { //TODO make sure all members are checked
-                    if member.roles(ctx).await.map_or(false, |roles| roles.into_iter().any(|role| role.id == state_ref.config.role)) {
-                        member.remove_role(ctx, state_ref.config.role).await?;
+                let log_msg = game_over_log(state_ref.players.len(), &winners);
+                let log_ctx = ctx.clone();
+                let guild = state_ref.guild;
+                tokio::spawn(async move { // the typemap is locked while handling the game state, so wait for it to be released
+                    if let Err(e) = log_channel::post(&log_ctx, guild, log_msg).await {
+                        eprintln!("failed to post werewolf game result to log channel: {} ({:?})", e, e);
                     }
+                });
+                let announcement = game_over(&winners.iter().map(|winner| winner.id).collect::<Vec<_>>());
+                state_ref.config.text_channel.say(ctx, &announcement).await?;
+                state_ref.log.push(LogEntry::now(announcement));
+                if let Some(seed) = state_ref.seed.take() {
+                    let reveal = seed_revealed(&hex::encode(seed), &seed_commitment(&seed));
+                    state_ref.config.text_channel.say(ctx, &reveal).await?;
+                    state_ref.log.push(LogEntry::now(reveal));
                 }
-                state_ref.state = State::default();
+                state_ref.export_log(ctx).await?;
+                state_ref.sync_voice_mutes(ctx, None).await?;
+                state_ref.clean_up(ctx).await?;
+                tournament::record_game(ctx, &state_ref.paths, state_ref.guild, state_ref.config.text_channel, &state_ref.players, &winner_ids).await?;
+                werewolf_history::record_game(&state_ref.paths, werewolf_history::Game {
+                    guild: state_ref.guild,
+                    channel: state_ref.config.text_channel,
+                    start: state_ref.started.take().unwrap_or_else(Utc::now),
+                    end: Utc::now(),
+                    players: state_ref.players.clone(),
+                    winners: winner_ids.into_iter().collect(),
+                    deaths: mem::take(&mut state_ref.deaths),
+                }).await?;
+                state_ref.last_players = mem::take(&mut state_ref.players);
+                state_ref.clear_game();
                 None
             }
         })
     })
 }
 
+/// Replaces user mentions with the users' tags, since mentions aren't rendered in attachments.
+async fn resolve_mentions(ctx: &Context, text: &str) -> Result<String, Error> {
+    static MENTION_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("<@!?([0-9]+)>").expect("failed to compile mention regex"));
+
+    let mut resolved = String::with_capacity(text.len());
+    let mut last_end = 0;
+    for captures in MENTION_REGEX.captures_iter(text) {
+        let mention = captures.get(0).expect("missing regex match");
+        resolved.push_str(&text[last_end..mention.start()]);
+        let user_id = UserId(captures[1].parse().expect("mention regex matched non-numeric user ID"));
+        resolved.push_str(&format!("@{}", user_id.to_user(ctx).await?.tag()));
+        last_end = mention.end();
+    }
+    resolved.push_str(&text[last_end..]);
+    Ok(resolved)
+}
+
 async fn handle_timeout(ctx: &Context, state_ref: &mut GameState) -> Result<Option<Duration>, Error> {
+    let signed_up = state_ref.signed_up();
+    let inactive = state_ref.inactive_players();
     let state = mem::replace(&mut state_ref.state, State::default());
     state_ref.state = match state {
         State::Signups(signups) => {
-            if signups.num_players() < MIN_PLAYERS {
-                State::Signups(signups)
+            state_ref.close_signup_message(ctx, &signed_up).await?;
+            if signups.num_players() < state_ref.config.min_players() {
+                state_ref.remove_game_role(ctx).await?;
+                state_ref.config.text_channel.say(ctx, signups_cancelled(state_ref.config.min_players())).await?;
+                state_ref.clear_game();
+                State::default()
             } else {
-                // lock channel
-                let everyone = RoleId(state_ref.guild.0); // Gefolge @everyone role, same ID as the guild
-                state_ref.config.text_channel.create_permission(ctx, &PermissionOverwrite {
-                    kind: PermissionOverwriteType::Role(everyone),
-                    allow: Permissions::empty(),
-                    deny: Permissions::SEND_MESSAGES | Permissions::ADD_REACTIONS
-                }).await?;
-                // create a random role distribution
-                let num_ww = signups.num_players() * 2 / 5;
-                let mut roles = (0..num_ww).map(|i| Role::Werewolf(i)).collect::<Vec<_>>();
-                roles.push(Role::Detective);
-                if signups.num_players() > 4 && thread_rng().gen() { roles.push(Role::Healer); }
-                // start the game with that distribution
+                let starter = state_ref.starter;
+                state_ref.clear_game();
+                state_ref.starter = starter; // they can still pause the game
+                state_ref.lock_channels(ctx).await?;
+                let seed = thread_rng().gen::<[u8; 32]>();
+                state_ref.seed = Some(seed);
+                state_ref.config.text_channel.say(ctx, seed_committed(&seed_commitment(&seed))).await?;
+                let preset = state_ref.config.roles.filter(|preset| preset.min_players() <= signups.num_players());
+                let roles = match state_ref.config.roles {
+                    Some(preset) if preset.min_players() <= signups.num_players() => preset.roles(),
+                    Some(preset) => {
+                        state_ref.config.text_channel.say(ctx, role_preset_fallback(preset, preset.min_players())).await?;
+                        random_roles(signups.num_players(), &mut seeded_rng(&seed, "roles"))
+                    }
+                    None => random_roles(signups.num_players(), &mut seeded_rng(&seed, "roles")),
+                };
                 let started = signups.start(roles.clone())?;
+                state_ref.players = started.secret_ids().expect("failed to get secred player IDs").into_iter().cloned().collect();
+                state_ref.started = Some(Utc::now());
+                state_ref.roles = roles.clone();
+                state_ref.set_up_spectator_channel(ctx).await?; // hide the log from players until they die
+                state_ref.spectate(ctx, game_start_log(&roles, &state_ref.players)).await?;
                 for (secret_id, player) in started.secret_ids().expect("failed to get secred player IDs").into_iter().enumerate() {
                     let dm = quantum_role_dm(&roles, started.num_players(), secret_id);
                     player.create_dm_channel(ctx).await?.say(ctx, &dm).await?;
                 }
+                state_ref.assign_variants(ctx, preset).await?;
+                state_ref.phase.advance(&started)?;
                 match started {
                     State::Night(ref night) => {
                         state_ref.start_night(ctx, night).await?;
@@ -562,25 +3033,121 @@ async fn handle_timeout(ctx: &Context, state_ref: &mut GameState) -> Result<Opti
                 started
             }
         }
-        State::Night(night) => state_ref.resolve_night(ctx, night).await?,
+        State::Night(night) => {
+            // players who didn't send night actions don't act this night
+            state_ref.record_inactivity(ctx, inactive).await?;
+            state_ref.resolve_night(ctx, night).await?
+        }
         State::Day(day) => {
-            state_ref.resolve_day(ctx, day).await?;
-            mem::replace(&mut state_ref.state, State::default())
+            state_ref.record_inactivity(ctx, inactive).await?;
+            // players who neither voted nor delegated their vote abstain
+            let mut abstained = if state_ref.config.anonymous_votes { Vec::default() } else {
+                day.alive().iter().filter(|&player| !state_ref.votes.contains_key(player) && !state_ref.delegations.contains_key(player)).copied().collect::<Vec<_>>()
+            };
+            abstained.sort();
+            state_ref.config.vote_channel().say(ctx, time_up(&abstained)).await?;
+            let (max_votes, vote_result) = vote_leads(&state_ref.votes, &state_ref.delegations);
+            if state_ref.config.tie_break == TieBreak::Runoff && state_ref.runoff.is_none() && max_votes > 0 && vote_result.len() > 1 {
+                state_ref.start_runoff(ctx, vote_result.into_iter().collect()).await?;
+                State::Day(day)
+            } else {
+                state_ref.resolve_day(ctx, day).await?;
+                mem::replace(&mut state_ref.state, State::default())
+            }
         }
         State::Complete(_) => unimplemented!(), // there shouldn't be any timeouts after the game ends
     };
     handle_game_state(ctx, state_ref).await
 }
 
+/// Subscribes to reactions, which are used to vote on vote messages.
+pub fn register(listeners: &mut ReactionListeners) {
+    listeners.subscribe(Filter::default(), on_reaction);
+}
+
+fn on_reaction(ctx: Context, reaction: Reaction, kind: reaction_listeners::Kind) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send>> {
+    Box::pin(async move {
+        handle_vote_reaction(&ctx, &reaction, kind).await?;
+        handle_rematch_reaction(&ctx, &reaction, kind).await
+    })
+}
+
+/// Signs up players who react to the signup embed of a rematch, and withdraws the signup if they remove the reaction.
+async fn handle_rematch_reaction(ctx: &Context, reaction: &Reaction, kind: reaction_listeners::Kind) -> Result<(), Error> {
+    let user_id = if let Some(user_id) = reaction.user_id { user_id } else { return Ok(()) };
+    if user_id == ctx.cache.current_user_id().await { return Ok(()) } // ignore the bot's own reactions
+    if reaction.emoji != ReactionType::Unicode(REMATCH_EMOJI.to_owned()) { return Ok(()) }
+    let game = {
+        let data = ctx.data.read().await;
+        if let Some((&game, _)) = data.get::<GameState>().expect("missing Werewolf game state").iter()
+            .find(|(_, state)| state.rematch && state.signup_message.as_ref().map_or(false, |signup_message| signup_message.message == reaction.message_id))
+        { game } else { return Ok(()) }
+    };
+    match kind {
+        reaction_listeners::Kind::Add => if let Err(reason) = sign_up(ctx, game, user_id).await? {
+            reaction.delete(ctx).await?;
+            user_id.create_dm_channel(ctx).await?.say(ctx, reason).await?;
+            return Ok(())
+        },
+        reaction_listeners::Kind::Remove => if sign_out(ctx, game, user_id).await?.is_err() { return Ok(()) }, // e.g. the reaction was removed because the signup failed
+    }
+    continue_game(ctx, game).await?;
+    Ok(())
+}
+
+/// Counts a reaction on a vote message as a vote, or removing it as withdrawing the vote. Reactions which aren't valid votes are removed.
+async fn handle_vote_reaction(ctx: &Context, reaction: &Reaction, kind: reaction_listeners::Kind) -> Result<(), Error> {
+    let user_id = if let Some(user_id) = reaction.user_id { user_id } else { return Ok(()) };
+    if user_id == ctx.cache.current_user_id().await { return Ok(()) } // ignore the bot's own reactions
+    let game = {
+        let mut data = ctx.data.write().await;
+        let (&game, state_ref) = if let Some(game) = data.get_mut::<GameState>().expect("missing Werewolf game state").iter_mut()
+            .find(|(_, state)| state.vote_message.as_ref().map_or(false, |vote_message| vote_message.message == reaction.message_id))
+        { game } else { return Ok(()) };
+        let vote_message = state_ref.vote_message.as_ref().expect("just checked");
+        let vote = vote_options(&vote_message.candidates, vote_message.no_lynch).find(|(emoji, _)| reaction.emoji == ReactionType::Unicode((*emoji).to_owned())).map(|(_, vote)| vote);
+        match kind {
+            reaction_listeners::Kind::Add => {
+                let alive = if let State::Day(ref day) = state_ref.state { day.alive().contains(&user_id) && !state_ref.departed.contains(&user_id) } else { false };
+                let vote = if let (true, Some(vote), None) = (alive, vote, state_ref.paused) { vote } else {
+                    reaction.delete(ctx).await?;
+                    return Ok(())
+                };
+                // only the most recent reaction counts, so remove the previous one
+                if let Some(old_vote) = state_ref.votes.insert(user_id, vote) {
+                    if old_vote != vote {
+                        if let Some((old_emoji, _)) = vote_options(&vote_message.candidates, vote_message.no_lynch).find(|&(_, option)| option == old_vote) {
+                            vote_message.channel.delete_reaction(ctx, vote_message.message, Some(user_id), ReactionType::Unicode(old_emoji.to_owned())).await?;
+                        }
+                    }
+                }
+                state_ref.delegations.remove(&user_id); // voting yourself revokes a delegation
+            }
+            reaction_listeners::Kind::Remove => {
+                if vote.is_none() || state_ref.votes.get(&user_id).copied() != vote { return Ok(()) }
+                state_ref.votes.remove(&user_id);
+            }
+        }
+        game
+    };
+    continue_game(ctx, game).await?;
+    Ok(())
+}
+
 pub async fn parse_action(ctx: &Context, src: UserId, mut msg: &str) -> Option<Result<Action, Error>> {
-    async fn parse_player(ctx: &Context, guild: GuildId, subj: &mut &str) -> Result<UserId, Option<UserId>> {
+    async fn parse_player(ctx: &Context, game: GameId, subj: &mut &str) -> Result<UserId, Option<UserId>> {
         if let Some(user_id) = parse::eat_user_mention(subj) {
-            if player_in_game(ctx, user_id, guild).await { Ok(user_id) } else { Err(Some(user_id)) }
+            if player_in_game(ctx, user_id, game).await { Ok(user_id) } else { Err(Some(user_id)) }
         } else {
             let data = ctx.data.read().await;
-            let state_ref = data.get::<GameState>().expect("missing Werewolf game state").get(&guild).expect("tried to parse action for missing game");
+            let state_ref = data.get::<GameState>().expect("missing Werewolf game state").get(&game).expect("tried to parse action for missing game");
             if let Some(user_ids) = state_ref.state.secret_ids() {
                 if let Some(next_word) = parse::next_word(&subj) {
+                    // players can be referred to by their number from the probability table
+                    if let Some(&user_id) = next_word.parse::<usize>().ok().and_then(|num| num.checked_sub(1)).and_then(|idx| state_ref.players.get(idx)) {
+                        *subj = &subj[next_word.len()..]; // consume number
+                        return Ok(user_id)
+                    }
                     let users = if let Ok(users) = stream::iter(user_ids).then(|user_id| user_id.to_user(ctx)).try_collect::<Vec<_>>().await { users } else { return Err(None) };
                     let matching_users = user_ids.into_iter().zip(users).filter_map(|(&user_id, user)| if user.name == next_word { Some(user_id) } else { None }).collect::<Vec<_>>();
                     if matching_users.len() == 1 {
@@ -595,33 +3162,61 @@ pub async fn parse_action(ctx: &Context, src: UserId, mut msg: &str) -> Option<R
     }
 
     // A simple parser for game actions.
-    let guild = *ctx.data.read().await.get::<GameState>().expect("missing Werewolf game state").iter().filter(|(_, state)| state.state.secret_ids().map_or(false, |secret_ids| secret_ids.contains(&src))).map(|(guild_id, _)| guild_id).exactly_one().ok()?;
+    let game = *ctx.data.read().await.get::<GameState>().expect("missing Werewolf game state").iter().filter(|(_, state)| state.state.secret_ids().map_or(false, |secret_ids| secret_ids.contains(&src))).map(|(game, _)| game).exactly_one().ok()?;
     if msg.starts_with('!') { msg = &msg[1..] } // remove leading `!`, if any
     let cmd_name = if let Some(cmd_name) = parse::next_word(&msg) { cmd_name } else { return None };
     msg = &msg[cmd_name.len()..]; // consume command name
     parse::eat_whitespace(&mut msg);
     Some(match &cmd_name[..] {
+        "delegate" => {
+            match parse_player(ctx, game, &mut msg).await {
+                Ok(tgt) => Ok(Action::Delegate(src, tgt)),
+                Err(Some(user_id)) => Err(Error::GameAction(MessageBuilder::default().mention(&user_id).push(" spielt nicht mit").build())), //TODO use dm_mention if in DM channel
+                Err(None) => Err(Error::GameAction("kann das Ziel nicht lesen".into()))
+            }
+        }
         "h" | "heal" => {
-            match parse_player(ctx, guild, &mut msg).await {
+            match parse_player(ctx, game, &mut msg).await {
                 Ok(tgt) => Ok(Action::Night(NightAction::Heal(src, tgt))),
                 Err(Some(user_id)) => Err(Error::GameAction(MessageBuilder::default().mention(&user_id).push(" spielt nicht mit").build())), //TODO use dm_mention if in DM channel
                 Err(None) => Err(Error::GameAction("kann das Ziel nicht lesen".into()))
             }
         }
         "i" | "inspect" | "investigate" => {
-            match parse_player(ctx, guild, &mut msg).await {
+            match parse_player(ctx, game, &mut msg).await {
                 Ok(tgt) => Ok(Action::Night(NightAction::Investigate(src, tgt))),
                 Err(Some(user_id)) => Err(Error::GameAction(MessageBuilder::default().mention(&user_id).push(" spielt nicht mit").build())), //TODO use dm_mention if in DM channel
                 Err(None) => Err(Error::GameAction("kann das Ziel nicht lesen".into()))
             }
         }
         "k" | "kill" => {
-            match parse_player(ctx, guild, &mut msg).await {
+            match parse_player(ctx, game, &mut msg).await {
                 Ok(tgt) => Ok(Action::Night(NightAction::Kill(src, tgt))),
                 Err(Some(user_id)) => Err(Error::GameAction(MessageBuilder::default().mention(&user_id).push(" spielt nicht mit").build())), //TODO use dm_mention if in DM channel
                 Err(None) => Err(Error::GameAction("kann das Ziel nicht lesen".into()))
             }
         }
+        "aim" => {
+            match parse_player(ctx, game, &mut msg).await {
+                Ok(tgt) => Ok(Action::Aim(src, tgt)),
+                Err(Some(user_id)) => Err(Error::GameAction(MessageBuilder::default().mention(&user_id).push(" spielt nicht mit").build())), //TODO use dm_mention if in DM channel
+                Err(None) => Err(Error::GameAction("kann das Ziel nicht lesen".into()))
+            }
+        }
+        "couple" => {
+            let lover1 = match parse_player(ctx, game, &mut msg).await {
+                Ok(lover1) => lover1,
+                Err(Some(user_id)) => return Some(Err(Error::GameAction(MessageBuilder::default().mention(&user_id).push(" spielt nicht mit").build()))), //TODO use dm_mention if in DM channel
+                Err(None) => return Some(Err(Error::GameAction("kann den ersten Spieler nicht lesen".into()))),
+            };
+            parse::eat_whitespace(&mut msg);
+            match parse_player(ctx, game, &mut msg).await {
+                Ok(lover2) => Ok(Action::Couple(src, lover1, lover2)),
+                Err(Some(user_id)) => Err(Error::GameAction(MessageBuilder::default().mention(&user_id).push(" spielt nicht mit").build())), //TODO use dm_mention if in DM channel
+                Err(None) => Err(Error::GameAction("kann den zweiten Spieler nicht lesen".into()))
+            }
+        }
+        "undelegate" => Ok(Action::Undelegate(src)),
         "sleep" => unimplemented!(), //TODO if *this player's* mandatory night actions are complete, note that the player is done submitting night actions. otherwise, reply with an error
         "unvote" => Ok(Action::Unvote(src)),
         "v" | "vote" => {
@@ -631,7 +3226,7 @@ pub async fn parse_action(ctx: &Context, src: UserId, mut msg: &str) -> Option<R
                 if vec!["no lynch", "nolynch", "nl"].into_iter().any(|prefix| msg.to_ascii_lowercase() == prefix) {
                     return Some(Ok(Action::Vote(src, Vote::NoLynch)))
                 }
-                match parse_player(ctx, guild, &mut msg).await {
+                match parse_player(ctx, game, &mut msg).await {
                     Ok(tgt) => Ok(Action::Vote(src, Vote::Player(tgt))),
                     Err(Some(user_id)) => Err(Error::GameAction(MessageBuilder::default().mention(&user_id).push(" spielt nicht mit").build())), //TODO use dm_mention if in DM channel
                     Err(None) => Err(Error::GameAction("kann das Ziel nicht lesen".into()))
@@ -642,89 +3237,124 @@ pub async fn parse_action(ctx: &Context, src: UserId, mut msg: &str) -> Option<R
     })
 }
 
-pub async fn player_in_game(ctx: &Context, user_id: UserId, guild_id: GuildId) -> bool {
+/// `Some(true)` during the night, `Some(false)` during the day, and `None` if no game is running.
+fn is_night(state: &State<UserId>) -> Option<bool> {
+    match state {
+        State::Night(_) => Some(true),
+        State::Day(_) => Some(false),
+        State::Signups(_) | State::Complete(_) => None,
+    }
+}
+
+/// Applies [`Config::mute_voice`] to members joining or leaving voice during a game, and unmutes members who left voice while muted by a game that has since ended.
+pub async fn voice_state_update(ctx: &Context, guild: GuildId, voice_state: &VoiceState) -> Result<(), Error> {
+    let voice_channel = if let Some(voice_channel) = voice_state.channel_id { voice_channel } else { return Ok(()) }; // members can only be (un)muted while in voice
+    let user = voice_state.user_id;
+    let mut data = ctx.data.write().await;
+    let mut games = data.get_mut::<GameState>().expect("missing Werewolf game state").values_mut().filter(|state_ref| state_ref.guild == guild).collect::<Vec<_>>();
+    let should_mute = |state_ref: &GameState| state_ref.should_mute(user, voice_channel, is_night(&state_ref.state));
+    let mute = games.iter().any(|state_ref| should_mute(state_ref));
+    if mute == games.iter().any(|state_ref| state_ref.voice_muted.contains(&user)) { return Ok(()) }
+    guild.edit_member(ctx, user, |m| m.mute(mute)).await?;
+    for state_ref in &mut games {
+        if mute && should_mute(state_ref) {
+            state_ref.voice_muted.insert(user);
+        } else {
+            state_ref.voice_muted.remove(&user);
+        }
+        state_ref.save().await?;
+    }
+    Ok(())
+}
+
+pub async fn player_in_game(ctx: &Context, user_id: UserId, game: GameId) -> bool {
     let data = ctx.data.read().await;
-    let state_ref = data.get::<GameState>().expect("missing Werewolf game state").get(&guild_id);
+    let state_ref = data.get::<GameState>().expect("missing Werewolf game state").get(&game);
     state_ref.map_or(false, |state_ref| state_ref.state.secret_ids().map_or(false, |secret_ids| secret_ids.contains(&user_id)))
 }
 
-pub fn quantum_role_dm(roles: &[Role], num_players: usize, secret_id: usize) -> String {
-    // Willkommen
-    let mut builder = MessageBuilder::default();
-    builder.push_line("Willkommen bei Quantenwerwölfe!"); //TODO Spielname (flavor) oder Variantenname (für normales ww etc)
-    // Rollenname
-    let mut role_counts = HashMap::<_, usize>::default();
-    let extra_villagers = num_players - roles.len();
-    if extra_villagers > 0 {
-        role_counts.insert(Role::Villager, extra_villagers);
+/// The hex-encoded SHA-256 hash of a game's seed, which is posted when the game starts so players can check afterwards that the seed revealed at the end wasn't changed.
+pub fn seed_commitment(seed: &[u8; 32]) -> String {
+    hex::encode(Sha256::digest(seed))
+}
+
+/// A random number generator for one of the bot's random decisions during a game, derived from the game's seed.
+///
+/// The `purpose` keeps the decisions independent of each other: `roles` for the role distribution, `variants` for the tanner, lovers, Amor, and hunter (shuffling the players sorted by user ID), `lovers` for the lovers if Amor doesn't choose any, and `lynch N` for a random tie break after `N` players have died.
+pub fn seeded_rng(seed: &[u8; 32], purpose: &str) -> StdRng {
+    let mut rng_seed = [0; 32];
+    rng_seed.copy_from_slice(&Sha256::new().chain(seed).chain(purpose.as_bytes()).finalize());
+    StdRng::from_seed(rng_seed)
+}
+
+/// Creates a random role distribution for the given number of players.
+///
+/// Only the roles implemented by `quantum_werewolf` are available. Amor and the hunter are variants handled by the bot, see [`RolePreset`]. Roles which change night resolution (e.g. witch, bodyguard) need to be added to `quantum_werewolf` first.
+pub fn random_roles(num_players: usize, rng: &mut impl Rng) -> Vec<Role> {
+    let num_ww = num_players * 2 / 5;
+    let mut roles = (0..num_ww).map(|i| Role::Werewolf(i)).collect::<Vec<_>>();
+    roles.push(Role::Detective);
+    if num_players > 4 && rng.gen() { roles.push(Role::Healer); }
+    roles
+}
+
+/// Ends the day according to the given votes: If a single player or “no lynch” has the most votes, that vote is carried out, otherwise nobody is lynched.
+pub fn resolve_votes(day: Day<UserId>, votes: &HashMap<UserId, Vote>, delegations: &HashMap<UserId, UserId>) -> State<UserId> {
+    resolve_votes_with(day, votes, delegations, TieBreak::NoLynch)
+}
+
+/// Like [`resolve_votes`], but breaks ties as configured.
+pub fn resolve_votes_with(day: Day<UserId>, votes: &HashMap<UserId, Vote>, delegations: &HashMap<UserId, UserId>, tie_break: TieBreak) -> State<UserId> {
+    match lynch_choice(votes, delegations, tie_break, &mut thread_rng()) {
+        Some(player) => day.lynch(player),
+        None => day.no_lynch(),
     }
-    for &role in roles {
-        let normalized_role = if let Role::Werewolf(_) = role {
-            Role::Werewolf(0)
-        } else {
-            role
-        };
-        *role_counts.entry(normalized_role).or_insert(0) += 1;
-    }
-    let mut role_count_list = role_counts.clone().into_iter().collect::<Vec<_>>();
-    role_count_list.sort_by_key(|&(role, _)| role_name(role, Nom, false));
-    builder.push("Du bist eine ");
-    builder.push_bold_safe(format!("Quantenüberlagerung aus {}", join(None, role_count_list.into_iter().map(|(role, count)| {
-        let card = cardinal(count as u64, Dat, role_gender(role));
-        if let Role::Werewolf(_) = role {
-            format!("{} {}", card, if count == 1 { "Werwolf" } else { "Werwölfen" })
-        } else {
-            format!("{} {}", card, role_name(role, Dat, count != 1))
-        }
-    }))));
-    builder.push(".");
-    // Rollenrang
-    builder.push(" Dein Rollenrang ist ");
-    builder.push_bold(secret_id + 1);
-    builder.push(".");
-    //TODO Partei (für qww erst relevant, wenn nur noch eine Rolle möglich ist)
-    //TODO Dorfname (bei Variante „die Gemeinschaft der Dörfer“)
-    builder.push_line("");
-    //TODO Gruppenmitspieler (irrelevant für qww, zB Werwölfe, Freimaurer, Seherinnen/Kekse)
-    // Aktionen (Parteiaktionen klar als solche kennzeichnen)
-    if *role_counts.get(&Role::Healer).unwrap_or(&0) > 0 {
-        builder.push("Solange du noch lebst, kannst du jede Nacht einen lebenden Spieler deiner Wahl heilen (");
-        builder.push_mono_safe("heal <player>");
-        builder.push_line("). In allen Universen, in denen du lebst und Heiler bist, kann dieser Spieler in dieser Nacht nicht sterben. Du kannst keinen Spieler heilen, den du schon in der vorherigen Nacht geheilt hast.");
-    }
-    if *role_counts.get(&Role::Detective).unwrap_or(&0) > 0 {
-        builder.push("Solange du noch lebst, kannst du jede Nacht einen Spieler deiner Wahl untersuchen (");
-        builder.push_mono_safe("investigate <player>");
-        builder.push_line("). Falls es mindestens ein Universum gibt, in dem du Detektiv bist, erfährst du die Partei dieses Spielers in einem zufälligen solchen Universum. Alle Universen, in denen du Detektiv bist und der Spieler nicht diese Partei hat, werden eliminiert.");
-    }
-    builder.push("Solange du noch lebst, tötest du in jeder Nacht einen lebenden Spieler deiner Wahl (");
-    builder.push_mono_safe("kill <player>");
-    builder.push_line("). In allen Universen, in denen du der Werwolf mit der kleinsten Rangnummer unter den lebenden Werwölfen bist, stirbt dieser Spieler.");
-    // sonstige Effekte (Parteieffekte klar als solche kennzeichnen)
-    builder.push_line("Jeden Morgen wird öffentlich aber anonym dein Rollenrang sowie die relativen Häufigkeiten der Universen, in denen du zum Dorf gehörst, derer in denen du zu den Werwölfen gehörst, und derer in denen du tot bist angekündigt.");
-    builder.push_line("Wenn du in allen Universen tot bist, stirbst du.");
-    builder.push_line("Wenn du stirbst oder am Ende des Spiels wird aus den Universen, in denen du bis eben noch gelebt hast, ein zufälliges ausgewählt und du bekommst deine Identität aus diesem Universum. Alle anderen Quantenüberlagerungen verlieren diese Identität aus ihren Überlagerungen, und alle Universen, in denen du nicht diese Identität warst, werden eliminiert.");
-    //TODO wincons (für qww erst relevant, wenn nur noch eine Rolle möglich ist)
-    //TODO optional: Kurzzusammenfassung der Regeln bzw link zu den vollständigen Regeln
-    // Unterschrift
-    builder.push("Viel Spaß!");
-    builder.build()
-}
-
-fn vote_leads(state_ref: &GameState) -> (usize, HashSet<Vote>) {
-    let mut vote_count = HashMap::<Vote, usize>::default();
-    for (_, &vote) in state_ref.votes.iter() {
-        *vote_count.entry(vote).or_insert(0) += 1;
-    }
-    vote_count.into_iter()
-        .fold((0, HashSet::default()), |(max_votes, mut voted), (vote, count)|
-            match count.cmp(&max_votes) {
-                Ordering::Less => (max_votes, voted),
-                Ordering::Equal => {
-                    voted.insert(vote);
-                    (max_votes, voted)
-                }
-                Ordering::Greater => (count, iter::once(vote).collect()),
+}
+
+/// The player to be lynched according to the given votes, if any. See [`resolve_votes_with`].
+pub fn lynch_choice(votes: &HashMap<UserId, Vote>, delegations: &HashMap<UserId, UserId>, tie_break: TieBreak, rng: &mut impl Rng) -> Option<UserId> {
+    // determine the players and/or game actions with the most votes
+    let (_, vote_result) = vote_leads(votes, delegations);
+    // if the result is a single player, lynch that player
+    let vote = if vote_result.len() == 1 {
+        vote_result.into_iter().next()
+    } else {
+        match tie_break {
+            TieBreak::NoLynch | TieBreak::Runoff => None, // the runoff vote itself is started by the game, so a tie at this point is final
+            TieBreak::Random => {
+                let mut tied = vote_result.into_iter().collect::<Vec<_>>();
+                tied.sort(); // make the choice independent of hash map order
+                tied.choose(rng).copied()
             }
-        )
+        }
+    };
+    match vote {
+        Some(Vote::Player(user_id)) => Some(user_id),
+        Some(Vote::NoLynch) | None => None,
+    }
 }
+
+/// Commands for playing and moderating Werewolf, including seasons and statistics.
+#[group("Werwölfe")]
+#[commands(
+    command_day,
+    command_in,
+    command_night,
+    command_out,
+    command_roles,
+    season,
+    wwconfig,
+    wwgm,
+    wwhelp,
+    wwlastwords,
+    wwmod,
+    wwpause,
+    wwprob,
+    wwrank,
+    wwrematch,
+    wwreplace,
+    wwresume,
+    wwstart,
+    wwstats,
+)]
+pub struct Werewolf;
@@ -0,0 +1,177 @@
+//! A record of all finished werewolf games, used by `!wwstats` and read by gefolge.org for the hall of fame.
+
+use {
+    std::{
+        collections::{
+            BTreeMap,
+            BTreeSet,
+        },
+        io,
+    },
+    chrono::prelude::*,
+    quantum_werewolf::game::Role,
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    serenity::{
+        framework::standard::{
+            Args,
+            CommandResult,
+            macros::command,
+        },
+        model::prelude::*,
+        prelude::*,
+        utils::MessageBuilder,
+    },
+    tokio::fs,
+    crate::{
+        Error,
+        atomic_file,
+        lang::role_stats_label,
+        parse,
+        paths::Paths,
+        werewolf_rating,
+    },
+};
+
+/// A stable name for a role, independent of the language used in messages. Werewolf ranks are ignored.
+pub fn role_key(role: Role) -> &'static str {
+    match role {
+        Role::Detective => "detective",
+        Role::Healer => "healer",
+        Role::Villager => "villager",
+        Role::Werewolf(_) => "werewolf",
+    }
+}
+
+/// The role with the given [`role_key`], or `None` if the key is unknown, e.g. because the game was recorded by a newer version. Werewolves get rank 0.
+pub fn parse_role_key(key: &str) -> Option<Role> {
+    match key {
+        "detective" => Some(Role::Detective),
+        "healer" => Some(Role::Healer),
+        "villager" => Some(Role::Villager),
+        "werewolf" => Some(Role::Werewolf(0)),
+        _ => None,
+    }
+}
+
+/// A player dying during a game.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Death {
+    pub player: UserId,
+    /// The role revealed on death, see [`role_key`].
+    pub role: Option<String>,
+    pub time: DateTime<Utc>,
+}
+
+/// A finished game.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Game {
+    pub guild: GuildId,
+    pub channel: ChannelId,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub players: Vec<UserId>,
+    pub winners: BTreeSet<UserId>,
+    /// In the order they happened. Since roles are only revealed on death, the roles of surviving players aren't known.
+    pub deaths: Vec<Death>,
+}
+
+impl Game {
+    /// The player's role if it was revealed.
+    pub(crate) fn role(&self, player: UserId) -> Option<&str> {
+        self.deaths.iter().find(|death| death.player == player).and_then(|death| death.role.as_deref())
+    }
+}
+
+/// How often a player has played and won, overall or with a given role.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Record {
+    pub games: usize,
+    pub wins: usize,
+}
+
+impl Record {
+    fn add(&mut self, won: bool) {
+        self.games += 1;
+        if won { self.wins += 1; }
+    }
+
+    fn describe(&self) -> String {
+        format!("{} {}, {} gewonnen ({}%)", self.games, if self.games == 1 { "Spiel" } else { "Spiele" }, self.wins, (self.wins * 100).checked_div(self.games).unwrap_or_default())
+    }
+}
+
+/// All finished games in all guilds, stored as a JSON file which is also read by gefolge.org.
+#[derive(Default, Deserialize, Serialize)]
+pub struct History(Vec<Game>);
+
+impl History {
+    pub async fn load(paths: &Paths) -> Result<History, Error> {
+        match fs::read_to_string(paths.werewolf_history()).await {
+            Ok(buf) => Ok(serde_json::from_str(&buf)?), //TODO use async-json
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(History::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn save(&self, paths: &Paths) -> Result<(), Error> {
+        let buf = serde_json::to_vec_pretty(self)?; //TODO use async-json
+        atomic_file::write(&paths.werewolf_history(), &buf).await?;
+        Ok(())
+    }
+
+    /// All finished games, oldest first.
+    pub fn games(&self) -> &[Game] {
+        &self.0
+    }
+
+    /// The given player's record in the given guild (or all guilds if `None`), overall and by revealed role. Games in which the player survived are listed under `None`.
+    pub fn player_stats(&self, player: UserId, guild: Option<GuildId>) -> (Record, BTreeMap<Option<&str>, Record>) {
+        let mut overall = Record::default();
+        let mut by_role = BTreeMap::<_, Record>::default();
+        for game in &self.0 {
+            if guild.map_or(false, |guild| guild != game.guild) || !game.players.contains(&player) { continue }
+            let won = game.winners.contains(&player);
+            overall.add(won);
+            by_role.entry(game.role(player)).or_default().add(won);
+        }
+        (overall, by_role)
+    }
+}
+
+/// Adds a finished game to the history and updates the players' ratings.
+pub(crate) async fn record_game(paths: &Paths, game: Game) -> Result<(), Error> {
+    let mut history = History::load(paths).await?;
+    werewolf_rating::record_game(paths, &history, &game).await?;
+    history.0.push(game);
+    history.save(paths).await?;
+    Ok(())
+}
+
+/// Shows how often you (or the mentioned player) have played and won Werewolf on this server, overall and by role.
+#[command]
+#[usage = "[<Spieler>]"]
+#[example = "@Peter"]
+pub async fn wwstats(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let mut rest = args.rest();
+    let player = parse::eat_user_mention(&mut rest).unwrap_or(msg.author.id);
+    let paths = ctx.data.read().await.get::<Paths>().expect("missing paths").clone();
+    let history = History::load(&paths).await?;
+    let (overall, by_role) = history.player_stats(player, msg.guild_id);
+    let mut builder = MessageBuilder::default();
+    builder.push("Werwölfe-Statistik für ").push_safe(player.to_user(ctx).await?.tag()).push(": ").push(overall.describe());
+    for (role, record) in by_role {
+        builder.push_line("");
+        match role {
+            Some(key) => if let Some(role) = parse_role_key(key) { builder.push(role_stats_label(Some(role))) } else { builder.push_safe(key) },
+            None => builder.push(role_stats_label(None)),
+        };
+        builder.push(": ").push(record.describe());
+    }
+    msg.channel_id.say(ctx, builder).await?;
+    Ok(())
+}
@@ -0,0 +1,165 @@
+//! An Elo-style rating for werewolf players, updated after each finished game and shown by `!wwrank`.
+
+use {
+    std::{
+        collections::BTreeMap,
+        io,
+    },
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    serenity::{
+        framework::standard::{
+            Args,
+            CommandResult,
+            macros::command,
+        },
+        model::prelude::*,
+        prelude::*,
+        utils::MessageBuilder,
+    },
+    tokio::fs,
+    crate::{
+        Error,
+        atomic_file,
+        paths::Paths,
+        werewolf_history::{
+            Game,
+            History,
+        },
+    },
+};
+
+/// The rating of players who haven't played any games yet.
+pub const INITIAL_RATING: f64 = 1000.0;
+/// The maximum rating change for a player with a role of average difficulty.
+const K_FACTOR: f64 = 32.0;
+/// How many players are shown by `!wwrank`.
+const LEADERBOARD_SIZE: usize = 10;
+
+/// How much harder it is to win with the given role (see [`crate::werewolf_history::role_key`]) than as a villager. Wins with harder roles gain more rating, losses with them lose less. Roles of surviving players aren't known and count as average.
+fn role_difficulty(role: Option<&str>) -> f64 {
+    match role {
+        Some("werewolf") => 1.25, // outnumbered by the village
+        Some("detective") | Some("healer") => 0.9, // the village's night actions make winning easier
+        Some(_) | None => 1.0,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rating {
+    pub rating: f64,
+    pub games: usize,
+}
+
+impl Default for Rating {
+    fn default() -> Rating {
+        Rating {
+            rating: INITIAL_RATING,
+            games: 0,
+        }
+    }
+}
+
+/// The ratings of all players in all guilds, stored as a JSON file next to the game history.
+#[derive(Default, Deserialize, Serialize)]
+pub struct Ratings(BTreeMap<GuildId, BTreeMap<UserId, Rating>>);
+
+impl Ratings {
+    /// Loads the saved ratings, or computes them from the game history if none have been saved yet.
+    pub async fn load(paths: &Paths, history: &History) -> Result<Ratings, Error> {
+        match fs::read_to_string(paths.werewolf_ratings()).await {
+            Ok(buf) => Ok(serde_json::from_str(&buf)?), //TODO use async-json
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Ratings::from_history(history)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn save(&self, paths: &Paths) -> Result<(), Error> {
+        let buf = serde_json::to_vec_pretty(self)?; //TODO use async-json
+        atomic_file::write(&paths.werewolf_ratings(), &buf).await?;
+        Ok(())
+    }
+
+    /// Replays all games in the history.
+    pub fn from_history(history: &History) -> Ratings {
+        let mut ratings = Ratings::default();
+        for game in history.games() {
+            ratings.record_game(game);
+        }
+        ratings
+    }
+
+    /// Updates the ratings of the game's players. The winning and losing teams are rated against each other using their average ratings. If everyone won or everyone lost, only the game counts are updated.
+    pub fn record_game(&mut self, game: &Game) {
+        let guild_ratings = self.0.entry(game.guild).or_default();
+        let team_average = |won: bool| {
+            let team = game.players.iter().filter(|player| game.winners.contains(player) == won).collect::<Vec<_>>();
+            if team.is_empty() { return None }
+            Some(team.iter().map(|player| guild_ratings.get(player).copied().unwrap_or_default().rating).sum::<f64>() / team.len() as f64)
+        };
+        // the winners' surprise at winning, equal to the losers' surprise at losing
+        let upset = team_average(true).and_then(|winners| team_average(false).map(|losers| 1.0 - 1.0 / (1.0 + 10f64.powf((losers - winners) / 400.0))));
+        for &player in &game.players {
+            let rating = guild_ratings.entry(player).or_default();
+            rating.games += 1;
+            if let Some(upset) = upset {
+                let difficulty = role_difficulty(game.role(player));
+                if game.winners.contains(&player) {
+                    rating.rating += K_FACTOR * upset * difficulty;
+                } else {
+                    rating.rating -= K_FACTOR * upset / difficulty;
+                }
+            }
+        }
+    }
+
+    /// Players in the given guild sorted by rating, highest first.
+    pub fn leaderboard(&self, guild: GuildId) -> Vec<(UserId, Rating)> {
+        let mut leaderboard = self.0.get(&guild).into_iter().flatten().map(|(&player, &rating)| (player, rating)).collect::<Vec<_>>();
+        leaderboard.sort_by(|(player1, rating1), (player2, rating2)| rating2.rating.partial_cmp(&rating1.rating).expect("NaN rating").then_with(|| player1.cmp(player2)));
+        leaderboard
+    }
+}
+
+/// Updates the ratings for a finished game that hasn't been added to the history yet.
+pub(crate) async fn record_game(paths: &Paths, history: &History, game: &Game) -> Result<(), Error> {
+    let mut ratings = Ratings::load(paths, history).await?;
+    ratings.record_game(game);
+    ratings.save(paths).await?;
+    Ok(())
+}
+
+/// Shows the players with the highest Werewolf rating on this server, and your own rating.
+#[command]
+#[only_in(guilds)]
+pub async fn wwrank(ctx: &Context, msg: &Message, _: Args) -> CommandResult {
+    let guild_id = msg.guild_id.expect("not in guild but only_in(guilds) passed");
+    let paths = ctx.data.read().await.get::<Paths>().expect("missing paths").clone();
+    let history = History::load(&paths).await?;
+    let ratings = Ratings::load(&paths, &history).await?;
+    let leaderboard = ratings.leaderboard(guild_id);
+    if leaderboard.is_empty() {
+        msg.reply(ctx, "auf diesem Server wurden noch keine Werwölfe-Spiele gespielt").await?;
+        return Ok(())
+    }
+    let mut builder = MessageBuilder::default();
+    builder.push("Werwölfe-Rangliste:");
+    for (rank, (player, rating)) in leaderboard.iter().enumerate().take(LEADERBOARD_SIZE) {
+        builder.push_line("");
+        builder.push(format!("{}. ", rank + 1));
+        builder.mention(player);
+        builder.push(format!(": {:.0} ({} {})", rating.rating, rating.games, if rating.games == 1 { "Spiel" } else { "Spiele" }));
+    }
+    if let Some(rank) = leaderboard.iter().position(|&(player, _)| player == msg.author.id) {
+        if rank >= LEADERBOARD_SIZE {
+            let rating = leaderboard[rank].1;
+            builder.push_line("");
+            builder.push(format!("Du bist auf Platz {} mit {:.0} ({} {}).", rank + 1, rating.rating, rating.games, if rating.games == 1 { "Spiel" } else { "Spielen" }));
+        }
+    }
+    msg.channel_id.say(ctx, builder).await?;
+    Ok(())
+}
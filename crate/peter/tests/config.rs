@@ -0,0 +1,74 @@
+//! Checks that configs with an older layout are upgraded to the current one.
+
+use {
+    serde_json::json,
+    peter::config::migrate,
+};
+
+fn migrated(config: serde_json::Value) -> (bool, serde_json::Value) {
+    let mut config = if let serde_json::Value::Object(config) = config { config } else { panic!("config is not an object") };
+    let migrated = migrate(&mut config).expect("failed to migrate config");
+    (migrated, serde_json::Value::Object(config))
+}
+
+#[test]
+fn from_v1() {
+    let (migrated, config) = migrated(json!({
+        "version": 1,
+        "peter": {"botToken": "abc", "selfAssignableRoles": [1, 2]},
+        "channels": {"ignored": [10], "voice": 11},
+        "twitch": {"clientID": "", "clientSecret": "", "users": {}},
+        "werewolf": {
+            "355761290809180170": {"role": 20, "textChannel": 21},
+            "1": {"role": 30, "textChannel": 31},
+        },
+    }));
+    assert!(migrated);
+    assert_eq!(config, json!({
+        "version": 4,
+        "peter": {"botToken": "abc"},
+        "twitch": {"clientID": "", "clientSecret": "", "users": {}},
+        "guilds": {
+            "355761290809180170": {
+                "channels": {"ignored": [10], "voice": 11},
+                "selfAssignableRoles": [1, 2],
+                "werewolf": [{"role": 20, "textChannel": 21}],
+            },
+            "1": {
+                "werewolf": [{"role": 30, "textChannel": 31}],
+            },
+        },
+    }));
+}
+
+#[test]
+fn from_v3() {
+    let (migrated, config) = migrated(json!({
+        "version": 3,
+        "peter": {"botToken": "abc"},
+        "twitch": {"clientID": "", "clientSecret": "", "users": {}},
+        "guilds": {
+            "1": {"channels": {"log": 5}, "werewolf": {"role": 30, "textChannel": 31}},
+            "2": {"werewolf": null},
+        },
+    }));
+    assert!(migrated);
+    assert_eq!(config, json!({
+        "version": 4,
+        "peter": {"botToken": "abc"},
+        "twitch": {"clientID": "", "clientSecret": "", "users": {}},
+        "guilds": {
+            "1": {"channels": {"log": 5}, "werewolf": [{"role": 30, "textChannel": 31}]},
+            "2": {},
+        },
+    }));
+}
+
+#[test]
+fn current_and_newer() {
+    let current = json!({"version": 4, "peter": {"botToken": "abc"}, "guilds": {"1": {"werewolf": [{"role": 30}]}}});
+    assert_eq!(migrated(current.clone()), (false, current));
+    let mut newer = serde_json::Map::default();
+    newer.insert(format!("version"), json!(5));
+    assert!(migrate(&mut newer).is_err());
+}
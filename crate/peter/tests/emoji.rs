@@ -0,0 +1,76 @@
+//! Checks that emoji in messages are parsed correctly, and without panicking on malformed input.
+
+use {
+    std::env,
+    serenity::model::prelude::*,
+    peter::{
+        emoji::{
+            self,
+            Iter,
+        },
+        paths::Paths,
+    },
+};
+
+fn custom(animated: bool, id: u64, name: &str) -> ReactionType {
+    ReactionType::Custom { animated, id: EmojiId(id), name: Some(name.to_owned()) }
+}
+
+fn emoji_in(text: &str) -> Vec<ReactionType> {
+    Iter::new(&Paths::with_root(env::temp_dir().join("peter-test")), text.to_owned()).collect()
+}
+
+#[test]
+fn valid_custom_emoji() {
+    assert_eq!(emoji::parse_custom_emoji("<:peter:123456789012345678> hallo"), Some((custom(false, 123456789012345678, "peter"), 27)));
+    assert_eq!(emoji::parse_custom_emoji("<a:peter:123456789012345678>"), Some((custom(true, 123456789012345678, "peter"), 28)));
+    assert_eq!(emoji_in("a <:peter:1> b <a:dance:2>"), vec![custom(false, 1, "peter"), custom(true, 2, "dance")]);
+}
+
+#[test]
+fn id_out_of_range() {
+    assert_eq!(emoji::parse_custom_emoji("<:peter:99999999999999999999>"), None);
+    assert_eq!(emoji::parse_custom_emoji("<a:peter:184467440737095516160>"), None);
+    assert_eq!(emoji_in("<:peter:99999999999999999999>"), Vec::<ReactionType>::default());
+}
+
+#[test]
+fn malformed_custom_emoji() {
+    for text in &["<:p:1>", "<:peter:>", "<:peter:12a>", "<b:peter:1>", "<:pe ter:1>", "<:peter:1", ":peter:1>", "<::1>", "<", ""] {
+        assert_eq!(emoji::parse_custom_emoji(text), None, "parsed {:?}", text);
+    }
+    assert_eq!(emoji_in("<:peter:1"), Vec::<ReactionType>::default());
+}
+
+#[test]
+fn emoji_only() {
+    assert!(emoji::is_emoji_only("👍"));
+    assert!(emoji::is_emoji_only(" 👍🏽 <:peter:1>\n:thumbsup: "));
+    assert!(!emoji::is_emoji_only(""));
+    assert!(!emoji::is_emoji_only("  "));
+    assert!(!emoji::is_emoji_only("👍 ok"));
+    assert!(!emoji::is_emoji_only("<:peter:99999999999999999999>"));
+}
+
+fn unicode(emoji: &str) -> ReactionType {
+    ReactionType::Unicode(emoji.to_owned())
+}
+
+#[test]
+fn flags() {
+    assert_eq!(emoji_in("🇩🇪🇫🇷"), vec![unicode("🇩🇪"), unicode("🇫🇷")]);
+    assert_eq!(emoji_in("🏳️‍🌈"), vec![unicode("🏳️‍🌈")]);
+    assert_eq!(emoji_in("🏴󠁧󠁢󠁳󠁣󠁴󠁿"), vec![unicode("🏴󠁧󠁢󠁳󠁣󠁴󠁿")]);
+}
+
+#[test]
+fn families() {
+    assert_eq!(emoji_in("👨‍👩‍👧‍👦👩‍👩‍👦"), vec![unicode("👨‍👩‍👧‍👦"), unicode("👩‍👩‍👦")]);
+    // ZWJ sequences with skin tones which may be missing from the emoji list
+    assert_eq!(emoji_in("👩🏽‍🤝‍👩🏻!"), vec![unicode("👩🏽‍🤝‍👩🏻")]);
+}
+
+#[test]
+fn keycaps() {
+    assert_eq!(emoji_in("1️⃣ 2⃣ #️⃣ 3"), vec![unicode("1️⃣"), unicode("2⃣"), unicode("#️⃣")]);
+}
@@ -0,0 +1,38 @@
+//! Checks the infrastructure shared by hidden-role games.
+
+use {
+    std::collections::HashMap,
+    serenity::model::prelude::*,
+    peter::games::{
+        self,
+        Timeouts,
+    },
+};
+
+#[test]
+fn timeouts() {
+    let mut timeouts = Timeouts::default();
+    assert!(!timeouts.any_active());
+    let first = timeouts.start();
+    let second = timeouts.start();
+    timeouts.cancel(first);
+    assert!(timeouts.is_cancelled(first));
+    assert!(!timeouts.is_cancelled(second));
+    assert!(timeouts.any_active());
+    timeouts.cancel_all();
+    assert!(timeouts.is_cancelled(second));
+    assert!(!timeouts.any_active());
+}
+
+#[test]
+fn votes_for_any_option_type() {
+    let (a, b, c) = (UserId(1), UserId(2), UserId(3));
+    let votes = vec![(a, "ja"), (b, "nein")].into_iter().collect::<HashMap<_, _>>();
+    let delegations = vec![(c, b)].into_iter().collect::<HashMap<_, _>>();
+    assert_eq!(games::vote_weight(&delegations, b), 2);
+    assert_eq!(games::delegation_chain(&delegations, c).collect::<Vec<_>>(), vec![c, b]);
+    assert_eq!(games::vote_counts(&votes, &delegations)["nein"], 2);
+    let (max_votes, leads) = games::vote_leads(&votes, &delegations);
+    assert_eq!(max_votes, 2);
+    assert_eq!(leads, vec!["nein"].into_iter().collect());
+}
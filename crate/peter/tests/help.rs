@@ -0,0 +1,23 @@
+//! Checks that `!help` has something to say about every command.
+
+use peter::{
+    commands::GENERAL_GROUP,
+    setup::SETUP_GROUP,
+    werewolf::WEREWOLF_GROUP,
+};
+
+#[test]
+fn commands_are_documented() {
+    fn check(command: &serenity::framework::standard::Command) {
+        assert!(command.options.desc.is_some(), "missing doc comment on {}", command.options.names[0]);
+        for &sub_command in command.options.sub_commands {
+            check(sub_command);
+        }
+    }
+
+    for group in &[&GENERAL_GROUP, &SETUP_GROUP, &WEREWOLF_GROUP] {
+        for &command in group.options.commands {
+            check(command);
+        }
+    }
+}
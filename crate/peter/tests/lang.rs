@@ -0,0 +1,138 @@
+//! Checks the German grammar of the Werewolf messages.
+
+use {
+    chrono::prelude::*,
+    quantum_werewolf::game::Role,
+    serenity::model::prelude::*,
+    peter::{
+        lang::*,
+        werewolf::RolePreset,
+    },
+};
+
+#[test]
+fn counted_nouns() {
+    assert_eq!(counted(1, Dat, F, "Stimme", "Stimmen"), "einer Stimme");
+    assert_eq!(counted(3, Dat, F, "Stimme", "Stimmen"), "3 Stimmen");
+    assert_eq!(counted(1u64, Nom, F, "Minute", "Minuten"), "eine Minute");
+    assert_eq!(numbered(1, "Werwolf", "Werwölfe"), "1 Werwolf");
+    assert_eq!(numbered(2, "Werwolf", "Werwölfe"), "2 Werwölfe");
+}
+
+#[test]
+fn role_presets() {
+    let preset = |s: &str| s.parse::<RolePreset>().expect("failed to parse role preset");
+    assert_eq!(role_preset(&preset("Werwolf")), "1 Werwolf");
+    assert_eq!(role_preset(&preset("Heiler, 2 Werwölfe, Detektiv")), "2 Werwölfe, Detektiv und Heiler");
+    assert_eq!(role_preset(&preset("Liebespaar, Gerber, Werwolf")), "1 Werwolf, Gerber und Liebespaar");
+    assert_eq!(role_preset(&preset("Werwolf, Jäger und Amor")), "1 Werwolf, Amor und Jäger");
+}
+
+#[test]
+fn variants() {
+    assert_eq!(variants_log(Some(UserId(100)), Some((UserId(101), UserId(102))), None, None), "Gerber: <@100>\nLiebespaar: <@101> und <@102>");
+    assert_eq!(variants_log(None, None, Some(UserId(103)), Some(UserId(104))), "Amor: <@103>\nJäger: <@104>");
+    assert_eq!(lovers_chosen_log(UserId(103), UserId(101), UserId(102), true), "Amor (<@103>) hat <@101> und <@102> verliebt.");
+    assert_eq!(hunter_revenge(UserId(104), UserId(105)), "<@104> war der Jäger und reißt <@105> mit: <@105> wird am Ende des nächsten Tages gelyncht.");
+    assert_eq!(revenge_lynched(UserId(105)), "<@105> wurde vom Jäger mitgerissen und wird anstelle der Abstimmung gelyncht.");
+}
+
+#[test]
+fn game_over_announcement() {
+    assert_eq!(game_over(&[]), "das Spiel ist vorbei: niemand hat gewonnen");
+    assert_eq!(game_over(&[UserId(100)]), "das Spiel ist vorbei: <@100> hat gewonnen");
+    assert_eq!(game_over(&[UserId(100), UserId(101)]), "das Spiel ist vorbei: <@100> <@101> haben gewonnen");
+}
+
+#[test]
+fn phase_embed_votes() {
+    assert_eq!(vote_status(&[]), "noch keine Stimmen");
+    assert_eq!(vote_status(&[(Some(UserId(100)), 2), (None, 1)]), "<@100>: 2 Stimmen\nniemand: 1 Stimme");
+}
+
+#[test]
+fn probability_table_alignment() {
+    assert_eq!(
+        probability_embed_table(&[(format!("Alice"), Ok((50, 25, 25))), (format!("Bob"), Ok((100, 0, 0)))]),
+        "```\nNr. Spieler Dorf  Werwolf  tot\n  1 Alice    50%      25%  25%\n  2 Bob     100%       0%   0%\n```",
+    );
+}
+
+#[test]
+fn afk_announcement() {
+    assert_eq!(afk_limit_reached(UserId(100), 1, None), "<@100> war in einer Phase inaktiv und scheidet aus dem Spiel aus. Bitte ersetzt den Spieler mit `wwreplace`.");
+    assert_eq!(afk_limit_reached(UserId(100), 2, Some(RoleId(5))), "<@100> war in 2 Phasen inaktiv und scheidet aus dem Spiel aus. <@&5> Bitte ersetzt den Spieler mit `wwreplace`.");
+    assert_eq!(afk_limit_reached_lynch(UserId(100), 3), "<@100> war in 3 Phasen inaktiv und scheidet am Ende des Tages aus dem Spiel aus.");
+    assert_eq!(inactive_lynched(UserId(100)), "<@100> war zu lange inaktiv und wird anstelle der Abstimmung gelyncht.");
+}
+
+#[test]
+fn departure() {
+    assert_eq!(player_left(UserId(100), Some(RoleId(5))), "<@100> hat den Server verlassen. <@&5> Bitte ersetzt den Spieler mit `wwreplace`.");
+    assert_eq!(departed_lynched(UserId(100)), "<@100> hat den Server verlassen und wird anstelle der Abstimmung gelyncht.");
+}
+
+#[test]
+fn game_log() {
+    assert_eq!(vote_result_log(&[(Some(UserId(100)), 3)]), "Abstimmungsergebnis:\n<@100>: 3 Stimmen");
+    assert_eq!(anonymous_votes_log(&[(UserId(100), Some(UserId(101))), (UserId(101), None)]), "Geheime Stimmen:\n<@100>: <@101>\n<@101>: niemand");
+    assert_eq!(game_log_title("werwolf", Utc.ymd(2021, 3, 14).and_hms(20, 5, 0)), "Werwolf-Spiel in #werwolf vom 14.03.2021, 20:05 UTC");
+}
+
+#[test]
+fn phase_titles() {
+    assert_eq!(phase_title(true, Some(2), false), "🌙 Nacht 2");
+    assert_eq!(phase_title(false, None, true), "☀️ Tag (vorbei)");
+}
+
+#[test]
+fn hidden_roles() {
+    assert_eq!(deaths(&[(UserId(100), None)]), "<@100> ist tot.");
+    assert_eq!(probability_table(vec![Ok((50, 50, 0)), Err(None)]), "Die aktuelle Wahrscheinlichkeitsverteilung:\n1: 50% Dorf, 50% Werwolf, 0% tot\n2: tot");
+    assert_eq!(probability_embed_table(&[(format!("Alice"), Err(None))]), "```\nNr. Spieler Dorf  Werwolf  tot\n  1 Alice   tot\n```");
+}
+
+#[test]
+fn role_help_matches_role_dm() {
+    assert_eq!(parse_role("Seherin"), Some(Role::Detective));
+    assert_eq!(parse_role("Hexe"), None);
+    assert!(role_help(Role::Villager).starts_with("**Dorfbewohner** (Partei: Dorf)\n"));
+    let dm = quantum_role_dm(&[Role::Werewolf(0), Role::Healer], 3, 0);
+    for role in &[Role::Healer, Role::Werewolf(0)] {
+        let ability = role_ability(*role).expect("role without ability");
+        assert!(dm.contains(&ability));
+        assert!(role_help(*role).contains(&ability));
+    }
+    assert!(!dm.contains(&role_ability(Role::Detective).expect("detective without ability")));
+}
+
+#[test]
+fn game_master() {
+    assert_eq!(game_master_decision(Some(UserId(100))), "Die Spielleitung hat entschieden: <@100> wird gelyncht.");
+    assert_eq!(game_master_decision(None), "Die Spielleitung hat entschieden: heute wird niemand gelyncht.");
+    assert_eq!(game_master_lynch_log(None), "Die Spielleitung überlässt die Entscheidung wieder der Abstimmung.");
+    assert!(!narration("@everyone aufwachen").contains("@everyone"));
+}
+
+#[test]
+fn paused() {
+    assert_eq!(phase_paused(1), "pausiert, danach noch eine Minute");
+    assert_eq!(phase_paused(12), "pausiert, danach noch 12 Minuten");
+}
+
+#[test]
+fn last_words_are_quoted() {
+    assert_eq!(last_words(UserId(100), "Ich war's nicht!"), "Die letzten Worte von <@100>:\n> Ich war's nicht!");
+    assert!(!last_words(UserId(100), "@everyone").contains("@everyone"));
+}
+
+#[test]
+fn help_text() {
+    assert_eq!(help_usage("!", "ping", None), "`!ping`");
+    assert_eq!(help_usage("!", "wwmod vote", Some("<Spieler> <Ziel|nolynch|none>")), "`!wwmod vote <Spieler> <Ziel|nolynch|none>`");
+    assert_eq!(help_examples("?", "iam", &["@Werwölfe"]), "`?iam @Werwölfe`");
+    assert_eq!(help_command_list("!", &["in", "out"]), "`!in`, `!out`");
+    assert_eq!(help_requirements(Permissions::empty(), &[], false, false, false), None);
+    assert_eq!(help_requirements(Permissions::ADMINISTRATOR | Permissions::MANAGE_GUILD, &[], false, true, false).as_deref(), Some("Berechtigung: Administrator und Server verwalten\nnur auf Servern"));
+    assert_eq!(help_requirements(Permissions::empty(), &["Werwölfe-Channel", "Werwölfe-Moderation"], false, false, false).as_deref(), Some("Werwölfe-Channel und Werwölfe-Moderation"));
+}
@@ -0,0 +1,84 @@
+//! Checks how usage statistics are rolled up as they age.
+
+use {
+    chrono::prelude::*,
+    serde_json::json,
+    peter::stats::{
+        Config,
+        Stats,
+    },
+};
+
+#[test]
+fn hours_to_days() {
+    let config = serde_json::from_value::<Config>(json!({"hourlyRetentionHours": 48})).expect("failed to read config");
+    let mut stats = serde_json::from_value::<Stats>(json!({
+        "hourly": {
+            "2021-06-01T05": {"command:ping": 3},
+            "2021-06-08T11": {"command:ping": 2},
+            "2021-06-08T12": {"command:ping": 1, "command:help": 1},
+        },
+        "daily": {
+            "2021-06-08": {"command:ping": 4},
+        },
+    })).expect("failed to read stats");
+    let rollup = stats.rollup(&config, Utc.ymd(2021, 6, 10).and_hms(12, 30, 0));
+    assert_eq!(rollup.hours_rolled_up, 2);
+    assert_eq!(rollup.days_rolled_up, 0);
+    assert_eq!(serde_json::to_value(&stats).expect("failed to write stats"), json!({
+        "hourly": {
+            "2021-06-08T12": {"command:help": 1, "command:ping": 1},
+        },
+        "daily": {
+            "2021-06-01": {"command:ping": 3},
+            "2021-06-08": {"command:ping": 6},
+        },
+        "monthly": {},
+    }));
+}
+
+#[test]
+fn days_to_months() {
+    let config = serde_json::from_value::<Config>(json!({"dailyRetentionDays": 30})).expect("failed to read config");
+    let mut stats = serde_json::from_value::<Stats>(json!({
+        "daily": {
+            "2021-04-30": {"command:ping": 5},
+            "2021-05-10": {"command:ping": 1},
+            "2021-05-11": {"command:ping": 2},
+        },
+        "monthly": {
+            "2021-05": {"command:ping": 10},
+        },
+    })).expect("failed to read stats");
+    let rollup = stats.rollup(&config, Utc.ymd(2021, 6, 10).and_hms(12, 0, 0));
+    assert_eq!(rollup.days_rolled_up, 2);
+    assert_eq!(rollup.months_deleted, 0);
+    assert_eq!(serde_json::to_value(&stats).expect("failed to write stats"), json!({
+        "hourly": {},
+        "daily": {
+            "2021-05-11": {"command:ping": 2},
+        },
+        "monthly": {
+            "2021-04": {"command:ping": 5},
+            "2021-05": {"command:ping": 11},
+        },
+    }));
+}
+
+#[test]
+fn monthly_deletion() {
+    let monthly = json!({
+        "monthly": {
+            "2020-11": {"command:ping": 1},
+            "2020-12": {"command:ping": 2},
+            "2021-01": {"command:ping": 3},
+        },
+    });
+    let now = Utc.ymd(2021, 1, 15).and_hms(0, 0, 0);
+    let mut stats = serde_json::from_value::<Stats>(monthly.clone()).expect("failed to read stats");
+    assert_eq!(stats.rollup(&Config::default(), now).months_deleted, 0); // kept forever by default
+    let config = serde_json::from_value::<Config>(json!({"monthlyRetentionMonths": 1})).expect("failed to read config");
+    let mut stats = serde_json::from_value::<Stats>(monthly).expect("failed to read stats");
+    assert_eq!(stats.rollup(&config, now).months_deleted, 1); // the cutoff is in the previous year
+    assert_eq!(stats.totals("command:", None).get("ping"), Some(&5));
+}
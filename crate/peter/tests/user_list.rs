@@ -0,0 +1,86 @@
+//! Checks that profiles as stored by this and earlier versions of the bot can be read back and are written with the expected schema.
+
+use {
+    std::collections::BTreeSet,
+    serde_json::{
+        Value,
+        json,
+    },
+    peter::user_list::Profile,
+};
+
+fn keys(value: &Value) -> BTreeSet<&str> {
+    value.as_object().expect("profile is not a JSON object").keys().map(String::as_str).collect()
+}
+
+/// Deserializes the given profile and serializes it again, making sure that a second round trip doesn't change anything.
+fn round_trip(profile: Value) -> Value {
+    let written = serde_json::to_value(serde_json::from_value::<Profile>(profile).expect("failed to read profile")).expect("failed to write profile");
+    let rewritten = serde_json::to_value(serde_json::from_value::<Profile>(written.clone()).expect("failed to read written profile")).expect("failed to write profile");
+    assert_eq!(written, rewritten);
+    written
+}
+
+#[test]
+fn current_profile() {
+    let profile = round_trip(json!({
+        "avatar": "https://cdn.discordapp.com/avatars/86841168427495424/0123456789abcdef0123456789abcdef.png?size=1024",
+        "boost_history": [
+            {"start": "2020-01-01T00:00:00Z", "end": "2020-06-01T00:00:00Z"},
+            {"start": "2021-01-01T00:00:00Z", "end": null},
+        ],
+        "bot": false,
+        "discriminator": 4242,
+        "joined": "2016-06-18T12:00:00Z",
+        "nick": "Fenhl",
+        "pending": false,
+        "peter": {"example": {"answer": 42}},
+        "premium_since": "2021-01-01T00:00:00Z",
+        "role_history": [
+            {"timestamp": "2021-02-01T00:00:00Z", "added": ["386753710434287626"], "removed": []},
+        ],
+        "roles": ["386753710434287626"],
+        "snowflake": "86841168427495424",
+        "username": "Fenhl",
+    }));
+    assert_eq!(keys(&profile), vec![
+        "avatar", "boost_history", "bot", "discriminator", "joined", "nick", "pending", "peter", "premium_since", "role_history", "roles", "snowflake", "username",
+    ].into_iter().collect());
+    assert_eq!(profile["peter"], json!({"example": {"answer": 42}}));
+    assert_eq!(profile["boost_history"].as_array().map(Vec::len), Some(2));
+}
+
+#[test]
+fn legacy_profile() {
+    // the fields written before avatars, boosts, screening, and role history were recorded
+    let profile = round_trip(json!({
+        "bot": false,
+        "discriminator": 1,
+        "joined": null,
+        "nick": null,
+        "roles": [],
+        "snowflake": "86841168427495424",
+        "username": "legacy",
+    }));
+    assert_eq!(profile["avatar"], Value::Null);
+    assert_eq!(profile["boost_history"], json!([]));
+    assert_eq!(profile["pending"], json!(false));
+    assert_eq!(profile["role_history"], json!([]));
+    assert!(profile.get("left").is_none());
+    assert!(profile.get("peter").is_none());
+}
+
+#[test]
+fn former_profile() {
+    let profile = round_trip(json!({
+        "bot": false,
+        "discriminator": 1,
+        "joined": "2016-06-18T12:00:00Z",
+        "left": "2021-03-01T00:00:00Z",
+        "nick": null,
+        "roles": [],
+        "snowflake": "86841168427495424",
+        "username": "former",
+    }));
+    assert!(profile.get("left").map_or(false, Value::is_string));
+}
@@ -0,0 +1,284 @@
+//! Plays complete games against the quantum werewolf engine, using the bot's role distribution and vote resolution.
+
+use {
+    std::collections::{
+        HashMap,
+        HashSet,
+    },
+    quantum_werewolf::game::{
+        NightAction,
+        state::*,
+    },
+    rand::{
+        Rng as _,
+        distributions::Standard,
+        thread_rng,
+    },
+    serenity::model::prelude::*,
+    peter::{
+        games,
+        werewolf::{
+            self,
+            Phase,
+            RolePreset,
+            TieBreak,
+            Transition,
+            Vote,
+        },
+        werewolf_history::role_key,
+    },
+};
+
+/// An upper bound on the number of phases a game can take, to make sure tests terminate.
+const MAX_PHASES: usize = 100;
+
+fn players(num_players: usize) -> Vec<UserId> {
+    (1..=num_players as u64).map(UserId).collect()
+}
+
+fn start(players: &[UserId]) -> State<UserId> {
+    let mut state = State::default();
+    if let State::Signups(ref mut signups) = state {
+        for &player in players {
+            assert!(signups.sign_up(player));
+        }
+    } else {
+        panic!("default state is not signups")
+    }
+    if let State::Signups(signups) = state {
+        let roles = werewolf::random_roles(signups.num_players(), &mut thread_rng());
+        signups.start(roles).expect("failed to start game")
+    } else {
+        unreachable!()
+    }
+}
+
+fn alive(state: &State<UserId>) -> Vec<UserId> {
+    let mut alive = state.alive().map(|alive| alive.into_iter().cloned().collect::<Vec<_>>()).unwrap_or_default();
+    alive.sort();
+    alive
+}
+
+/// Plays a game until it ends, with every living player trying to kill the next living player each night, and using `vote` to determine the day's votes from the list of living players. `after_day` is called with the players who were alive during each day and the state after it.
+///
+/// Returns the winners. Also checks that the bot's phase tracking can follow every state the game goes through.
+fn play(players: &[UserId], mut vote: impl FnMut(&[UserId]) -> (HashMap<UserId, Vote>, HashMap<UserId, UserId>), mut after_day: impl FnMut(&[UserId], &State<UserId>)) -> Vec<UserId> {
+    let mut state = start(players);
+    let mut phase = Phase::Signups;
+    for _ in 0..MAX_PHASES {
+        phase = phase.after(&state).unwrap_or_else(|| panic!("state doesn't follow phase {:?}", phase));
+        let living = alive(&state);
+        state = match state {
+            State::Night(night) => {
+                let actions = living.iter().enumerate()
+                    .map(|(idx, &src)| NightAction::Kill(src, living[(idx + 1) % living.len()]))
+                    .collect::<Vec<_>>();
+                night.resolve_nar(&actions)
+            }
+            State::Day(day) => {
+                let (votes, delegations) = vote(&living);
+                let next = werewolf::resolve_votes(day, &votes, &delegations);
+                after_day(&living, &next);
+                next
+            }
+            State::Complete(Complete { winners }) => {
+                assert_eq!(phase, Phase::Ended);
+                return winners.into_iter().collect()
+            }
+            State::Signups(_) => panic!("game returned to signups"),
+        };
+    }
+    panic!("game did not end after {} phases", MAX_PHASES)
+}
+
+fn assert_valid_winners(players: &[UserId], winners: &[UserId]) {
+    assert!(!winners.is_empty(), "game ended without winners");
+    let players = players.iter().collect::<HashSet<_>>();
+    assert!(winners.iter().all(|winner| players.contains(winner)), "winner who didn't play: {:?}", winners);
+}
+
+#[test]
+fn lynch_majority() {
+    for num_players in MIN_PLAYERS..=10 {
+        let players = players(num_players);
+        let mut days = 0;
+        let winners = play(&players, |alive| (alive.iter().map(|&voter| (voter, Vote::Player(alive[0]))).collect(), HashMap::default()), |living, next| {
+            days += 1;
+            let next_alive = alive(next);
+            assert!(!next_alive.contains(&living[0]), "unanimously voted player survived");
+            assert!(next_alive.iter().all(|player| living.contains(player)), "dead player revived");
+        });
+        assert!(days > 0, "game ended without a day");
+        assert_valid_winners(&players, &winners);
+    }
+}
+
+#[test]
+fn always_no_lynch() {
+    let players = players(MIN_PLAYERS.max(5));
+    let winners = play(&players, |alive| (alive.iter().map(|&voter| (voter, Vote::NoLynch)).collect(), HashMap::default()), |living, next| {
+        assert_eq!(alive(next), living, "someone died during a day without a lynch");
+    });
+    assert_valid_winners(&players, &winners);
+}
+
+#[test]
+fn tie_is_no_lynch() {
+    let players = players((MIN_PLAYERS.max(6) + 1) / 2 * 2); // an even number, so the votes can be split evenly
+    let mut state = start(&players);
+    for _ in 0..MAX_PHASES {
+        let living = alive(&state);
+        state = match state {
+            State::Night(night) => night.resolve_nar(&Vec::default()),
+            State::Day(day) => {
+                // nobody acted at night, so everyone is still alive
+                assert_eq!(living, players);
+                let votes = living.iter().enumerate().map(|(idx, &voter)| (voter, Vote::Player(living[idx % 2]))).collect::<HashMap<_, _>>();
+                let next = werewolf::resolve_votes(day, &votes, &HashMap::default());
+                if let State::Night(_) = next {
+                    assert_eq!(alive(&next), living, "someone died on a tied vote");
+                } else {
+                    panic!("game didn't continue after a tied vote")
+                }
+                return
+            }
+            State::Complete(_) => panic!("game ended without any actions"),
+            State::Signups(_) => panic!("game returned to signups"),
+        };
+    }
+    panic!("no day after {} phases", MAX_PHASES)
+}
+
+#[test]
+fn random_tie_break_lynches_a_tied_player() {
+    let players = players(MIN_PLAYERS.max(6));
+    let mut state = start(&players);
+    for _ in 0..MAX_PHASES {
+        let living = alive(&state);
+        state = match state {
+            State::Night(night) => night.resolve_nar(&Vec::default()),
+            State::Day(day) => {
+                if living.len() < 4 || living.len() % 2 != 0 { return } // no tie possible between two candidates
+                let votes = living.iter().enumerate().map(|(idx, &voter)| (voter, Vote::Player(living[idx % 2]))).collect::<HashMap<_, _>>();
+                let next = werewolf::resolve_votes_with(day, &votes, &HashMap::default(), TieBreak::Random);
+                if let State::Night(_) = next {
+                    let next_alive = alive(&next);
+                    let dead = living.iter().filter(|player| !next_alive.contains(player)).copied().collect::<Vec<_>>();
+                    assert_eq!(dead.len(), 1);
+                    assert!(dead[0] == living[0] || dead[0] == living[1]);
+                }
+                return
+            }
+            State::Complete(_) => return,
+            State::Signups(_) => panic!("game returned to signups"),
+        };
+    }
+}
+
+#[test]
+fn delegated_votes_count() {
+    let [a, b, c, d, target, other] = [UserId(1), UserId(2), UserId(3), UserId(4), UserId(5), UserId(6)];
+    let votes = vec![(b, Vote::Player(target)), (c, Vote::Player(other)), (d, Vote::Player(other))].into_iter().collect::<HashMap<_, _>>();
+    // without delegation, `other` leads 2–1
+    let (max_votes, leads) = games::vote_leads(&votes, &HashMap::default());
+    assert_eq!(max_votes, 2);
+    assert_eq!(leads, vec![Vote::Player(other)].into_iter().collect());
+    // a → b makes it a tie
+    let delegations = vec![(a, b)].into_iter().collect::<HashMap<_, _>>();
+    let (max_votes, leads) = games::vote_leads(&votes, &delegations);
+    assert_eq!(max_votes, 2);
+    assert_eq!(leads, vec![Vote::Player(target), Vote::Player(other)].into_iter().collect());
+    // delegations are transitive: target → a → b gives b's vote a weight of 3
+    let delegations = vec![(a, b), (target, a)].into_iter().collect::<HashMap<_, _>>();
+    let (max_votes, leads) = games::vote_leads(&votes, &delegations);
+    assert_eq!(max_votes, 3);
+    assert_eq!(leads, vec![Vote::Player(target)].into_iter().collect());
+}
+
+#[test]
+fn role_presets() {
+    let preset = "2 Werwölfe, Detektiv und Heiler".parse::<RolePreset>().expect("failed to parse role preset");
+    assert_eq!(preset.roles().len(), 4);
+    assert_eq!(preset.min_players(), MIN_PLAYERS.max(5));
+    assert_eq!(preset.to_string(), "2 Werwölfe, Detektiv und Heiler");
+    assert_eq!("werwolf".parse::<RolePreset>().map(|preset| preset.roles().len()), Ok(1));
+    assert!("Detektiv".parse::<RolePreset>().is_err()); // no werewolves
+    assert!("2 Werwölfe, Hexe".parse::<RolePreset>().is_err());
+    assert!("Werwolf, 2 Detektive".parse::<RolePreset>().is_err());
+    let preset = "Werwolf, Gerber und Liebespaar".parse::<RolePreset>().expect("failed to parse role preset with variants");
+    assert_eq!(preset.roles().len(), 1); // variants aren't quantum roles
+    assert_eq!(preset.to_string(), "1 Werwolf, Gerber und Liebespaar");
+    assert!("Werwolf, Gerber, Gerber".parse::<RolePreset>().is_err());
+    let preset = "Werwolf, Amor und Jäger".parse::<RolePreset>().expect("failed to parse role preset with Amor and hunter");
+    assert_eq!(preset.roles().len(), 1);
+    assert_eq!(preset.to_string(), "1 Werwolf, Amor und Jäger");
+    assert!("Werwolf, Liebespaar, Amor".parse::<RolePreset>().is_err()); // Amor chooses the lovers
+    assert!("Werwolf, Jäger, Jägerin".parse::<RolePreset>().is_err());
+}
+
+#[test]
+fn seeded_randomness() {
+    let seed = [42; 32];
+    let other_seed = [43; 32];
+    assert_eq!(werewolf::seed_commitment(&seed).len(), 64);
+    assert_ne!(werewolf::seed_commitment(&seed), werewolf::seed_commitment(&other_seed));
+    // the same seed and purpose always give the same decisions
+    let roles = |num_players| werewolf::random_roles(num_players, &mut werewolf::seeded_rng(&seed, "roles")).into_iter().map(role_key).collect::<Vec<_>>();
+    for num_players in MIN_PLAYERS..20 {
+        assert_eq!(roles(num_players), roles(num_players));
+    }
+    let numbers = |seed, purpose| werewolf::seeded_rng(seed, purpose).sample_iter(Standard).take(8).collect::<Vec<u64>>();
+    assert_eq!(numbers(&seed, "lynch 1"), numbers(&seed, "lynch 1"));
+    assert_ne!(numbers(&seed, "lynch 1"), numbers(&seed, "lynch 2"));
+    assert_ne!(numbers(&seed, "roles"), numbers(&other_seed, "roles"));
+}
+
+#[test]
+fn phase_transitions() {
+    let mut phase = Phase::Signups;
+    for &(transition, expected) in &[
+        (Transition::Start, Phase::Night(1)),
+        (Transition::Dawn, Phase::Day(1)),
+        (Transition::Dusk, Phase::Night(2)),
+        (Transition::Dawn, Phase::Day(2)),
+        (Transition::End, Phase::Ended),
+        (Transition::Reset, Phase::Signups),
+    ] {
+        phase = phase.next(transition).expect("valid transition rejected");
+        assert_eq!(phase, expected);
+    }
+    assert_eq!(Phase::Day(3).number(), Some(3));
+    assert_eq!(Phase::Ended.number(), None);
+    // cancelling is possible at any time
+    assert_eq!(Phase::Night(2).next(Transition::Reset), Some(Phase::Signups));
+}
+
+#[test]
+fn invalid_phase_transitions() {
+    assert_eq!(Phase::Signups.next(Transition::Dawn), None);
+    assert_eq!(Phase::Signups.next(Transition::End), None);
+    assert_eq!(Phase::Night(1).next(Transition::Dusk), None);
+    assert_eq!(Phase::Night(1).next(Transition::Start), None);
+    assert_eq!(Phase::Day(1).next(Transition::Dawn), None);
+    assert_eq!(Phase::Ended.next(Transition::Start), None);
+    assert_eq!(Phase::Ended.next(Transition::End), None);
+}
+
+#[test]
+fn phase_follows_state() {
+    let state = start(&players(MIN_PLAYERS));
+    let first = match state {
+        State::Night(_) => Phase::Night(1),
+        State::Day(_) => Phase::Day(1),
+        State::Signups(_) | State::Complete(_) => panic!("game didn't start"),
+    };
+    assert_eq!(Phase::Signups.after(&state), Some(first));
+    assert_eq!(first.after(&state), Some(first)); // no state change, no phase change
+    // a game can only start after signups reopen, so a phase left at the end of the previous game is an error instead of a panic
+    assert_eq!(Phase::Ended.after(&state), None);
+    assert_eq!(Phase::Ended.after(&State::default()), Some(Phase::Signups));
+    assert_eq!(Phase::Signups.after(&State::Complete(Complete { winners: Default::default() })), None);
+    // cancelling resets the phase from anywhere
+    assert_eq!(Phase::Day(2).after(&State::default()), Some(Phase::Signups));
+    assert_eq!(Phase::Night(3).after(&State::Complete(Complete { winners: Default::default() })), Some(Phase::Ended));
+}
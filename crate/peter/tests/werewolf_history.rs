@@ -0,0 +1,61 @@
+//! Checks the win rates computed from the werewolf game history.
+
+use {
+    quantum_werewolf::game::Role,
+    serde_json::json,
+    serenity::model::prelude::*,
+    peter::{
+        lang::role_stats_label,
+        werewolf_history::{
+            History,
+            Record,
+            parse_role_key,
+            role_key,
+        },
+    },
+};
+
+#[test]
+fn player_stats() {
+    let history = serde_json::from_value::<History>(json!([
+        {
+            "guild": "1", "channel": "10",
+            "start": "2021-01-01T20:00:00Z", "end": "2021-01-01T21:00:00Z",
+            "players": ["100", "101", "102"],
+            "winners": ["100"],
+            "deaths": [{"player": "101", "role": "werewolf", "time": "2021-01-01T20:30:00Z"}],
+        },
+        {
+            "guild": "1", "channel": "10",
+            "start": "2021-01-02T20:00:00Z", "end": "2021-01-02T21:00:00Z",
+            "players": ["100", "101", "102"],
+            "winners": ["101", "102"],
+            "deaths": [{"player": "100", "role": "werewolf", "time": "2021-01-02T20:30:00Z"}],
+        },
+        {
+            "guild": "2", "channel": "20",
+            "start": "2021-01-03T20:00:00Z", "end": "2021-01-03T21:00:00Z",
+            "players": ["100", "103", "104"],
+            "winners": ["100"],
+            "deaths": [],
+        },
+    ])).expect("failed to read history");
+    let (overall, by_role) = history.player_stats(UserId(100), Some(GuildId(1)));
+    assert_eq!(overall, Record { games: 2, wins: 1 });
+    assert_eq!(by_role.get(&None), Some(&Record { games: 1, wins: 1 }));
+    assert_eq!(by_role.get(&Some("werewolf")), Some(&Record { games: 1, wins: 0 }));
+    let (overall, _) = history.player_stats(UserId(100), None);
+    assert_eq!(overall, Record { games: 3, wins: 2 });
+}
+
+#[test]
+fn role_labels() {
+    for role in vec![Role::Detective, Role::Healer, Role::Villager, Role::Werewolf(2)] {
+        let key = role_key(role);
+        assert_eq!(parse_role_key(key).map(role_key), Some(key));
+    }
+    assert_eq!(parse_role_key("witch"), None);
+    assert_eq!(role_stats_label(parse_role_key("werewolf")), "Werwolf");
+    assert_eq!(role_stats_label(parse_role_key("detective")), "Detektiv");
+    assert_eq!(role_stats_label(None), "überlebt (Rolle unbekannt)");
+}
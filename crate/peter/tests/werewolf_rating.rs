@@ -0,0 +1,53 @@
+//! Checks the player ratings computed from the werewolf game history.
+
+use {
+    serde_json::json,
+    serenity::model::prelude::*,
+    peter::{
+        werewolf_history::History,
+        werewolf_rating::{
+            INITIAL_RATING,
+            Ratings,
+        },
+    },
+};
+
+#[test]
+fn ratings() {
+    let history = serde_json::from_value::<History>(json!([
+        {
+            "guild": "1", "channel": "10",
+            "start": "2021-01-01T20:00:00Z", "end": "2021-01-01T21:00:00Z",
+            "players": ["100", "101", "102", "103"],
+            "winners": ["100", "101"],
+            "deaths": [{"player": "100", "role": "werewolf", "time": "2021-01-01T20:30:00Z"}],
+        },
+        {
+            "guild": "1", "channel": "10",
+            "start": "2021-01-02T20:00:00Z", "end": "2021-01-02T21:00:00Z",
+            "players": ["100", "101", "102"],
+            "winners": [],
+            "deaths": [],
+        },
+        {
+            "guild": "2", "channel": "20",
+            "start": "2021-01-03T20:00:00Z", "end": "2021-01-03T21:00:00Z",
+            "players": ["100", "104"],
+            "winners": ["104"],
+            "deaths": [],
+        },
+    ])).expect("failed to read history");
+    let ratings = Ratings::from_history(&history);
+    let leaderboard = ratings.leaderboard(GuildId(1));
+    assert_eq!(leaderboard.iter().map(|&(player, _)| player).collect::<Vec<_>>(), vec![UserId(100), UserId(101), UserId(102), UserId(103)]);
+    assert_eq!(leaderboard[0].1.games, 2);
+    assert_eq!(leaderboard[3].1.games, 1);
+    // equally rated teams: half of the K factor, scaled by role difficulty
+    assert!((leaderboard[0].1.rating - (INITIAL_RATING + 20.0)).abs() < 1e-9);
+    assert!((leaderboard[1].1.rating - (INITIAL_RATING + 16.0)).abs() < 1e-9);
+    assert!((leaderboard[2].1.rating - (INITIAL_RATING - 16.0)).abs() < 1e-9);
+    let leaderboard = ratings.leaderboard(GuildId(2));
+    assert_eq!(leaderboard[0].0, UserId(104));
+    assert!((leaderboard[1].1.rating - (INITIAL_RATING - 16.0)).abs() < 1e-9);
+    assert!(ratings.leaderboard(GuildId(3)).is_empty());
+}